@@ -48,8 +48,10 @@ function insertNestedParam(map, key, param) {
 }
 
 function main() {
-    const sourceFile = path.join(__dirname, './server/src/server.rs');
-    const content = fs.readFileSync(sourceFile, 'utf8');
+    const sourceFiles = ['./server/src/server.rs', './server/src/databases.rs'];
+    const content = sourceFiles
+        .map(sourceFile => fs.readFileSync(path.join(__dirname, sourceFile), 'utf8'))
+        .join('\n');
 
     const startHandleRe = /\/\*\*/;
     const endHandleRe = /\*\//;
@@ -0,0 +1,273 @@
+use rocksdb_client_rust::RocksDBClient;
+use std::collections::HashMap;
+use std::process;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "RocksDB Bench", about = "Drives a mix of requests against a RocksDB server and reports latencies.")]
+struct Opt {
+    #[structopt(long, help = "Server host", default_value = "127.0.0.1")]
+    host: String,
+    #[structopt(long, help = "Server port", default_value = "12345")]
+    port: u16,
+    #[structopt(long, env = "ROCKSDB_TOKEN", hide_env_values = true, help = "Token for servers started with --token")]
+    token: Option<String>,
+    #[structopt(long, help = "Concurrent connections, one thread each", default_value = "4")]
+    connections: usize,
+    #[structopt(long, help = "Seconds to run", default_value = "10")]
+    duration: u64,
+    #[structopt(long, help = "Target requests per second across all connections, 0 for as fast as possible", default_value = "0")]
+    qps: u64,
+    #[structopt(long, help = "Weights of the operations, e.g. put=40,get=40,scan=10,merge=10", default_value = "put=50,get=50")]
+    mix: Mix,
+    #[structopt(long, help = "Number of distinct keys", default_value = "10000")]
+    keys: u64,
+    #[structopt(long, help = "Bytes of filler in each put value", default_value = "100")]
+    value_size: usize,
+    #[structopt(long, help = "Prefix of the benchmark keys", default_value = "bench:")]
+    prefix: String,
+    #[structopt(long, help = "Entries read by each scan", default_value = "10")]
+    scan_limit: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Op {
+    Put,
+    Get,
+    Scan,
+    Merge,
+}
+
+impl FromStr for Op {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "put" => Ok(Op::Put),
+            "get" => Ok(Op::Get),
+            "scan" => Ok(Op::Scan),
+            "merge" => Ok(Op::Merge),
+            _ => Err(format!("Unknown operation {}, expected put, get, scan or merge", s)),
+        }
+    }
+}
+
+impl Op {
+    fn name(&self) -> &'static str {
+        match self {
+            Op::Put => "put",
+            Op::Get => "get",
+            Op::Scan => "scan",
+            Op::Merge => "merge",
+        }
+    }
+}
+
+/// Operations with their relative weights.
+#[derive(Debug, Clone)]
+struct Mix {
+    weights: Vec<(Op, u32)>,
+    total: u32,
+}
+
+impl FromStr for Mix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = Vec::new();
+        for part in s.split(',') {
+            let (op, weight) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Expected OP=WEIGHT, got {}", part))?;
+            let weight = weight
+                .parse::<u32>()
+                .map_err(|_| format!("Invalid weight in {}", part))?;
+            if weight > 0 {
+                weights.push((op.trim().parse::<Op>()?, weight));
+            }
+        }
+        let total = weights.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return Err("The mix needs at least one operation with a positive weight".to_string());
+        }
+        Ok(Mix { weights, total })
+    }
+}
+
+impl Mix {
+    fn pick(&self, roll: u32) -> Op {
+        let mut roll = roll % self.total;
+        for (op, weight) in &self.weights {
+            if roll < *weight {
+                return *op;
+            }
+            roll -= weight;
+        }
+        unreachable!("roll is below the total weight")
+    }
+}
+
+/// Xorshift generator; request keys only need to be spread, not unpredictable.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    latencies: HashMap<Op, Vec<Duration>>,
+    errors: HashMap<Op, u64>,
+    first_error: Option<String>,
+}
+
+impl Stats {
+    fn merge(&mut self, other: Stats) {
+        for (op, latencies) in other.latencies {
+            self.latencies.entry(op).or_default().extend(latencies);
+        }
+        for (op, errors) in other.errors {
+            *self.errors.entry(op).or_default() += errors;
+        }
+        if self.first_error.is_none() {
+            self.first_error = other.first_error;
+        }
+    }
+}
+
+fn run_worker(opt: &Opt, worker: usize, deadline: Instant) -> Stats {
+    let mut builder = RocksDBClient::builder().host(opt.host.clone()).port(opt.port);
+    if let Some(token) = &opt.token {
+        builder = builder.token(token.clone());
+    }
+    let mut client = builder.build();
+
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+    let mut rng = Rng::new(seed ^ (worker as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    // Each connection sends its share of --qps at a fixed interval.
+    let interval = (opt.qps > 0).then(|| Duration::from_secs_f64(opt.connections as f64 / opt.qps as f64));
+    let filler = "x".repeat(opt.value_size);
+
+    let mut stats = Stats::default();
+    let mut next_at = Instant::now();
+    while Instant::now() < deadline {
+        if let Some(interval) = interval {
+            let now = Instant::now();
+            if next_at > now {
+                thread::sleep(next_at - now);
+            }
+            next_at += interval;
+        }
+
+        let op = opt.mix.pick(rng.next() as u32);
+        let key = format!("{}{:010}", opt.prefix, rng.next() % opt.keys.max(1));
+        let started = Instant::now();
+        // Values are JSON objects, so merges into keys written by puts succeed.
+        let result = match op {
+            Op::Put => client.put(key, format!("{{\"v\":\"{}\"}}", filler), None, None),
+            Op::Get => client.get(key, None, None, None),
+            Op::Scan => client.get_range_map(Some(opt.prefix.clone()), Some(key), None, Some(opt.scan_limit), None),
+            Op::Merge => client.merge(key, format!("{{\"m\":{}}}", rng.next() % 1000), None, None, None),
+        };
+        let elapsed = started.elapsed();
+
+        match result {
+            Ok(_) => stats.latencies.entry(op).or_default().push(elapsed),
+            Err(e) => {
+                *stats.errors.entry(op).or_default() += 1;
+                stats.first_error.get_or_insert_with(|| format!("{}: {}", op.name(), e));
+            }
+        }
+    }
+    stats
+}
+
+fn percentile(sorted: &[Duration], percent: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let index = ((sorted.len() - 1) as f64 * percent / 100.0).round() as usize;
+    sorted[index]
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    if opt.connections == 0 {
+        eprintln!("--connections must be at least 1");
+        process::exit(1);
+    }
+
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(opt.duration);
+    let mut stats = Stats::default();
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..opt.connections)
+            .map(|worker| {
+                let opt = &opt;
+                scope.spawn(move || run_worker(opt, worker, deadline))
+            })
+            .collect();
+        for worker in workers {
+            stats.merge(worker.join().expect("worker thread panicked"));
+        }
+    });
+    let elapsed = started.elapsed().as_secs_f64();
+
+    println!(
+        "{:<6} {:>10} {:>8} {:>10} {:>9} {:>9} {:>9} {:>9}",
+        "op", "requests", "errors", "req/s", "p50 ms", "p90 ms", "p99 ms", "max ms"
+    );
+    let mut ops: Vec<Op> = stats.latencies.keys().chain(stats.errors.keys()).copied().collect();
+    ops.sort();
+    ops.dedup();
+    let mut total_requests = 0;
+    let mut total_errors = 0;
+    for op in ops {
+        let mut latencies = stats.latencies.remove(&op).unwrap_or_default();
+        latencies.sort();
+        let errors = stats.errors.get(&op).copied().unwrap_or(0);
+        let requests = latencies.len() as u64 + errors;
+        total_requests += requests;
+        total_errors += errors;
+        println!(
+            "{:<6} {:>10} {:>8} {:>10.1} {:>9.3} {:>9.3} {:>9.3} {:>9.3}",
+            op.name(),
+            requests,
+            errors,
+            requests as f64 / elapsed,
+            millis(percentile(&latencies, 50.0)),
+            millis(percentile(&latencies, 90.0)),
+            millis(percentile(&latencies, 99.0)),
+            millis(latencies.last().copied().unwrap_or_default()),
+        );
+    }
+    println!(
+        "total  {:>10} {:>8} {:>10.1} over {:.1}s with {} connections",
+        total_requests,
+        total_errors,
+        total_requests as f64 / elapsed,
+        elapsed,
+        opt.connections
+    );
+
+    if let Some(first_error) = stats.first_error {
+        eprintln!("First error: {}", first_error);
+        process::exit(1);
+    }
+}
@@ -1,6 +1,7 @@
 use structopt::StructOpt;
 use log::{error, info};
 use rocksdb_client_rust::RocksDBClient;
+use std::process;
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "RocksDB Cli Client", about = "A simple RocksDB cli client.")]
@@ -15,6 +16,25 @@ struct CommonOpts {
     host: String,
     #[structopt(long, help = "Server port", default_value = "12345")]
     port: u16,
+    #[structopt(long, env = "ROCKSDB_TOKEN", hide_env_values = true, help = "Token for servers started with --token")]
+    token: Option<String>,
+}
+
+impl CommonOpts {
+    fn connect(self) -> RocksDBClient {
+        let mut builder = RocksDBClient::builder().host(self.host).port(self.port);
+        if let Some(token) = self.token {
+            builder = builder.token(token);
+        }
+        builder.build()
+    }
+}
+
+/// Reports a failed request and exits with status 1, so scripts can tell it failed.
+fn fail(message: String) -> ! {
+    error!("{}", message);
+    eprintln!("{}", message);
+    process::exit(1);
 }
 
 #[derive(StructOpt, Debug)]
@@ -101,92 +121,92 @@ fn main() {
 
     match opt.cmd {
         Command::Put { common, key, value } => {
-            let mut client = RocksDBClient::new(common.host, common.port);
+            let mut client = common.connect();
             info!("Sending PUT request: key={}, value={}", key, value);
             match client.put(key, value, None, None) {
                 Ok(_) => println!("PUT request successful"),
-                Err(e) => error!("Failed to put value: {}", e),
+                Err(e) => fail(format!("Failed to put value: {}", e)),
             }
         }
         Command::Get { common, key } => {
-            let mut client = RocksDBClient::new(common.host, common.port);
+            let mut client = common.connect();
             info!("Sending GET request: key={}", key);
             match client.get(key, None, None, None) {
                 Ok(Some(value)) => println!("result: {}", value),
                 Ok(None) => println!("GET request successful: key not found"),
-                Err(e) => error!("Failed to get value: {}", e),
+                Err(e) => fail(format!("Failed to get value: {}", e)),
             }
         }
         Command::Delete { common, key } => {
-            let mut client = RocksDBClient::new(common.host, common.port);
+            let mut client = common.connect();
             info!("Sending DELETE request: key={}", key);
             match client.delete(key, None, None) {
                 Ok(_) => println!("DELETE request successful"),
-                Err(e) => error!("Failed to delete key: {}", e),
+                Err(e) => fail(format!("Failed to delete key: {}", e)),
             }
         }
         Command::Merge { common, key, value } => {
-            let mut client = RocksDBClient::new(common.host, common.port);
+            let mut client = common.connect();
             info!("Sending MERGE request: key={}, value={}", key, value);
-            match client.merge(key, value, None, None) {
+            match client.merge(key, value, None, None, None) {
                 Ok(_) => println!("MERGE request successful"),
-                Err(e) => error!("Failed to merge value: {}", e),
+                Err(e) => fail(format!("Failed to merge value: {}", e)),
             }
         }
         Command::ListColumnFamilies { common } => {
-            let mut client = RocksDBClient::new(common.host, common.port);
+            let mut client = common.connect();
             info!("Sending LIST_COLUMN_FAMILIES request");
             match client.list_column_families() {
                 Ok(families) => println!("result: {:?}", families),
-                Err(e) => error!("Failed to list column families: {}", e),
+                Err(e) => fail(format!("Failed to list column families: {}", e)),
             }
         }
         Command::CreateColumnFamily { common, name } => {
-            let mut client = RocksDBClient::new(common.host, common.port);
+            let mut client = common.connect();
             info!("Sending CREATE_COLUMN_FAMILY request: name={}", name);
             match client.create_column_family(name) {
-                Ok(_) => println!("CREATE_COLUMN_FAMILY request successful"),
-                Err(e) => error!("Failed to create column family: {}", e),
+                Ok(result) => println!("CREATE_COLUMN_FAMILY request successful: {}", result.unwrap_or_default()),
+                Err(e) => fail(format!("Failed to create column family: {}", e)),
             }
         }
         Command::DropColumnFamily { common, name } => {
-            let mut client = RocksDBClient::new(common.host, common.port);
+            let mut client = common.connect();
             info!("Sending DROP_COLUMN_FAMILY request: name={}", name);
             match client.drop_column_family(name) {
-                Ok(_) => println!("DROP_COLUMN_FAMILY request successful"),
-                Err(e) => error!("Failed to drop column family: {}", e),
+                Ok(result) => println!("DROP_COLUMN_FAMILY request successful: {}", result.unwrap_or_default()),
+                Err(e) => fail(format!("Failed to drop column family: {}", e)),
             }
         }
         Command::CompactRange { common, start, end } => {
-            let mut client = RocksDBClient::new(common.host, common.port);
+            let mut client = common.connect();
             info!("Sending COMPACT_RANGE request: start={:?}, end={:?}", start, end);
             match client.compact_range(start, end, None) {
                 Ok(_) => println!("COMPACT_RANGE request successful"),
-                Err(e) => error!("Failed to compact range: {}", e),
+                Err(e) => fail(format!("Failed to compact range: {}", e)),
             }
         }
         Command::BeginTransaction { common } => {
-            let mut client = RocksDBClient::new(common.host, common.port);
+            let mut client = common.connect();
             info!("Sending BEGIN_TRANSACTION request");
             match client.begin_transaction() {
                 Ok(_) => println!("BEGIN_TRANSACTION request successful"),
-                Err(e) => error!("Failed to begin transaction: {}", e),
+                Err(e) => fail(format!("Failed to begin transaction: {}", e)),
             }
         }
         Command::CommitTransaction { common } => {
-            let mut client = RocksDBClient::new(common.host, common.port);
+            let mut client = common.connect();
             info!("Sending COMMIT_TRANSACTION request");
             match client.commit_transaction() {
                 Ok(_) => println!("COMMIT_TRANSACTION request successful"),
-                Err(e) => error!("Failed to commit transaction: {}", e),
+                Err(e) => fail(format!("Failed to commit transaction: {}", e)),
             }
         }
         Command::RollbackTransaction { common } => {
-            let mut client = RocksDBClient::new(common.host, common.port);
+            let mut client = common.connect();
             info!("Sending ROLLBACK_TRANSACTION request");
             match client.rollback_transaction() {
                 Ok(_) => println!("ROLLBACK_TRANSACTION request successful"),
-                Err(e) => error!("Failed to rollback transaction: {}", e),
+                Err(e) => fail(format!("Failed to rollback transaction: {}", e)),
             }
         }
     }
@@ -1,7 +1,14 @@
-use std::collections::HashMap;
-use std::io::{BufRead, BufReader, Write};
-use std::net::TcpStream;
-use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::future::Future;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use async_std::task::{spawn_blocking, JoinHandle};
+use futures::Stream;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Request {
@@ -17,18 +24,110 @@ pub struct Request {
     restore_path: Option<String>,
     iterator_id: Option<usize>,
     txn: Option<bool>,
+    db: Option<String>,
+    token: Option<String>,
+    idempotency_key: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Response {
     pub success: bool,
+    #[serde(default, deserialize_with = "deserialize_result")]
     pub result: Option<String>,
+    #[serde(default)]
+    pub error_code: Option<String>,
+    /// RocksDB's category of a `DB_ERROR`, e.g. `Busy` or `TryAgain` for a transaction conflict worth retrying.
+    #[serde(default)]
+    pub error_kind: Option<String>,
+    #[serde(default)]
+    pub binary_length: Option<usize>,
+    /// Raw bytes sent after the response line, see `scan_binary`.
+    #[serde(skip)]
+    pub binary: Option<Vec<u8>>,
+    /// Set when the server returned the remembered result of an earlier attempt, see `with_idempotency_key`.
+    #[serde(default)]
+    pub replayed: Option<bool>,
+}
+
+/// A raw key and value returned by `scan_binary`.
+pub type BinaryEntry = (Vec<u8>, Vec<u8>);
+
+/// Splits a big-endian `u32` length and that many bytes off the front of `data`.
+fn take_length_prefixed(data: &mut &[u8]) -> Result<Vec<u8>, String> {
+    let truncated = || "Truncated binary response".to_string();
+    let (length, rest) = data.split_first_chunk::<4>().ok_or_else(truncated)?;
+    let length = u32::from_be_bytes(*length) as usize;
+    if rest.len() < length {
+        return Err(truncated());
+    }
+    let (part, rest) = rest.split_at(length);
+    *data = rest;
+    Ok(part.to_vec())
+}
+
+/// Results are strings, except `get` with `decode=json`; those are kept as JSON text.
+fn deserialize_result<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        None => None,
+        Some(Value::String(result)) => Some(result),
+        Some(result) => Some(result.to_string()),
+    })
 }
 
 pub struct RequestHandler {
     host: String,
     port: u16,
     connection: Option<TcpStream>,
+    db: Option<String>,
+    cf_name: Option<String>,
+    token: Option<String>,
+    idempotency_key: Option<String>,
+    low_priority: bool,
+    timeout: Option<Duration>,
+    reconnect: bool,
+    retry_policy: RetryPolicy,
+    keepalive: Option<Keepalive>,
+    last_used: Instant,
+}
+
+/// Checks a connection that sat idle with a `ping` before the next request is sent on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keepalive {
+    /// Idle time after which the connection is checked.
+    pub interval: Duration,
+    /// How long to wait for the answer before the connection counts as dead.
+    pub timeout: Duration,
+}
+
+/// How often a request that failed on the connection is tried again, doubling the delay each time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// No retries beyond the single reconnect of `reconnect`.
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            base_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn delay(&self, retry: u32) -> Duration {
+        self.base_delay.saturating_mul(2u32.saturating_pow(retry))
+    }
+}
+
+/// Whether an error came from the connection rather than from the server's answer, so that the same
+/// request may succeed when sent again.
+fn is_transient(error: &str) -> bool {
+    ["Connection error", "Send error", "Receive error"]
+        .iter()
+        .any(|prefix| error.starts_with(prefix))
 }
 
 impl RequestHandler {
@@ -37,13 +136,57 @@ impl RequestHandler {
             host,
             port,
             connection: None,
+            db: None,
+            cf_name: None,
+            token: None,
+            idempotency_key: None,
+            low_priority: false,
+            timeout: None,
+            reconnect: true,
+            retry_policy: RetryPolicy::default(),
+            keepalive: None,
+            last_used: Instant::now(),
+        }
+    }
+
+    pub fn set_keepalive(&mut self, keepalive: Option<Keepalive>) {
+        self.keepalive = keepalive;
+    }
+
+    /// A handler with the same settings that opens its own connection on first use.
+    fn detached(&self) -> Self {
+        Self {
+            host: self.host.clone(),
+            port: self.port,
+            connection: None,
+            db: self.db.clone(),
+            cf_name: self.cf_name.clone(),
+            token: self.token.clone(),
+            idempotency_key: None,
+            low_priority: false,
+            timeout: self.timeout,
+            reconnect: self.reconnect,
+            retry_policy: self.retry_policy,
+            keepalive: self.keepalive,
+            last_used: Instant::now(),
         }
     }
 
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    /// Sends later requests to the named database; `None` selects the default one.
+    pub fn use_database(&mut self, db: Option<String>) {
+        self.db = db;
+    }
+
     fn get_connection(&mut self) -> Result<&mut TcpStream, String> {
-        if self.connection.is_none() {
-            self.reconnect()?;
-        } else if self.connection.as_ref().unwrap().peer_addr().is_err() {
+        let alive = self
+            .connection
+            .as_ref()
+            .is_some_and(|conn| conn.peer_addr().is_ok());
+        if !alive {
             self.reconnect()?;
         }
         self.connection.as_mut().ok_or_else(|| "Failed to acquire connection".to_string())
@@ -51,19 +194,126 @@ impl RequestHandler {
 
     fn reconnect(&mut self) -> Result<(), String> {
         let addr = format!("{}:{}", self.host, self.port);
-        let stream = TcpStream::connect(&addr).map_err(|e| format!("Connection error: {}", e))?;
+        let stream = match self.timeout {
+            Some(timeout) => {
+                let socket_addr = addr
+                    .to_socket_addrs()
+                    .map_err(|e| format!("Connection error: {}", e))?
+                    .next()
+                    .ok_or_else(|| format!("Connection error: could not resolve {}", addr))?;
+                TcpStream::connect_timeout(&socket_addr, timeout)
+            }
+            None => TcpStream::connect(&addr),
+        }
+        .map_err(|e| format!("Connection error: {}", e))?;
+
+        stream
+            .set_read_timeout(self.timeout)
+            .and_then(|_| stream.set_write_timeout(self.timeout))
+            .map_err(|e| format!("Connection error: {}", e))?;
         self.connection = Some(stream);
         Ok(())
     }
 
-    pub fn send_request(&mut self, request: Request) -> Result<Response, String> {
+    /// Sends `key` as the idempotency key of the next request only.
+    pub fn set_idempotency_key(&mut self, key: String) {
+        self.idempotency_key = Some(key);
+    }
+
+    /// Sends the next request with `options.low_priority`.
+    pub fn set_low_priority(&mut self) {
+        self.low_priority = true;
+    }
+
+    fn fill_defaults(&mut self, request: &mut Request) {
+        if request.idempotency_key.is_none() {
+            request.idempotency_key = self.idempotency_key.take();
+        }
+        if std::mem::take(&mut self.low_priority) {
+            request
+                .options
+                .get_or_insert_with(HashMap::new)
+                .insert("low_priority".to_string(), "true".to_string());
+        }
+        if request.db.is_none() {
+            request.db = self.db.clone();
+        }
+        if request.token.is_none() {
+            request.token = self.token.clone();
+        }
+        // The server forgets `use_cf` when the connection drops, so the selection is sent explicitly.
+        if request.cf_name.is_none() && request.action != "use_cf" {
+            request.cf_name = self.cf_name.clone();
+        }
+    }
+
+    /// Sends a request and reads its response, retrying connection failures as the retry policy allows.
+    ///
+    /// Errors answered by the server, such as `UNAUTHORIZED`, arrive as a `Response` and are never retried.
+    /// A retry after the request was sent may execute it twice; send an idempotency key to prevent that.
+    pub fn send_request(&mut self, mut request: Request) -> Result<Response, String> {
+        self.fill_defaults(&mut request);
         let request_bytes = serde_json::to_vec(&request).map_err(|e| format!("Serialization error: {}", e))?;
 
+        let mut retry = 0;
+        loop {
+            match self.exchange(&request_bytes) {
+                Err(e) if is_transient(&e) && retry < self.retry_policy.max_retries => {
+                    std::thread::sleep(self.retry_policy.delay(retry));
+                    retry += 1;
+                }
+                received => return received,
+            }
+        }
+    }
+
+    /// Drops the connection if it has been idle for the keepalive interval and does not answer a `ping`
+    /// in time, so that a dead connection is replaced before a real request is sent on it.
+    fn check_idle_connection(&mut self) {
+        let Some(keepalive) = self.keepalive else {
+            return;
+        };
+        if self.last_used.elapsed() < keepalive.interval {
+            return;
+        }
+        let Some(conn) = self.connection.as_mut() else {
+            return;
+        };
+
+        // The token goes along so that servers started with --token count the ping as authenticated.
+        let mut ping = RequestBuilder::new("ping").build();
+        ping.token = self.token.clone();
+        let Ok(mut ping_bytes) = serde_json::to_vec(&ping) else {
+            return;
+        };
+        ping_bytes.push(b'\n');
+
+        let alive = conn.set_read_timeout(Some(keepalive.timeout)).is_ok()
+            && conn.write_all(&ping_bytes).is_ok()
+            && Self::read_response(BufReader::new(&mut *conn)).is_ok()
+            && conn.set_read_timeout(self.timeout).is_ok();
+        if !alive {
+            self.connection = None;
+        }
+    }
+
+    fn exchange(&mut self, request_bytes: &[u8]) -> Result<Response, String> {
+        self.check_idle_connection();
+        let received = self.send_and_receive(request_bytes);
+        self.last_used = Instant::now();
+        received
+    }
+
+    fn send_and_receive(&mut self, request_bytes: &[u8]) -> Result<Response, String> {
         {
             let mut needs_reconnect = false;
             // First attempt to get connection and send the request
             if let Some(conn) = self.connection.as_mut() {
-                if let Err(_e) = conn.write_all(&request_bytes).and_then(|_| conn.write_all(b"\n")) {
+                if let Err(e) = conn.write_all(request_bytes).and_then(|_| conn.write_all(b"\n")) {
+                    if !self.reconnect {
+                        self.connection = None;
+                        return Err(format!("Send error: {}", e));
+                    }
                     needs_reconnect = true;
                 }
             } else {
@@ -74,18 +324,95 @@ impl RequestHandler {
             if needs_reconnect {
                 self.reconnect()?;
                 let conn = self.get_connection()?;
-                conn.write_all(&request_bytes).map_err(|e| format!("Send error: {}", e))?;
-                conn.write_all(b"\n").map_err(|e| format!("Send error: {}", e))?;
+                let sent = conn.write_all(request_bytes).and_then(|_| conn.write_all(b"\n"));
+                if let Err(e) = sent {
+                    self.connection = None;
+                    return Err(format!("Send error: {}", e));
+                }
             }
         }
 
         // Read response
+        let received = {
+            let conn = self.get_connection()?;
+            Self::read_response(BufReader::new(conn))
+        };
+        if received.is_err() {
+            // After a timeout the late response would be read as the answer to the next request.
+            self.connection = None;
+        }
+        received
+    }
+
+    /// Sends a request followed by `length` raw bytes of `payload`, and copies the raw bytes of the
+    /// response into `sink` instead of `Response::binary`. Neither side is held in memory.
+    ///
+    /// The request is not retried after a send error, since `payload` cannot be rewound.
+    pub fn send_streaming_request(
+        &mut self,
+        mut request: Request,
+        payload: Option<(&mut dyn Read, u64)>,
+        sink: Option<&mut dyn Write>,
+    ) -> Result<Response, String> {
+        self.fill_defaults(&mut request);
+        let request_bytes = serde_json::to_vec(&request).map_err(|e| format!("Serialization error: {}", e))?;
+
         let conn = self.get_connection()?;
+        let received = Self::exchange_streaming(conn, &request_bytes, payload, sink);
+        if received.is_err() {
+            // The server may still be reading the payload or writing the stream.
+            self.connection = None;
+        }
+        received
+    }
+
+    fn exchange_streaming(
+        conn: &mut TcpStream,
+        request_bytes: &[u8],
+        payload: Option<(&mut dyn Read, u64)>,
+        sink: Option<&mut dyn Write>,
+    ) -> Result<Response, String> {
+        conn.write_all(request_bytes)
+            .and_then(|_| conn.write_all(b"\n"))
+            .map_err(|e| format!("Send error: {}", e))?;
+        if let Some((payload, length)) = payload {
+            let sent = std::io::copy(&mut payload.take(length), conn).map_err(|e| format!("Send error: {}", e))?;
+            if sent != length {
+                return Err(format!("Send error: payload ended after {} of {} bytes", sent, length));
+            }
+        }
+
+        let Some(sink) = sink else {
+            return Self::read_response(BufReader::new(conn));
+        };
         let mut reader = BufReader::new(conn);
         let mut response_bytes = Vec::new();
         reader.read_until(b'\n', &mut response_bytes).map_err(|e| format!("Receive error: {}", e))?;
 
         let response: Response = serde_json::from_slice(&response_bytes).map_err(|e| format!("Deserialization error: {}", e))?;
+        if let Some(length) = response.binary_length {
+            let length = length as u64;
+            let received = std::io::copy(&mut reader.take(length), sink).map_err(|e| format!("Receive error: {}", e))?;
+            if received != length {
+                return Err(format!("Receive error: stream ended after {} of {} bytes", received, length));
+            }
+        }
+        Ok(response)
+    }
+
+    fn read_response(mut reader: BufReader<&mut TcpStream>) -> Result<Response, String> {
+        let mut response_bytes = Vec::new();
+        reader.read_until(b'\n', &mut response_bytes).map_err(|e| format!("Receive error: {}", e))?;
+        if response_bytes.is_empty() {
+            return Err("Receive error: connection closed by the server".to_string());
+        }
+
+        let mut response: Response = serde_json::from_slice(&response_bytes).map_err(|e| format!("Deserialization error: {}", e))?;
+        if let Some(length) = response.binary_length {
+            let mut binary = vec![0; length];
+            reader.read_exact(&mut binary).map_err(|e| format!("Receive error: {}", e))?;
+            response.binary = Some(binary);
+        }
         Ok(response)
     }
 
@@ -120,6 +447,9 @@ impl RequestBuilder {
                 restore_path: None,
                 iterator_id: None,
                 txn: None,
+                db: None,
+                token: None,
+                idempotency_key: None,
             },
         }
     }
@@ -168,6 +498,11 @@ impl RequestBuilder {
         self
     }
 
+    pub fn db(mut self, db: Option<String>) -> Self {
+        self.request.db = db;
+        self
+    }
+
     pub fn option(mut self, key: String, value: String) -> Self {
         if self.request.options.is_none() {
             self.request.options = Some(HashMap::new());
@@ -181,15 +516,186 @@ impl RequestBuilder {
     }
 }
 
+/// Collects the connection settings of a [`RocksDBClient`].
+#[derive(Debug, Clone)]
+pub struct RocksDBClientBuilder {
+    host: String,
+    port: u16,
+    token: Option<String>,
+    timeout: Option<Duration>,
+    reconnect: bool,
+    retry_policy: RetryPolicy,
+    keepalive: Option<Keepalive>,
+    db: Option<String>,
+}
+
+impl Default for RocksDBClientBuilder {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 12345,
+            token: None,
+            timeout: None,
+            reconnect: true,
+            retry_policy: RetryPolicy::default(),
+            keepalive: None,
+            db: None,
+        }
+    }
+}
+
+impl RocksDBClientBuilder {
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Token sent with every request, for servers started with `--token`.
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Limits connecting, sending and waiting for a response; unlimited by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Whether a request that finds the connection broken is retried once on a new connection
+    /// (default `true`). When disabled, the request fails and the next one reconnects.
+    pub fn reconnect(mut self, reconnect: bool) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    /// Retries of requests that fail on the connection; see [`RocksDBClient::set_retry_policy`].
+    pub fn retry_policy(mut self, max_retries: u32, base_delay: Duration) -> Self {
+        self.retry_policy = RetryPolicy { max_retries, base_delay };
+        self
+    }
+
+    /// Before sending a request on a connection idle for at least `interval`, checks it with a `ping`
+    /// and reconnects if there is no answer within `timeout`. Off by default.
+    pub fn keepalive(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.keepalive = Some(Keepalive { interval, timeout });
+        self
+    }
+
+    /// Database the client talks to; see [`RocksDBClient::use_database`].
+    pub fn database(mut self, db: impl Into<String>) -> Self {
+        self.db = Some(db.into());
+        self
+    }
+
+    pub fn build(self) -> RocksDBClient {
+        let mut request_handler = RequestHandler::new(self.host, self.port);
+        request_handler.token = self.token;
+        request_handler.timeout = self.timeout;
+        request_handler.reconnect = self.reconnect;
+        request_handler.retry_policy = self.retry_policy;
+        request_handler.keepalive = self.keepalive;
+        request_handler.db = self.db;
+
+        RocksDBClient { request_handler }
+    }
+}
+
 pub struct RocksDBClient {
     request_handler: RequestHandler,
 }
 
 impl RocksDBClient {
     pub fn new(host: String, port: u16) -> Self {
-        Self {
-            request_handler: RequestHandler::new(host, port),
-        }
+        Self::builder().host(host).port(port).build()
+    }
+
+    pub fn builder() -> RocksDBClientBuilder {
+        RocksDBClientBuilder::default()
+    }
+
+    /// Sends all later requests to the named database; `None` selects the default one.
+    pub fn use_database(&mut self, db: Option<String>) {
+        self.request_handler.use_database(db);
+    }
+
+    /// Retries a request up to `max_retries` times when connecting, sending or receiving fails, for example
+    /// while the server restarts. The first retry waits `base_delay` and each later one twice as long.
+    /// Errors the server answers with fail right away.
+    pub fn set_retry_policy(&mut self, max_retries: u32, base_delay: Duration) {
+        self.request_handler.set_retry_policy(RetryPolicy { max_retries, base_delay });
+    }
+
+    /// Checks idle connections with a `ping` before reusing them; `None` turns the check off.
+    /// See [`RocksDBClientBuilder::keepalive`].
+    pub fn set_keepalive(&mut self, keepalive: Option<Keepalive>) {
+        self.request_handler.set_keepalive(keepalive);
+    }
+
+    /// Sends `key` with the next request, so that retrying it with the same key returns the first
+    /// result instead of executing it twice, e.g. `client.with_idempotency_key("order-17").incr(..)`.
+    pub fn with_idempotency_key(&mut self, key: impl Into<String>) -> &mut Self {
+        self.request_handler.set_idempotency_key(key.into());
+        self
+    }
+
+    /// Sends the next request at low priority, e.g. `client.with_low_priority().put(..)` for background
+    /// writes that should give way to user-facing ones. Applies to `put`, `merge` and `write_batch_write`.
+    pub fn with_low_priority(&mut self) -> &mut Self {
+        self.request_handler.set_low_priority();
+        self
+    }
+
+    /// Selects the column family used by later requests that pass `None` as `cf_name`.
+    pub fn use_cf(&mut self, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("use_cf")
+            .cf_name(cf_name.clone())
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        let result = self.request_handler.handle_response(response)?;
+        self.request_handler.cf_name = cf_name;
+        Ok(result)
+    }
+
+    /// Returns `pong` if the server answers.
+    pub fn ping(&mut self) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("ping")
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn list_databases(&mut self) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("list_databases")
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn create_database(&mut self, name: String) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("create_database")
+            .db(Some(name))
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn drop_database(&mut self, name: String) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("drop_database")
+            .db(Some(name))
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
     }
 
     pub fn put(&mut self, key: String, value: String, cf_name: Option<String>, txn: Option<bool>) -> Result<Option<String>, String> {
@@ -217,100 +723,157 @@ impl RocksDBClient {
         self.request_handler.handle_response(response)
     }
 
-    pub fn delete(&mut self, key: String, cf_name: Option<String>, txn: Option<bool>) -> Result<Option<String>, String> {
-        let request = RequestBuilder::new("delete")
+    /// Like `get`, but a missing key is an error instead of `Ok(None)`.
+    pub fn get_required(&mut self, key: String, cf_name: Option<String>, txn: Option<bool>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("get")
             .key(Some(key))
             .cf_name(cf_name)
             .txn(txn)
+            .option("required".to_string(), "true".to_string())
             .build();
 
         let response = self.request_handler.send_request(request)?;
         self.request_handler.handle_response(response)
     }
 
-    pub fn merge(&mut self, key: String, value: String, cf_name: Option<String>, txn: Option<bool>) -> Result<Option<String>, String> {
-        let request = RequestBuilder::new("merge")
+    /// Reads a key as it was when the named snapshot was created.
+    pub fn get_at_snapshot(&mut self, key: String, snapshot: String, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("get")
             .key(Some(key))
-            .value(Some(value))
             .cf_name(cf_name)
-            .txn(txn)
+            .option("snapshot".to_string(), snapshot)
             .build();
 
         let response = self.request_handler.send_request(request)?;
         self.request_handler.handle_response(response)
     }
 
-    pub fn get_property(&mut self, value: String, cf_name: Option<String>) -> Result<Option<String>, String> {
-        let request = RequestBuilder::new("get_property")
-            .value(Some(value))
+    pub fn put_json(&mut self, key: String, value: &Value, cf_name: Option<String>, txn: Option<bool>) -> Result<Option<String>, String> {
+        self.put(key, value.to_string(), cf_name, txn)
+    }
+
+    pub fn get_json(&mut self, key: String, cf_name: Option<String>, txn: Option<bool>) -> Result<Option<Value>, String> {
+        let request = RequestBuilder::new("get")
+            .key(Some(key))
             .cf_name(cf_name)
+            .txn(txn)
+            .option("decode".to_string(), "json".to_string())
             .build();
 
         let response = self.request_handler.send_request(request)?;
-        self.request_handler.handle_response(response)
+        self.request_handler
+            .handle_response(response)?
+            .map(|result| serde_json::from_str(&result).map_err(|e| e.to_string()))
+            .transpose()
     }
 
-    pub fn keys(&mut self, start: String, limit: String, query: Option<String>) -> Result<Option<String>, String> {
-        let request = RequestBuilder::new("keys")
-            .option("start".to_string(), start)
-            .option("limit".to_string(), limit)
-            .option("query".to_string(), query.unwrap_or_default())
+    pub fn delete(&mut self, key: String, cf_name: Option<String>, txn: Option<bool>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("delete")
+            .key(Some(key))
+            .cf_name(cf_name)
+            .txn(txn)
             .build();
 
         let response = self.request_handler.send_request(request)?;
         self.request_handler.handle_response(response)
     }
 
-    pub fn all(&mut self, query: Option<String>) -> Result<Option<String>, String> {
-        let request = RequestBuilder::new("all")
-            .option("query".to_string(), query.unwrap_or_default())
+    pub fn delete_if(&mut self, key: String, value: String, cf_name: Option<String>, txn: Option<bool>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("delete_if")
+            .key(Some(key))
+            .value(Some(value))
+            .cf_name(cf_name)
+            .txn(txn)
             .build();
 
         let response = self.request_handler.send_request(request)?;
         self.request_handler.handle_response(response)
     }
 
-    pub fn list_column_families(&mut self) -> Result<Option<String>, String> {
-        let request = RequestBuilder::new("list_column_families")
-            .build();
+    /// Deletes the keys in `[start, end)` whose value matches the predicate options, e.g.
+    /// `value_prefix`, `value_contains` or `json_pointer` with `json_value`. Returns the number deleted.
+    pub fn scan_delete(&mut self, start: Option<String>, end: Option<String>, predicate: HashMap<String, String>, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("scan_delete")
+            .cf_name(cf_name);
+        if let Some(start) = start {
+            builder = builder.option("start".to_string(), start);
+        }
+        if let Some(end) = end {
+            builder = builder.option("end".to_string(), end);
+        }
+        for (name, value) in predicate {
+            builder = builder.option(name, value);
+        }
+        let request = builder.build();
 
         let response = self.request_handler.send_request(request)?;
         self.request_handler.handle_response(response)
     }
 
-    pub fn create_column_family(&mut self, cf_name: String) -> Result<Option<String>, String> {
-        let request = RequestBuilder::new("create_column_family")
-            .cf_name(Some(cf_name))
+    /// Deletes every key of the column family, or of every column family with `all_cfs`, and returns how many were deleted.
+    pub fn truncate(&mut self, cf_name: Option<String>, all_cfs: bool) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("truncate")
+            .cf_name(cf_name)
+            .option("confirm".to_string(), "true".to_string())
+            .option("all_cfs".to_string(), all_cfs.to_string())
             .build();
 
         let response = self.request_handler.send_request(request)?;
         self.request_handler.handle_response(response)
     }
 
-    pub fn drop_column_family(&mut self, cf_name: String) -> Result<Option<String>, String> {
-        let request = RequestBuilder::new("drop_column_family")
-            .cf_name(Some(cf_name))
-            .build();
+    pub fn merge(&mut self, key: String, value: String, cf_name: Option<String>, txn: Option<bool>, return_value: Option<bool>) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("merge")
+            .key(Some(key))
+            .value(Some(value))
+            .cf_name(cf_name)
+            .txn(txn);
+        if let Some(return_value) = return_value {
+            builder = builder.option("return_value".to_string(), return_value.to_string());
+        }
+        let request = builder.build();
 
         let response = self.request_handler.send_request(request)?;
         self.request_handler.handle_response(response)
     }
 
-    pub fn compact_range(&mut self, start: Option<String>, end: Option<String>, cf_name: Option<String>) -> Result<Option<String>, String> {
-        let request = RequestBuilder::new("compact_range")
-            .option("start".to_string(), start.unwrap_or_default())
-            .option("end".to_string(), end.unwrap_or_default())
+    /// Merges with a named operation (`json-patch`, `merge-patch`, `numeric-add` or `string-append`)
+    /// instead of the column family's merge operator. Returns the merged value.
+    pub fn merge_with_op(&mut self, key: String, value: String, merge_op: String, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("merge")
+            .key(Some(key))
+            .value(Some(value))
             .cf_name(cf_name)
+            .option("merge_op".to_string(), merge_op)
+            .option("return_value".to_string(), "true".to_string())
             .build();
 
         let response = self.request_handler.send_request(request)?;
         self.request_handler.handle_response(response)
     }
 
-    pub fn write_batch_put(&mut self, key: String, value: String, cf_name: Option<String>) -> Result<Option<String>, String> {
-        let request = RequestBuilder::new("write_batch_put")
+    /// Merges an RFC 6902 `add` operation: sets `value` at the JSON pointer `path`.
+    pub fn merge_add(&mut self, key: String, path: String, value: Value, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let patch = json!([{ "op": "add", "path": path, "value": value }]);
+        self.merge(key, patch.to_string(), cf_name, None, None)
+    }
+
+    /// Merges an RFC 6902 `remove` operation: deletes the value at the JSON pointer `path`.
+    pub fn merge_remove(&mut self, key: String, path: String, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let patch = json!([{ "op": "remove", "path": path }]);
+        self.merge(key, patch.to_string(), cf_name, None, None)
+    }
+
+    /// Merges an RFC 6902 `replace` operation: overwrites the existing value at the JSON pointer `path`.
+    pub fn merge_replace(&mut self, key: String, path: String, value: Value, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let patch = json!([{ "op": "replace", "path": path, "value": value }]);
+        self.merge(key, patch.to_string(), cf_name, None, None)
+    }
+
+    pub fn rename(&mut self, key: String, new_key: String, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("rename")
             .key(Some(key))
-            .value(Some(value))
+            .option("new_key".to_string(), new_key)
             .cf_name(cf_name)
             .build();
 
@@ -318,52 +881,433 @@ impl RocksDBClient {
         self.request_handler.handle_response(response)
     }
 
-    pub fn write_batch_merge(&mut self, key: String, value: String, cf_name: Option<String>) -> Result<Option<String>, String> {
-        let request = RequestBuilder::new("write_batch_merge")
+    /// Stores the value of `key` under `new_key` too, in `new_cf_name` or the source's column family.
+    pub fn copy(&mut self, key: String, new_key: String, cf_name: Option<String>, new_cf_name: Option<String>) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("copy")
             .key(Some(key))
-            .value(Some(value))
-            .cf_name(cf_name)
-            .build();
+            .option("new_key".to_string(), new_key)
+            .cf_name(cf_name);
+        if let Some(new_cf_name) = new_cf_name {
+            builder = builder.option("new_cf_name".to_string(), new_cf_name);
+        }
+        let request = builder.build();
 
         let response = self.request_handler.send_request(request)?;
         self.request_handler.handle_response(response)
     }
 
-    pub fn write_batch_delete(&mut self, key: String, cf_name: Option<String>) -> Result<Option<String>, String> {
-        let request = RequestBuilder::new("write_batch_delete")
+    /// Returns `{"value": .., "inserted": ..}`: the stored value of `key`, after inserting `value` if it was missing.
+    pub fn getset(&mut self, key: String, value: String, cf_name: Option<String>, txn: Option<bool>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("getset")
             .key(Some(key))
+            .value(Some(value))
             .cf_name(cf_name)
+            .txn(txn)
             .build();
 
         let response = self.request_handler.send_request(request)?;
         self.request_handler.handle_response(response)
     }
 
-    pub fn write_batch_write(&mut self) -> Result<Option<String>, String> {
-        let request = RequestBuilder::new("write_batch_write")
+    pub fn get_or_put(&mut self, key: String, value: String, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("get_or_put")
+            .key(Some(key))
+            .value(Some(value))
+            .cf_name(cf_name)
             .build();
 
         let response = self.request_handler.send_request(request)?;
         self.request_handler.handle_response(response)
     }
 
-    pub fn write_batch_clear(&mut self) -> Result<Option<String>, String> {
-        let request = RequestBuilder::new("write_batch_clear")
-            .build();
+    /// Adds `by` (default 1) to the integer under `key` and returns the new value.
+    pub fn incr(&mut self, key: String, by: Option<i64>, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("incr").key(Some(key)).cf_name(cf_name);
+        if let Some(by) = by {
+            builder = builder.option("by".to_string(), by.to_string());
+        }
+        let request = builder.build();
 
         let response = self.request_handler.send_request(request)?;
         self.request_handler.handle_response(response)
     }
 
-    pub fn write_batch_destroy(&mut self) -> Result<Option<String>, String> {
-        let request = RequestBuilder::new("write_batch_destroy")
-            .build();
+    /// Subtracts `by` (default 1) from the integer under `key` and returns the new value.
+    pub fn decr(&mut self, key: String, by: Option<i64>, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("decr").key(Some(key)).cf_name(cf_name);
+        if let Some(by) = by {
+            builder = builder.option("by".to_string(), by.to_string());
+        }
+        let request = builder.build();
 
         let response = self.request_handler.send_request(request)?;
         self.request_handler.handle_response(response)
     }
 
-    pub fn create_iterator(&mut self) -> Result<Option<String>, String> {
+    pub fn wait_for_key(&mut self, key: String, cf_name: Option<String>, timeout_ms: Option<u64>) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("wait_for_key")
+            .key(Some(key))
+            .cf_name(cf_name);
+        if let Some(timeout_ms) = timeout_ms {
+            builder = builder.option("timeout_ms".to_string(), timeout_ms.to_string());
+        }
+        let request = builder.build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn get_property(&mut self, value: String, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("get_property")
+            .value(Some(value))
+            .cf_name(cf_name)
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn get_properties(&mut self, properties: Vec<String>, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("get_properties")
+            .value(Some(json!(properties).to_string()))
+            .cf_name(cf_name)
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn keys(&mut self, start: String, limit: String, query: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("keys")
+            .option("start".to_string(), start)
+            .option("limit".to_string(), limit)
+            .option("query".to_string(), query.unwrap_or_default())
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn all(&mut self, query: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("all")
+            .option("query".to_string(), query.unwrap_or_default())
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Fetches several keys at once as a JSON object `{key: value, ...}`. A missing key maps to its
+    /// default, or to `null` without one.
+    pub fn get_many(&mut self, keys: Vec<(String, Option<String>)>, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let entries: Vec<Value> = keys
+            .into_iter()
+            .map(|(key, default)| match default {
+                Some(default) => json!({ "key": key, "default": default }),
+                None => Value::String(key),
+            })
+            .collect();
+        let request = RequestBuilder::new("get_many")
+            .value(Some(Value::Array(entries).to_string()))
+            .cf_name(cf_name)
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Fetches the entries under `prefix` or in `[start, end)` as a JSON object `{key: value, ...}` in key order.
+    pub fn get_range_map(&mut self, prefix: Option<String>, start: Option<String>, end: Option<String>, limit: Option<usize>, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("get_range_map").cf_name(cf_name);
+        for (name, value) in [("prefix", prefix), ("start", start), ("end", end), ("limit", limit.map(|limit| limit.to_string()))] {
+            if let Some(value) = value {
+                builder = builder.option(name.to_string(), value);
+            }
+        }
+        let request = builder.build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Like `get_range_map`, but `start` and `end` are numbers matched against keys of `prefix` followed by the
+    /// number zero-padded to `key_width` digits (default 20).
+    pub fn get_range_map_numeric(&mut self, prefix: Option<String>, start: Option<u64>, end: Option<u64>, key_width: Option<usize>, limit: Option<usize>, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("get_range_map")
+            .cf_name(cf_name)
+            .option("key_type".to_string(), "numeric".to_string());
+        let options = [
+            ("prefix", prefix),
+            ("start", start.map(|start| start.to_string())),
+            ("end", end.map(|end| end.to_string())),
+            ("key_width", key_width.map(|key_width| key_width.to_string())),
+            ("limit", limit.map(|limit| limit.to_string())),
+        ];
+        for (name, value) in options {
+            if let Some(value) = value {
+                builder = builder.option(name.to_string(), value);
+            }
+        }
+        let request = builder.build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Fetches the keys under `prefix` followed by `separator` (default `:`) as a JSON object keyed by the
+    /// rest of the key, e.g. `{"email": .., "name": ..}` for `user:123`.
+    pub fn get_entity(&mut self, prefix: String, separator: Option<String>, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("get_entity")
+            .cf_name(cf_name)
+            .option("prefix".to_string(), prefix);
+        if let Some(separator) = separator {
+            builder = builder.option("separator".to_string(), separator);
+        }
+        let request = builder.build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Like `get_range_map`, but keeps only `fields` of each JSON object value. Values that are not
+    /// JSON objects fail the request if `strict`, and are returned whole otherwise.
+    #[allow(clippy::too_many_arguments)]
+    pub fn get_range_fields(&mut self, prefix: Option<String>, start: Option<String>, end: Option<String>, limit: Option<usize>, fields: Vec<String>, strict: bool, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("get_range_map")
+            .cf_name(cf_name)
+            .option("fields".to_string(), fields.join(","))
+            .option("strict".to_string(), strict.to_string());
+        for (name, value) in [("prefix", prefix), ("start", start), ("end", end), ("limit", limit.map(|limit| limit.to_string()))] {
+            if let Some(value) = value {
+                builder = builder.option(name.to_string(), value);
+            }
+        }
+        let request = builder.build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Fetches the last `count` (default 10) entries under `prefix` as a JSON array, in descending key order.
+    pub fn tail(&mut self, prefix: Option<String>, count: Option<usize>, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("tail").cf_name(cf_name);
+        if let Some(prefix) = prefix {
+            builder = builder.option("prefix".to_string(), prefix);
+        }
+        if let Some(count) = count {
+            builder = builder.option("count".to_string(), count.to_string());
+        }
+        let request = builder.build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Fetches every key whose key or value contains `query` together with its value, as raw bytes
+    /// without JSON encoding. Keys and values that are not UTF-8 are included.
+    pub fn scan_binary(&mut self, query: Option<String>) -> Result<Vec<BinaryEntry>, String> {
+        let request = RequestBuilder::new("all")
+            .option("query".to_string(), query.unwrap_or_default())
+            .option("format".to_string(), "binary".to_string())
+            .build();
+
+        let mut response = self.request_handler.send_request(request)?;
+        let data = response.binary.take().unwrap_or_default();
+        self.request_handler.handle_response(response)?;
+
+        let mut entries = Vec::new();
+        let mut rest = data.as_slice();
+        while !rest.is_empty() {
+            let key = take_length_prefixed(&mut rest)?;
+            let value = take_length_prefixed(&mut rest)?;
+            entries.push((key, value));
+        }
+        Ok(entries)
+    }
+
+    pub fn export(&mut self, path: String, prefix: Option<String>, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("export")
+            .option("path".to_string(), path)
+            .option("prefix".to_string(), prefix.unwrap_or_default())
+            .cf_name(cf_name)
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn import(&mut self, path: String, cf_name: Option<String>, overwrite: Option<bool>, batch_size: Option<usize>) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("import")
+            .option("path".to_string(), path)
+            .cf_name(cf_name);
+        if let Some(overwrite) = overwrite {
+            builder = builder.option("overwrite".to_string(), overwrite.to_string());
+        }
+        if let Some(batch_size) = batch_size {
+            builder = builder.option("batch_size".to_string(), batch_size.to_string());
+        }
+        let request = builder.build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn list_column_families(&mut self) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("list_column_families")
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn create_column_family(&mut self, cf_name: String) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("create_column_family")
+            .cf_name(Some(cf_name))
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn drop_column_family(&mut self, cf_name: String) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("drop_column_family")
+            .cf_name(Some(cf_name))
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn compact_range(&mut self, start: Option<String>, end: Option<String>, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("compact_range")
+            .option("start".to_string(), start.unwrap_or_default())
+            .option("end".to_string(), end.unwrap_or_default())
+            .cf_name(cf_name)
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Drops the SST files that lie entirely within `[start, end]`, freeing their space without a compaction.
+    pub fn delete_files_in_range(&mut self, start: String, end: String, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("delete_files_in_range")
+            .option("start".to_string(), start)
+            .option("end".to_string(), end)
+            .cf_name(cf_name)
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn sync_wal(&mut self) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("sync_wal").build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Changes a mutable column family option, such as `disable_auto_compactions`, on the running database.
+    pub fn set_db_option(&mut self, name: String, value: String, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("set_db_option")
+            .option("name".to_string(), name)
+            .option("value".to_string(), value)
+            .cf_name(cf_name)
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn write_batch_put(&mut self, key: String, value: String, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("write_batch_put")
+            .key(Some(key))
+            .value(Some(value))
+            .cf_name(cf_name)
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn write_batch_merge(&mut self, key: String, value: String, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("write_batch_merge")
+            .key(Some(key))
+            .value(Some(value))
+            .cf_name(cf_name)
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn write_batch_delete(&mut self, key: String, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("write_batch_delete")
+            .key(Some(key))
+            .cf_name(cf_name)
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn write_batch_write(&mut self) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("write_batch_write")
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn write_batch_clear(&mut self) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("write_batch_clear")
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Starts a new empty write batch, e.g. after `write_batch_destroy`; a pending batch is discarded.
+    pub fn write_batch_create(&mut self) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("write_batch_create")
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn write_batch_destroy(&mut self) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("write_batch_destroy")
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn begin_bulk_load(&mut self, disable_auto_compactions: Option<bool>) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("begin_bulk_load");
+        if let Some(disable_auto_compactions) = disable_auto_compactions {
+            builder = builder.option("disable_auto_compactions".to_string(), disable_auto_compactions.to_string());
+        }
+        let request = builder.build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn end_bulk_load(&mut self, compact: Option<bool>) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("end_bulk_load");
+        if let Some(compact) = compact {
+            builder = builder.option("compact".to_string(), compact.to_string());
+        }
+        let request = builder.build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn create_iterator(&mut self) -> Result<Option<String>, String> {
         let request = RequestBuilder::new("create_iterator")
             .build();
 
@@ -371,6 +1315,65 @@ impl RocksDBClient {
         self.request_handler.handle_response(response)
     }
 
+    /// Like `create_iterator`, but the iterator returns base64-encoded keys and values, and `iterator_seek`
+    /// takes a base64-encoded key, so binary keys survive the round trip.
+    pub fn create_iterator_base64(&mut self) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("create_iterator")
+            .option("encoding".to_string(), "base64".to_string())
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Opens an iterator over `[lower_bound, upper_bound)` positioned at `start` and returns its ID with the
+    /// first `limit` entries, as a JSON object `{"iterator_id", "entries", "done"}`. With `base64`, `start`,
+    /// the bounds and all entries of the iterator are base64-encoded.
+    #[allow(clippy::too_many_arguments)]
+    pub fn scan_open(
+        &mut self,
+        start: Option<String>,
+        lower_bound: Option<String>,
+        upper_bound: Option<String>,
+        reverse: bool,
+        base64: bool,
+        limit: Option<usize>,
+    ) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("scan_open").key(start);
+        if let Some(lower_bound) = lower_bound {
+            builder = builder.option("lower_bound".to_string(), lower_bound);
+        }
+        if let Some(upper_bound) = upper_bound {
+            builder = builder.option("upper_bound".to_string(), upper_bound);
+        }
+        if reverse {
+            builder = builder.option("direction".to_string(), "reverse".to_string());
+        }
+        if base64 {
+            builder = builder.option("encoding".to_string(), "base64".to_string());
+        }
+        if let Some(limit) = limit {
+            builder = builder.option("limit".to_string(), limit.to_string());
+        }
+        let request = builder.build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Fetches the next `limit` entries of an iterator as a JSON object `{"entries", "done"}`.
+    pub fn iterator_next_batch(&mut self, iterator_id: String, limit: Option<usize>) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("iterator_next_batch")
+            .option("iterator_id".to_string(), iterator_id);
+        if let Some(limit) = limit {
+            builder = builder.option("limit".to_string(), limit.to_string());
+        }
+        let request = builder.build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
     pub fn destroy_iterator(&mut self, iterator_id: String) -> Result<Option<String>, String> {
         let request = RequestBuilder::new("destroy_iterator")
             .option("iterator_id".to_string(), iterator_id)
@@ -441,6 +1444,66 @@ impl RocksDBClient {
         self.request_handler.handle_response(response)
     }
 
+    pub fn create_snapshot(&mut self, name: String, ttl: Option<u64>) -> Result<Option<String>, String> {
+        let mut builder = RequestBuilder::new("create_snapshot")
+            .option("name".to_string(), name);
+        if let Some(ttl) = ttl {
+            builder = builder.option("ttl".to_string(), ttl.to_string());
+        }
+        let request = builder.build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn release_snapshot(&mut self, name: String) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("release_snapshot")
+            .option("name".to_string(), name)
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn list_snapshots(&mut self) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("list_snapshots")
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn swap_db(&mut self, path: String) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("swap_db")
+            .option("path".to_string(), path)
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Checkpoints the database and writes its files to `sink` as they arrive, in the framing
+    /// `import_backup_stream` reads. Returns the number of bytes written.
+    pub fn export_backup_stream(&mut self, sink: &mut dyn Write) -> Result<u64, String> {
+        let request = RequestBuilder::new("export_backup_stream")
+            .build();
+
+        let response = self.request_handler.send_streaming_request(request, None, Some(sink))?;
+        let length = response.binary_length.unwrap_or(0) as u64;
+        self.request_handler.handle_response(response)?;
+        Ok(length)
+    }
+
+    /// Replaces the database with `length` bytes of a stream written by `export_backup_stream`.
+    pub fn import_backup_stream(&mut self, source: &mut dyn Read, length: u64) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("import_backup_stream")
+            .option("length".to_string(), length.to_string())
+            .build();
+
+        let response = self.request_handler.send_streaming_request(request, Some((source, length)), None)?;
+        self.request_handler.handle_response(response)
+    }
+
     pub fn begin_transaction(&mut self) -> Result<Option<String>, String> {
         let request = RequestBuilder::new("begin_transaction")
             .build();
@@ -464,4 +1527,238 @@ impl RocksDBClient {
         let response = self.request_handler.send_request(request)?;
         self.request_handler.handle_response(response)
     }
+
+    /// Commits like `commit_transaction` but keeps the transaction database open, so the next
+    /// `begin_transaction` does not have to reopen the database.
+    pub fn commit_transaction_keep_open(&mut self) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("commit_transaction")
+            .option("keep_open".to_string(), "true".to_string())
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Rolls back like `rollback_transaction` but keeps the transaction database open.
+    pub fn rollback_transaction_keep_open(&mut self) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("rollback_transaction")
+            .option("keep_open".to_string(), "true".to_string())
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn info(&mut self) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("info")
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn write_health(&mut self) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("write_health")
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Writes, reads back and deletes a sentinel key, returning `{"ok": .., "write_ms": .., "read_ms": ..}`.
+    pub fn selftest(&mut self, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("selftest")
+            .cf_name(cf_name)
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    pub fn storage_stats(&mut self, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("storage_stats")
+            .cf_name(cf_name)
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Fetches the cache layer statistics: `enabled`, `entries`, `hits`, `misses` and `ttl_seconds`.
+    pub fn cache_stats(&mut self) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("cache_stats").build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Drops every cached entry and returns `{"enabled": .., "cleared": ..}`.
+    pub fn cache_clear(&mut self) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("cache_clear").build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+
+    /// Streams the entries whose key starts with `prefix` in key order, fetching one batch at a time
+    /// with `scan_open` and `iterator_next_batch`, so only the current batch is held in memory.
+    pub fn scan_stream(&self, prefix: String) -> ScanStream {
+        ScanStream {
+            cursor: Some(ScanCursor {
+                handler: self.request_handler.detached(),
+                prefix,
+                batch_size: SCAN_STREAM_BATCH_SIZE,
+                iterator_id: None,
+                done: false,
+            }),
+            fetching: None,
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// Fetches the effective RocksDB options of a column family as `{"db_options", "cf_options", "table_options"}`.
+    pub fn get_options(&mut self, cf_name: Option<String>) -> Result<Option<String>, String> {
+        let request = RequestBuilder::new("get_options")
+            .cf_name(cf_name)
+            .build();
+
+        let response = self.request_handler.send_request(request)?;
+        self.request_handler.handle_response(response)
+    }
+}
+
+/// Entries `ScanStream` fetches per request unless `batch_size` changes it.
+const SCAN_STREAM_BATCH_SIZE: usize = 100;
+
+#[derive(Deserialize)]
+struct ScanBatch {
+    iterator_id: Option<usize>,
+    entries: Vec<ScanEntry>,
+    done: bool,
+}
+
+#[derive(Deserialize)]
+struct ScanEntry {
+    key: String,
+    value: String,
+}
+
+/// The entries under a prefix, returned by `RocksDBClient::scan_stream`.
+///
+/// A `futures::Stream`; the next batch is only requested once the previous one has been consumed. Each batch
+/// is fetched on a blocking thread over the stream's own connection, so polling never blocks the executor.
+/// Dropping the stream closes that connection, which releases the server-side iterator.
+pub struct ScanStream {
+    /// `None` while a batch is being fetched and once the scan is finished.
+    cursor: Option<ScanCursor>,
+    fetching: Option<JoinHandle<(ScanCursor, ScanBatchResult)>>,
+    entries: VecDeque<(String, String)>,
+}
+
+type ScanBatchResult = Result<Vec<(String, String)>, String>;
+
+/// Position of a scan, moved to the blocking thread that fetches the next batch.
+struct ScanCursor {
+    handler: RequestHandler,
+    prefix: String,
+    batch_size: usize,
+    iterator_id: Option<usize>,
+    done: bool,
+}
+
+impl ScanStream {
+    /// Sets how many entries each request fetches (default 100).
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        if let Some(cursor) = self.cursor.as_mut() {
+            cursor.batch_size = batch_size.max(1);
+        }
+        self
+    }
+}
+
+impl ScanCursor {
+    fn fetch_batch(&mut self) -> ScanBatchResult {
+        let request = match self.iterator_id {
+            None => {
+                let mut builder = RequestBuilder::new("scan_open")
+                    .option("lower_bound".to_string(), self.prefix.clone())
+                    .option("limit".to_string(), self.batch_size.to_string());
+                if let Some(upper_bound) = prefix_successor(&self.prefix) {
+                    builder = builder.option("upper_bound".to_string(), upper_bound);
+                }
+                builder.build()
+            }
+            Some(iterator_id) => RequestBuilder::new("iterator_next_batch")
+                .option("iterator_id".to_string(), iterator_id.to_string())
+                .option("limit".to_string(), self.batch_size.to_string())
+                .build(),
+        };
+
+        let response = self.handler.send_request(request)?;
+        let result = self.handler.handle_response(response)?.unwrap_or_default();
+        let batch: ScanBatch =
+            serde_json::from_str(&result).map_err(|e| format!("Invalid scan batch: {}", e))?;
+        if batch.iterator_id.is_some() {
+            self.iterator_id = batch.iterator_id;
+        }
+
+        // Without an upper bound the scan runs past the prefix; the first key outside it ends the stream.
+        let prefix = &self.prefix;
+        let in_prefix = batch.entries.iter().take_while(|entry| entry.key.starts_with(prefix.as_str())).count();
+        self.done = batch.done || in_prefix < batch.entries.len();
+        Ok(batch
+            .entries
+            .into_iter()
+            .take(in_prefix)
+            .map(|entry| (entry.key, entry.value))
+            .collect())
+    }
+}
+
+/// The smallest string above every string starting with `prefix`, if it is valid UTF-8.
+fn prefix_successor(prefix: &str) -> Option<String> {
+    let mut bound = prefix.as_bytes().to_vec();
+    while let Some(last) = bound.pop() {
+        if last < u8::MAX {
+            bound.push(last + 1);
+            return String::from_utf8(bound).ok();
+        }
+    }
+    None
+}
+
+impl Stream for ScanStream {
+    type Item = Result<(String, String), String>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(entry) = this.entries.pop_front() {
+                return Poll::Ready(Some(Ok(entry)));
+            }
+            if let Some(fetching) = this.fetching.as_mut() {
+                let (cursor, batch) = match Pin::new(fetching).poll(cx) {
+                    Poll::Ready(fetched) => fetched,
+                    Poll::Pending => return Poll::Pending,
+                };
+                this.fetching = None;
+                match batch {
+                    Ok(entries) => this.entries.extend(entries),
+                    // The cursor is dropped with its connection, so the stream ends after the error.
+                    Err(e) => return Poll::Ready(Some(Err(e))),
+                }
+                if !cursor.done {
+                    this.cursor = Some(cursor);
+                }
+                continue;
+            }
+            let Some(mut cursor) = this.cursor.take() else {
+                return Poll::Ready(None);
+            };
+            this.fetching = Some(spawn_blocking(move || {
+                let batch = cursor.fetch_batch();
+                (cursor, batch)
+            }));
+        }
+    }
 }
@@ -0,0 +1,188 @@
+use async_std::fs::{File, OpenOptions};
+use async_std::io::{self, prelude::*, Read, ReadExt, Write};
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Prefix of the directories under the database path that hold a streamed backup while it is sent or received.
+const STREAM_DIR_PREFIX: &str = "stream-";
+
+/// Longest file name accepted in an imported stream.
+const MAX_NAME_LEN: usize = 255;
+
+static NEXT_STREAM_DIR: AtomicU64 = AtomicU64::new(0);
+
+/// A scratch directory under the database path, deleted with everything in it when dropped.
+#[derive(Debug)]
+pub struct StreamDir {
+    path: PathBuf,
+}
+
+impl StreamDir {
+    /// Picks an unused path without creating it, since a checkpoint insists on creating its own directory.
+    pub fn new(db_path: &str, kind: &str) -> Self {
+        let id = NEXT_STREAM_DIR.fetch_add(1, Ordering::SeqCst);
+        StreamDir {
+            path: Path::new(db_path).join(format!("{}{}-{}", STREAM_DIR_PREFIX, kind, id)),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for StreamDir {
+    fn drop(&mut self) {
+        if self.path.exists() {
+            if let Err(e) = std::fs::remove_dir_all(&self.path) {
+                warn!("Failed to remove {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+/// Removes the stream directories a crashed server left under `db_path`.
+pub fn remove_leftovers(db_path: &str) {
+    let Ok(entries) = std::fs::read_dir(db_path) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let leftover = entry.file_name().to_string_lossy().starts_with(STREAM_DIR_PREFIX)
+            && entry.file_type().map(|file_type| file_type.is_dir()).unwrap_or(false);
+        if leftover {
+            info!("Removing leftover {}", entry.path().display());
+            if let Err(e) = std::fs::remove_dir_all(entry.path()) {
+                warn!("Failed to remove {}: {}", entry.path().display(), e);
+            }
+        }
+    }
+}
+
+/// The files of a directory, sent as one frame per file: name length (u32), name, file length (u64)
+/// and contents, with big-endian integers.
+#[derive(Debug)]
+pub struct FileStream {
+    dir: StreamDir,
+    files: Vec<(String, u64)>,
+}
+
+impl FileStream {
+    pub fn from_dir(dir: StreamDir) -> Result<Self, String> {
+        let entries = std::fs::read_dir(dir.path())
+            .map_err(|e| format!("Failed to read {}: {}", dir.path().display(), e))?;
+
+        let mut files = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| e.to_string())?;
+            let metadata = entry.metadata().map_err(|e| e.to_string())?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let name = entry
+                .file_name()
+                .into_string()
+                .map_err(|name| format!("File name {:?} is not valid UTF-8", name))?;
+            files.push((name, metadata.len()));
+        }
+        files.sort();
+
+        Ok(FileStream { dir, files })
+    }
+
+    /// Total number of bytes `write_to` writes.
+    pub fn len(&self) -> u64 {
+        self.files
+            .iter()
+            .map(|(name, size)| 4 + name.len() as u64 + 8 + size)
+            .sum()
+    }
+
+    pub async fn write_to<W: Write + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+        for (name, size) in &self.files {
+            writer.write_all(&(name.len() as u32).to_be_bytes()).await?;
+            writer.write_all(name.as_bytes()).await?;
+            writer.write_all(&size.to_be_bytes()).await?;
+
+            let file = File::open(self.dir.path().join(name)).await?;
+            let copied = io::copy(&mut file.take(*size), writer).await?;
+            if copied != *size {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("{} shrank while it was streamed", name),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    let flat = !name.contains(['/', '\\']) && name != "." && name != "..";
+    if name.is_empty() || name.len() > MAX_NAME_LEN || !flat || name.chars().any(char::is_control) {
+        return Err(format!("Invalid file name in stream: {:?}", name));
+    }
+    Ok(())
+}
+
+/// Unpacks `length` bytes of frames from `reader` into the existing directory `dir`.
+///
+/// The whole payload is consumed even when it is rejected, so the connection stays in sync.
+pub async fn unpack<R: Read + Unpin>(reader: &mut R, length: u64, dir: &Path) -> Result<(), String> {
+    let mut payload = ReadExt::take(reader, length);
+    let result = unpack_frames(&mut payload, dir).await;
+    if result.is_err() {
+        io::copy(&mut payload, &mut io::sink())
+            .await
+            .map_err(|e| format!("Failed to read stream: {}", e))?;
+    }
+    result
+}
+
+/// Reads and discards `length` bytes, for a payload rejected before unpacking started.
+pub async fn skip<R: Read + Unpin>(reader: &mut R, length: u64) -> io::Result<()> {
+    io::copy(&mut ReadExt::take(reader, length), &mut io::sink()).await.map(|_| ())
+}
+
+async fn unpack_frames<R: Read + Unpin>(payload: &mut io::Take<&mut R>, dir: &Path) -> Result<(), String> {
+    let truncated = |e: io::Error| format!("Truncated stream: {}", e);
+
+    while payload.limit() > 0 {
+        let mut name_len = [0u8; 4];
+        payload.read_exact(&mut name_len).await.map_err(truncated)?;
+        let name_len = u32::from_be_bytes(name_len) as usize;
+        if name_len > MAX_NAME_LEN {
+            return Err(format!("File name in stream is longer than {} bytes", MAX_NAME_LEN));
+        }
+
+        let mut name = vec![0u8; name_len];
+        payload.read_exact(&mut name).await.map_err(truncated)?;
+        let name = String::from_utf8(name).map_err(|_| "File name in stream is not valid UTF-8".to_string())?;
+        validate_name(&name)?;
+
+        let mut size = [0u8; 8];
+        payload.read_exact(&mut size).await.map_err(truncated)?;
+        let size = u64::from_be_bytes(size);
+        if size > payload.limit() {
+            return Err(format!("Truncated stream: {} needs {} bytes", name, size));
+        }
+
+        let path = dir.join(&name);
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let copied = io::copy(&mut ReadExt::take(&mut *payload, size), &mut file)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        if copied != size {
+            return Err(format!("Truncated stream: {} ended after {} of {} bytes", name, copied, size));
+        }
+        file.sync_all()
+            .await
+            .map_err(|e| format!("Failed to sync {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
@@ -1,8 +1,11 @@
 use crate::cache::queue::{TaskQueue, TaskType};
-use crate::db_manager::RocksDBManager;
-use async_std::sync::{Arc, RwLock};
+use crate::db_manager::{RocksDBManager, WriteFlags};
+use crate::key_locks::AsyncKeyLocks;
+use async_std::sync::{Arc, MutexGuard, RwLock};
 use async_std::task;
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 use crate::metrics::METRICS;
 
@@ -10,9 +13,15 @@ type CacheData = Arc<RwLock<HashMap<(String, Option<String>), (String, Instant)>
 
 pub(crate) struct CacheLayer {
     data: CacheData,
-    ttl: Duration,
+    // In milliseconds, shared by all clones so a reload reaches the cleanup task too.
+    ttl_millis: Arc<AtomicU64>,
     pub(crate) enabled: bool,
     task_queue: Arc<TaskQueue>,
+    // Held by cached writes while they queue, and by `settle` until a read-modify-write is done.
+    write_locks: Arc<AsyncKeyLocks>,
+    // Lookups of this cache alone; the Prometheus counters add up every database.
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
 }
 
 impl CacheLayer {
@@ -29,9 +38,12 @@ impl CacheLayer {
 
         let cache = CacheLayer {
             data: data.clone(),
-            ttl,
+            ttl_millis: Arc::new(AtomicU64::new(ttl.as_millis() as u64)),
             enabled,
             task_queue,
+            write_locks: Arc::new(AsyncKeyLocks::new()),
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
         };
 
         if enabled {
@@ -47,6 +59,15 @@ impl CacheLayer {
         cache
     }
 
+    pub(crate) fn ttl(&self) -> Duration {
+        Duration::from_millis(self.ttl_millis.load(Ordering::Relaxed))
+    }
+
+    /// Changes the TTL of entries cached or read from now on; existing entries keep their expiry.
+    pub(crate) fn set_ttl(&self, ttl: Duration) {
+        self.ttl_millis.store(ttl.as_millis() as u64, Ordering::Relaxed);
+    }
+
     pub(crate) async fn get(&self, key: &str, cf_name: Option<String>) -> Option<String> {
         if !self.enabled {
             return None;
@@ -54,36 +75,69 @@ impl CacheLayer {
 
         let mut data = self.data.write().await;
         if let Some((value, expires_at)) = data.get_mut(&(key.to_string(), cf_name)) {
-            *expires_at = Instant::now() + self.ttl;
+            *expires_at = Instant::now() + self.ttl();
+            self.hits.fetch_add(1, Ordering::Relaxed);
             METRICS.inc_cache_hits();
             return Some(value.clone());
         }
+        self.misses.fetch_add(1, Ordering::Relaxed);
         METRICS.inc_cache_misses();
         None
     }
 
-    pub(crate) async fn put(&self, key: String, value: String, cf_name: Option<String>) {
+    pub(crate) async fn put(&self, key: String, value: String, cf_name: Option<String>, flags: WriteFlags) {
         if self.enabled {
+            let _locks = self.write_locks.lock_entries(&[(cf_name.as_deref(), &key)]).await;
             let mut data = self.data.write().await;
-            let expires_at = Instant::now() + self.ttl;
+            let expires_at = Instant::now() + self.ttl();
             data.insert((key.clone(), cf_name.clone()), (value.clone(), expires_at));
             METRICS.inc_cache_set();
             self.task_queue
-                .add_task(TaskType::Put, key, Some(value), cf_name)
+                .add_task(TaskType::Put, key, Some(value), cf_name, flags)
                 .await;
         }
     }
 
+    // Caches a value that was just read from RocksDB. Unlike `put`, nothing is queued for write-back.
+    pub(crate) async fn fill(&self, key: String, value: String, cf_name: Option<String>) {
+        if self.enabled {
+            let mut data = self.data.write().await;
+            let expires_at = Instant::now() + self.ttl();
+            data.insert((key, cf_name), (value, expires_at));
+            METRICS.inc_cache_set();
+        }
+    }
+
     pub(crate) async fn delete(&self, key: String, cf_name: Option<String>) {
         if self.enabled {
+            let _locks = self.write_locks.lock_entries(&[(cf_name.as_deref(), &key)]).await;
             let mut data = self.data.write().await;
             data.remove(&(key.clone(), cf_name.clone()));
             self.task_queue
-                .add_task(TaskType::Delete, key, None, cf_name)
+                .add_task(TaskType::Delete, key, None, cf_name, WriteFlags::default())
                 .await;
         }
     }
 
+    /// Waits until the writes queued so far have reached RocksDB.
+    pub(crate) async fn flush(&self) {
+        if self.enabled {
+            self.task_queue.flush().await;
+        }
+    }
+
+    /// Applies the queued writes of `entries` and holds back new cached writes to them until the guards
+    /// drop, so a read-modify-write can read RocksDB directly and then update the cache.
+    pub(crate) async fn settle(&self, entries: &[(Option<&str>, &str)]) -> Vec<MutexGuard<'_, ()>> {
+        if !self.enabled {
+            return Vec::new();
+        }
+
+        let locks = self.write_locks.lock_entries(entries).await;
+        self.flush().await;
+        locks
+    }
+
     pub(crate) async fn clear(&self, key: String, cf_name: Option<String>) {
         if self.enabled {
             let mut data = self.data.write().await;
@@ -91,6 +145,68 @@ impl CacheLayer {
         }
     }
 
+    pub(crate) async fn clear_cf(&self, cf_name: Option<String>) {
+        if self.enabled {
+            let mut data = self.data.write().await;
+            data.retain(|(_, cf), _| *cf != cf_name);
+        }
+    }
+
+    pub(crate) async fn len(&self) -> usize {
+        self.data.read().await.len()
+    }
+
+    pub(crate) fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Runs `write` after the queued writes and drops the `(key, cf_name)` entries it returns, so neither a queued
+    /// put nor a cached value outlives the write. Cached writes made while `write` runs land after it.
+    pub(crate) async fn write_and_clear<F>(&self, write: F) -> Result<(), String>
+    where
+        F: FnOnce() -> Result<Vec<(String, Option<String>)>, String>,
+    {
+        if !self.enabled {
+            return write().map(|_| ());
+        }
+
+        self.flush().await;
+        let keys = write()?;
+        let mut data = self.data.write().await;
+        for key in keys {
+            data.remove(&key);
+        }
+        Ok(())
+    }
+
+    /// Like `write_and_clear` for writes too large to list their keys: awaits `write` after the queued writes,
+    /// then drops every cached entry of `cf_name`, also when `write` failed part way.
+    pub(crate) async fn write_and_clear_cf<F, T>(&self, cf_name: Option<String>, write: F) -> Result<T, String>
+    where
+        F: Future<Output = Result<T, String>>,
+    {
+        if !self.enabled {
+            return write.await;
+        }
+
+        self.flush().await;
+        let written = write.await;
+        self.clear_cf(cf_name).await;
+        written
+    }
+
+    /// Drops every cached entry and returns how many there were. Queued writes still reach RocksDB.
+    pub(crate) async fn clear_all(&self) -> usize {
+        let mut data = self.data.write().await;
+        let cleared = data.len();
+        data.clear();
+        cleared
+    }
+
     async fn cleanup(&self) {
         let mut data = self.data.write().await;
         let now = Instant::now();
@@ -102,9 +218,12 @@ impl Clone for CacheLayer {
     fn clone(&self) -> Self {
         CacheLayer {
             data: self.data.clone(),
-            ttl: self.ttl,
+            ttl_millis: self.ttl_millis.clone(),
             enabled: self.enabled,
             task_queue: self.task_queue.clone(),
+            write_locks: self.write_locks.clone(),
+            hits: self.hits.clone(),
+            misses: self.misses.clone(),
         }
     }
 }
@@ -1,5 +1,5 @@
-use crate::db_manager::RocksDBManager;
-use async_std::channel::{unbounded, Receiver, Sender};
+use crate::db_manager::{RocksDBManager, WriteFlags};
+use async_std::channel::{bounded, unbounded, Receiver, Sender};
 use async_std::sync::Arc;
 use log::error;
 
@@ -8,11 +8,19 @@ pub enum TaskType {
     Delete,
 }
 
-struct Task {
+struct Write {
     task_type: TaskType,
     key: String,
     value: Option<String>,
     cf_name: Option<String>,
+    // The flags of the request that queued the write, applied when it reaches RocksDB.
+    flags: WriteFlags,
+}
+
+enum Task {
+    Write(Write),
+    // Answered once every task queued before it has been applied.
+    Flush(Sender<()>),
 }
 
 pub(crate) struct TaskQueue {
@@ -32,20 +40,40 @@ impl TaskQueue {
         key: String,
         value: Option<String>,
         cf_name: Option<String>,
+        flags: WriteFlags,
     ) {
         self.sender
-            .send(Task {
+            .send(Task::Write(Write {
                 key,
                 value,
                 cf_name,
                 task_type,
-            })
+                flags,
+            }))
             .await
             .unwrap();
     }
 
+    /// Waits until every task queued so far has reached RocksDB. Only returns while `process_tasks` runs.
+    pub(crate) async fn flush(&self) {
+        let (done, flushed) = bounded(1);
+        self.sender.send(Task::Flush(done)).await.unwrap();
+        let _ = flushed.recv().await;
+    }
+
     pub(crate) async fn process_tasks(&self, db_manager: Arc<RocksDBManager>) {
         while let Ok(task) = self.receiver.recv().await {
+            let task = match task {
+                Task::Write(task) => task,
+                Task::Flush(done) => {
+                    let _ = done.send(()).await;
+                    continue;
+                }
+            };
+            // A transaction DB kept open for the next transaction would reject the write.
+            if let Err(e) = db_manager.release_idle_transaction_db() {
+                error!("Failed to release the idle transaction DB: {}", e);
+            }
             match task.task_type {
                 TaskType::Put => {
                     if let Some(value) = task.value {
@@ -54,6 +82,7 @@ impl TaskQueue {
                             value.clone(),
                             task.cf_name.clone(),
                             None,
+                            task.flags,
                         ) {
                             error!("Failed to persist data to RocksDB: {}", e);
                         }
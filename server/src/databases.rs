@@ -0,0 +1,406 @@
+use crate::backup_stream;
+use crate::error::{ErrorCode, RequestError};
+use crate::server::{ConnectionState, Request, Response, RocksDBServer, ServerConfig};
+use async_std::io::Read;
+use async_std::task;
+use log::{debug, error, info};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// Subdirectory of `--dbpath` holding one directory per named database.
+const DATABASES_DIR: &str = "databases";
+
+/// Name the database at `--dbpath` itself is reported under in metrics and `info`.
+pub const DEFAULT_DATABASE: &str = "default";
+
+const MAX_NAME_LEN: usize = 64;
+
+/// Actions that destroy or replace data, or write files on the server, accepted only on the admin address while
+/// `--admin-address` is set.
+pub const ADMIN_ACTIONS: &[&str] = &[
+    "truncate",
+    "scan_delete",
+    "import",
+    "export",
+    "drop_column_family",
+    "delete_files_in_range",
+    "restore",
+    "restore_latest",
+    "swap_db",
+    "import_backup_stream",
+    "drop_database",
+];
+
+/// The default database plus the named databases selected by a request's `db` field.
+pub struct Databases {
+    root: PathBuf,
+    config: ServerConfig,
+    default: Arc<RocksDBServer>,
+    named: RwLock<HashMap<String, Arc<RocksDBServer>>>,
+    /// Set while `--warm-cache` is loading the block caches.
+    warming: AtomicBool,
+}
+
+/// Per-database state of one client connection, keyed by the request's `db` field.
+#[derive(Debug, Default)]
+pub struct Session {
+    connections: HashMap<Option<String>, ConnectionState>,
+    /// Whether the connection came in on the admin address.
+    admin: bool,
+}
+
+impl Session {
+    pub fn new(admin: bool) -> Self {
+        Session {
+            admin,
+            ..Session::default()
+        }
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), RequestError> {
+    let valid_chars = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+    if name.is_empty() || name.len() > MAX_NAME_LEN || !valid_chars {
+        return Err(RequestError::invalid_request(format!(
+            "Database name must be 1-{} characters of [A-Za-z0-9_-]",
+            MAX_NAME_LEN
+        )));
+    }
+    if name == DEFAULT_DATABASE {
+        return Err(RequestError::invalid_request(format!(
+            "Database name '{}' is reserved",
+            DEFAULT_DATABASE
+        )));
+    }
+    Ok(())
+}
+
+/// The config a named database opens with: its WAL and table file directories nest like its database path does.
+fn named_config(config: &ServerConfig, name: &str) -> ServerConfig {
+    ServerConfig {
+        db_config: config.db_config.nested(&Path::new(DATABASES_DIR).join(name)),
+        ..config.clone()
+    }
+}
+
+impl Databases {
+    /// Opens the default database at `db_path` and every named database found under it.
+    pub fn open(db_path: String, config: ServerConfig) -> Result<Self, String> {
+        let root = Path::new(&db_path).join(DATABASES_DIR);
+        let default = Arc::new(RocksDBServer::new(DEFAULT_DATABASE, db_path, &config)?);
+
+        let mut named = HashMap::new();
+        if root.is_dir() {
+            let entries = std::fs::read_dir(&root)
+                .map_err(|e| format!("Failed to read {}: {}", root.display(), e))?;
+            for entry in entries {
+                let path = entry.map_err(|e| e.to_string())?.path();
+                let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                if !path.is_dir() || validate_name(name).is_err() {
+                    debug!("Skipping {} in the databases directory", path.display());
+                    continue;
+                }
+
+                info!("Opening database {}", name);
+                let named_config = named_config(&config, name);
+                let server = RocksDBServer::new(name, path.to_string_lossy().into_owned(), &named_config)
+                    .map_err(|e| format!("Failed to open database {}: {}", name, e))?;
+                named.insert(name.to_string(), Arc::new(server));
+            }
+        }
+
+        Ok(Databases {
+            root,
+            config,
+            default,
+            named: RwLock::new(named),
+            warming: AtomicBool::new(false),
+        })
+    }
+
+    fn authorize(&self, req: &Request, session: &Session) -> Result<(), RequestError> {
+        let token = match &self.config.admin_token {
+            Some(admin_token) if session.admin => Some(admin_token),
+            _ => self.config.auth_token.as_ref(),
+        };
+        if token.is_some_and(|token| req.token.as_ref() != Some(token)) {
+            error!("Unauthorized request: {:?}", req);
+            return Err(RequestError::new(ErrorCode::Unauthorized, "Unauthorized"));
+        }
+
+        if self.config.admin_port && !session.admin && ADMIN_ACTIONS.contains(&req.action.as_str()) {
+            error!("Refused admin action {} on the data address", req.action);
+            return Err(RequestError::new(
+                ErrorCode::Unauthorized,
+                format!("{} is only accepted on the admin address", req.action),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Closes the default database and every named one.
+    #[cfg(test)]
+    pub(crate) fn close(&self) -> Result<(), String> {
+        self.default.close()?;
+        let named = self.named.read().map_err(|_| "Failed to read databases lock".to_string())?;
+        for server in named.values() {
+            server.close()?;
+        }
+        Ok(())
+    }
+
+    fn server(&self, db: Option<&str>) -> Result<Arc<RocksDBServer>, RequestError> {
+        let Some(db) = db else {
+            return Ok(self.default.clone());
+        };
+
+        self.named
+            .read()
+            .map_err(|_| RequestError::from("Failed to read databases lock"))?
+            .get(db)
+            .cloned()
+            .ok_or_else(|| RequestError::not_found("Database not found"))
+    }
+
+    pub(crate) async fn handle_request(&self, req: Request, session: &mut Session) -> Response {
+        if let Err(e) = self.authorize(&req, session) {
+            return Response::error(e);
+        }
+
+        let result = match req.action.as_str() {
+            "list_databases" => self.handle_list_databases(),
+            "create_database" => self.handle_create_database(req),
+            "drop_database" => self.handle_drop_database(req),
+            _ => {
+                // `"db": "default"` is the same as leaving the field out.
+                let db = req.db.clone().filter(|db| db != DEFAULT_DATABASE);
+                return match self.server(db.as_deref()) {
+                    Ok(server) => {
+                        let connection = session.connections.entry(db).or_default();
+                        server.handle_request(req, connection).await
+                    }
+                    Err(e) => Response::error(e),
+                };
+            }
+        };
+
+        match result {
+            Ok(response) => Response::success(response),
+            Err(e) => Response::error(e),
+        }
+    }
+
+    /// Executes `import_backup_stream`, whose payload of `options.length` bytes follows the request line.
+    ///
+    /// The payload is read even when the request is rejected, so the next request line is found.
+    pub(crate) async fn handle_import_backup_stream<R: Read + Unpin>(
+        &self,
+        req: Request,
+        session: &Session,
+        reader: &mut R,
+    ) -> Response {
+        let Some(length) = req.parse_option::<u64>("length") else {
+            return Response::error(RequestError::invalid_request("Length must be provided"));
+        };
+
+        let server = self
+            .authorize(&req, session)
+            .and_then(|()| self.server(req.db.as_deref().filter(|db| *db != DEFAULT_DATABASE)));
+        let result = match server {
+            Ok(server) => server.handle_import_backup_stream(reader, length).await,
+            Err(e) => match backup_stream::skip(reader, length).await {
+                Ok(()) => Err(e),
+                Err(read_error) => Err(format!("Failed to read stream: {}", read_error).into()),
+            },
+        };
+
+        match result {
+            Ok(response) => Response::success(response),
+            Err(e) => Response::error(e),
+        }
+    }
+
+    /// Releases everything a closed connection left open in any database.
+    pub(crate) fn close_session(&self, session: Session) {
+        for (db, connection) in session.connections {
+            // A database dropped meanwhile has already released its iterators.
+            if let Ok(server) = self.server(db.as_deref()) {
+                server.close_connection(connection);
+            }
+        }
+    }
+
+    /// Whether the default database is open and serving requests.
+    pub(crate) fn is_open(&self) -> bool {
+        self.default.is_open()
+    }
+
+    pub(crate) fn is_warming(&self) -> bool {
+        self.warming.load(Ordering::SeqCst)
+    }
+
+    /// Loads the block cache of every database in the background, one database at a time.
+    /// Requests are served meanwhile; only `/health` reports the warm-up.
+    pub(crate) fn start_warming(self: &Arc<Self>, prefixes: Vec<String>) {
+        self.warming.store(true, Ordering::SeqCst);
+
+        let databases = self.clone();
+        task::spawn(async move {
+            let started = Instant::now();
+            let mut servers = vec![databases.default.clone()];
+            if let Ok(named) = databases.named.read() {
+                servers.extend(named.values().cloned());
+            }
+
+            for server in servers {
+                let prefixes = prefixes.clone();
+                task::spawn_blocking(move || server.warm_block_cache(&prefixes)).await;
+            }
+
+            databases.warming.store(false, Ordering::SeqCst);
+            info!("Block cache warm-up finished in {:?}", started.elapsed());
+        });
+    }
+
+    /// Applies a reloaded cache TTL to every database.
+    pub(crate) fn set_cache_ttl(&self, ttl: Duration) {
+        self.default.set_cache_ttl(ttl);
+        if let Ok(named) = self.named.read() {
+            for server in named.values() {
+                server.set_cache_ttl(ttl);
+            }
+        }
+    }
+
+    /// Refreshes the write stall gauges of every database before metrics are scraped.
+    pub(crate) fn update_write_health_metrics(&self) {
+        self.default.update_write_health_metrics();
+        if let Ok(named) = self.named.read() {
+            for server in named.values() {
+                server.update_write_health_metrics();
+            }
+        }
+    }
+
+    /**
+     * Lists the named databases.
+     *
+     * This function handles the `list_databases` action which returns the names of all databases created with
+     * `create_database` as a sorted JSON array. The default database is not included.
+     *
+     * # Link: list_databases
+     *
+     * # Parameters
+     * - None
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    fn handle_list_databases(&self) -> Result<Option<String>, RequestError> {
+        debug!("handle_list_databases");
+
+        let mut names: Vec<String> = self
+            .named
+            .read()
+            .map_err(|_| RequestError::from("Failed to read databases lock"))?
+            .keys()
+            .cloned()
+            .collect();
+        names.sort();
+
+        Ok(Some(serde_json::to_string(&names).unwrap()))
+    }
+
+    /**
+     * Creates a named database.
+     *
+     * This function handles the `create_database` action which opens a new database under
+     * `<dbpath>/databases/<db>`. Requests select it with the same `db` field.
+     *
+     * # Link: create_database
+     *
+     * # Parameters
+     * - `db`: String - The database name, 1-64 characters of `[A-Za-z0-9_-]`
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    fn handle_create_database(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_create_database with db: {:?}", req.db);
+
+        let name = req
+            .db
+            .ok_or_else(|| RequestError::invalid_request("Database name must be provided"))?;
+        validate_name(&name)?;
+
+        let mut named = self
+            .named
+            .write()
+            .map_err(|_| RequestError::from("Failed to write databases lock"))?;
+        if named.contains_key(&name) {
+            return Err(RequestError::invalid_request("Database already exists"));
+        }
+
+        let path = self.root.join(&name);
+        let config = named_config(&self.config, &name);
+        let server = RocksDBServer::new(&name, path.to_string_lossy().into_owned(), &config)?;
+        // Pick up a cache TTL changed by a reload since startup.
+        server.set_cache_ttl(self.default.cache_ttl());
+        named.insert(name, Arc::new(server));
+        Ok(None)
+    }
+
+    /**
+     * Drops a named database.
+     *
+     * This function handles the `drop_database` action which closes the database and deletes its directory,
+     * including its backups. Requests still running against it fail.
+     *
+     * # Link: drop_database
+     *
+     * # Parameters
+     * - `db`: String - The database name
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    fn handle_drop_database(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_drop_database with db: {:?}", req.db);
+
+        let name = req
+            .db
+            .ok_or_else(|| RequestError::invalid_request("Database name must be provided"))?;
+
+        let server = self
+            .named
+            .write()
+            .map_err(|_| RequestError::from("Failed to write databases lock"))?
+            .remove(&name)
+            .ok_or_else(|| RequestError::not_found("Database not found"))?;
+        server.close()?;
+
+        let path = self.root.join(&name);
+        std::fs::remove_dir_all(&path)
+            .map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+        for dir in named_config(&self.config, &name).db_config.storage_dirs() {
+            if Path::new(dir).exists() {
+                std::fs::remove_dir_all(dir).map_err(|e| format!("Failed to delete {}: {}", dir, e))?;
+            }
+        }
+        Ok(None)
+    }
+}
@@ -1,21 +1,30 @@
+use base64::prelude::{Engine, BASE64_STANDARD};
 use json_patch::{Patch, PatchOperation};
 use log::{debug, error, info};
 use rust_rocksdb::backup::{BackupEngine, BackupEngineInfo, BackupEngineOptions, RestoreOptions};
+use rust_rocksdb::checkpoint::Checkpoint;
 use rust_rocksdb::{
-    Cache, ColumnFamilyDescriptor, DBCompressionType, DBWithThreadMode, Env, MergeOperands,
-    MultiThreaded, Options, Transaction, TransactionDB, TransactionDBOptions, TransactionOptions,
-    WriteBatchWithTransaction, WriteOptions,
+    BoundColumnFamily, Cache, ColumnFamilyDescriptor, DBAccess, DBPath, DBCompactionStyle, DBCompressionType, DBIteratorWithThreadMode,
+    DBWithThreadMode, Env, FifoCompactOptions, MergeOperands, MultiThreaded, Options, ReadOptions, ReadTier, SnapshotWithThreadMode, Transaction, TransactionDB, TransactionDBOptions,
+    TransactionOptions, WriteBatchWithTransaction, WriteOptions, DEFAULT_COLUMN_FAMILY_NAME,
 };
+use crate::key_locks::KeyLocks;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::Path;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, Condvar, Mutex, RwLock};
-use std::time::Duration;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock, RwLockWriteGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub type DbInstance = Arc<RwLock<Option<DBWithThreadMode<MultiThreaded>>>>;
 
+// Snapshots borrow the open DB; they are released before it is closed or replaced, see `replace_db`.
+type DbSnapshot = SnapshotWithThreadMode<'static, DBWithThreadMode<MultiThreaded>>;
+
 pub fn json_merge(
     _new_key: &[u8],
     existing_val: Option<&[u8]>,
@@ -47,6 +56,107 @@ pub fn json_merge(
     }
 }
 
+/// Operation `merge` applies in Rust when a request names one, instead of the column family's merge operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeOp {
+    /// RFC 6902 JSON Patch, the same operation as the column family's `json_merge` operator.
+    JsonPatch,
+    /// RFC 7396 JSON Merge Patch.
+    MergePatch,
+    NumericAdd,
+    StringAppend,
+}
+
+impl FromStr for MergeOp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json-patch" => Ok(MergeOp::JsonPatch),
+            "merge-patch" => Ok(MergeOp::MergePatch),
+            "numeric-add" => Ok(MergeOp::NumericAdd),
+            "string-append" => Ok(MergeOp::StringAppend),
+            _ => Err(format!("Unknown merge_op: {}", s)),
+        }
+    }
+}
+
+fn parse_number(value: &str) -> Option<serde_json::Number> {
+    match serde_json::from_str(value.trim()) {
+        Ok(Value::Number(number)) => Some(number),
+        _ => None,
+    }
+}
+
+/// The value of an integer that fits in i64 or u64.
+fn as_integer(number: &serde_json::Number) -> Option<i128> {
+    number.as_i64().map(i128::from).or_else(|| number.as_u64().map(i128::from))
+}
+
+/// Whether the number is an integer too wide for i64 and u64, which adding as a float would round.
+fn is_wide_integer(number: &serde_json::Number) -> bool {
+    as_integer(number).is_none() && !number.to_string().contains(['.', 'e', 'E'])
+}
+
+impl MergeOp {
+    /// Computes the new value from the stored one, or explains why the two do not combine.
+    pub fn apply(&self, existing: Option<&str>, operand: &str) -> Result<String, String> {
+        match self {
+            MergeOp::JsonPatch => {
+                let patch: Vec<PatchOperation> = serde_json::from_str(operand)
+                    .map_err(|e| format!("Value is not a JSON Patch: {}", e))?;
+                // A missing key starts from an empty array, like the `json_merge` operator.
+                let mut doc = match existing {
+                    Some(existing) => serde_json::from_str(existing)
+                        .map_err(|e| format!("Stored value is not valid JSON: {}", e))?,
+                    None => Value::Array(vec![]),
+                };
+                json_patch::patch(&mut doc, &patch)
+                    .map_err(|e| format!("Failed to apply patch: {}", e))?;
+                Ok(doc.to_string())
+            }
+            MergeOp::MergePatch => {
+                let patch: Value = serde_json::from_str(operand)
+                    .map_err(|e| format!("Value is not valid JSON: {}", e))?;
+                let mut doc = match existing {
+                    Some(existing) => serde_json::from_str(existing)
+                        .map_err(|e| format!("Stored value is not valid JSON: {}", e))?,
+                    None => Value::Null,
+                };
+                json_patch::merge(&mut doc, &patch);
+                Ok(doc.to_string())
+            }
+            MergeOp::NumericAdd => {
+                let by = parse_number(operand).ok_or("Value is not a number")?;
+                let current = match existing {
+                    Some(existing) => parse_number(existing).ok_or("Stored value is not a number")?,
+                    None => 0.into(),
+                };
+                // Integers stay exact, in i64 or u64; anything with a fraction is added as a float.
+                let sum = match (as_integer(&current), as_integer(&by)) {
+                    (Some(current), Some(by)) => {
+                        let sum = current + by;
+                        i64::try_from(sum)
+                            .map(serde_json::Number::from)
+                            .or_else(|_| u64::try_from(sum).map(serde_json::Number::from))
+                            .map_err(|_| "Result does not fit in a 64-bit integer")?
+                    }
+                    _ if is_wide_integer(&current) || is_wide_integer(&by) => {
+                        return Err("Integers beyond 64 bits are not supported".to_string())
+                    }
+                    _ => current
+                        .as_f64()
+                        .zip(by.as_f64())
+                        .and_then(|(current, by)| serde_json::Number::from_f64(current + by))
+                        .ok_or("Result is not a finite number")?,
+                };
+                Ok(sum.to_string())
+            }
+            MergeOp::StringAppend => Ok(format!("{}{}", existing.unwrap_or(""), operand)),
+        }
+    }
+}
+
 fn create_transaction(transaction_db: &Arc<TransactionDB>) -> Transaction<'static, TransactionDB> {
     let txn_opts = TransactionOptions::default();
     let write_opts = WriteOptions::default();
@@ -57,6 +167,237 @@ fn create_transaction(transaction_db: &Arc<TransactionDB>) -> Transaction<'stati
     }
 }
 
+/// Startup tuning applied when the database is opened.
+#[derive(Debug, Clone, Default)]
+pub struct DbConfig {
+    /// Background thread count passed to `increase_parallelism`; defaults to `available_cpus`.
+    pub parallelism: Option<i32>,
+    /// Flush all column families together so a crash never leaves them at different recovery points.
+    pub atomic_flush: bool,
+    /// Write the WAL and the memtables in a pipeline, which raises throughput under concurrent writes.
+    pub pipelined_write: bool,
+    /// Cap on flush and compaction I/O in bytes per second; `None` leaves it unlimited.
+    pub rate_limiter_bytes_per_sec: Option<i64>,
+    pub compaction: CompactionConfig,
+    /// Directory for the write-ahead log; `None` keeps it in the database path.
+    pub wal_dir: Option<String>,
+    /// Directories for table files, filled in order; empty keeps them in the database path.
+    pub db_paths: Vec<DbPath>,
+    /// Directory for backups; `None` keeps them in `backup` inside the database path.
+    pub backup_path: Option<String>,
+}
+
+impl DbConfig {
+    /// The same config with `subdir` appended to the WAL, table file and backup directories, so databases that
+    /// share them keep their files apart.
+    pub fn nested(&self, subdir: &Path) -> DbConfig {
+        let nest = |dir: &str| Path::new(dir).join(subdir).to_string_lossy().into_owned();
+        DbConfig {
+            wal_dir: self.wal_dir.as_deref().map(nest),
+            backup_path: self.backup_path.as_deref().map(nest),
+            db_paths: self
+                .db_paths
+                .iter()
+                .map(|db_path| DbPath {
+                    path: nest(&db_path.path),
+                    target_size: db_path.target_size,
+                })
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// The directories outside the database path that hold its files and backups.
+    pub fn storage_dirs(&self) -> Vec<&str> {
+        self.wal_dir
+            .iter()
+            .chain(self.db_paths.iter().map(|db_path| &db_path.path))
+            .chain(self.backup_path.iter())
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// A table file directory and the total size RocksDB keeps in it before moving on to the next one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DbPath {
+    pub path: String,
+    pub target_size: u64,
+}
+
+impl FromStr for DbPath {
+    type Err = String;
+
+    /// Parses `PATH:TARGET_BYTES`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, target_size) = s
+            .rsplit_once(':')
+            .ok_or_else(|| format!("Expected PATH:TARGET_BYTES, got {}", s))?;
+        if path.is_empty() {
+            return Err(format!("Missing path in {}", s));
+        }
+        let target_size = target_size
+            .parse::<u64>()
+            .map_err(|_| format!("Invalid target size in {}", s))?;
+        Ok(DbPath {
+            path: path.to_string(),
+            target_size,
+        })
+    }
+}
+
+/// Compaction style applied to every column family.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompactionStyle {
+    #[default]
+    Level,
+    Universal,
+    Fifo,
+}
+
+impl FromStr for CompactionStyle {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "level" => Ok(CompactionStyle::Level),
+            "universal" => Ok(CompactionStyle::Universal),
+            "fifo" => Ok(CompactionStyle::Fifo),
+            _ => Err("no match"),
+        }
+    }
+}
+
+impl CompactionStyle {
+    pub fn variants() -> [&'static str; 3] {
+        ["level", "universal", "fifo"]
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompactionConfig {
+    pub style: CompactionStyle,
+    /// Number of LSM levels; `None` keeps RocksDB's default of 7.
+    pub num_levels: Option<i32>,
+    /// Total table file size after which FIFO compaction deletes the oldest files; `None` keeps RocksDB's 1 GB.
+    pub fifo_max_size: Option<u64>,
+}
+
+// FIFO compaction tracks file ages and needs every table file kept open.
+fn max_open_files(compaction: CompactionConfig) -> i32 {
+    match compaction.style {
+        CompactionStyle::Fifo => -1,
+        _ => 1000,
+    }
+}
+
+// Compaction settings are per column family, so every descriptor needs them, not just the DB options.
+fn column_family_options(compaction: CompactionConfig) -> Options {
+    let mut opts = Options::default();
+    opts.set_merge_operator_associative("json_merge", json_merge);
+    match compaction.style {
+        CompactionStyle::Level => opts.set_compaction_style(DBCompactionStyle::Level),
+        CompactionStyle::Universal => opts.set_compaction_style(DBCompactionStyle::Universal),
+        CompactionStyle::Fifo => opts.set_compaction_style(DBCompactionStyle::Fifo),
+    }
+    if let Some(max_size) = compaction.fifo_max_size {
+        let mut fifo = FifoCompactOptions::default();
+        fifo.set_max_table_files_size(max_size);
+        opts.set_fifo_compaction_options(&fifo);
+    }
+    if let Some(num_levels) = compaction.num_levels {
+        opts.set_num_levels(num_levels);
+    }
+    opts
+}
+
+// Refill period and fairness are RocksDB's defaults.
+fn set_rate_limiter(opts: &mut Options, rate_bytes_per_sec: Option<i64>) {
+    if let Some(rate_bytes_per_sec) = rate_bytes_per_sec {
+        opts.set_ratelimiter(rate_bytes_per_sec, 100_000, 10);
+    }
+}
+
+fn set_storage_paths(opts: &mut Options, wal_dir: Option<&str>, db_paths: &[DbPath]) -> Result<(), String> {
+    if let Some(wal_dir) = wal_dir {
+        opts.set_wal_dir(wal_dir);
+    }
+    if !db_paths.is_empty() {
+        let db_paths = db_paths
+            .iter()
+            .map(|db_path| DBPath::new(&db_path.path, db_path.target_size).map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        opts.set_db_paths(&db_paths);
+    }
+    Ok(())
+}
+
+fn iterator_mode(key: &str) -> rust_rocksdb::IteratorMode<'_> {
+    rust_rocksdb::IteratorMode::From(key.as_bytes(), rust_rocksdb::Direction::Forward)
+}
+
+// RocksDB honours the memtable tier only for iterators, so memtable point reads seek the key instead.
+fn seek_exact<D: DBAccess>(
+    mut iter: DBIteratorWithThreadMode<'_, D>,
+    key: &str,
+) -> Result<Option<Vec<u8>>, rust_rocksdb::Error> {
+    match iter.next() {
+        Some(Ok((found, value))) if *found == *key.as_bytes() => Ok(Some(value.to_vec())),
+        Some(Err(e)) => Err(e),
+        _ => Ok(None),
+    }
+}
+
+/// Per-request read tuning taken from the request `options`.
+#[derive(Debug, Clone, Copy)]
+pub struct ReadFlags {
+    /// Verify block checksums on every read. Disabling it trades corruption
+    /// detection for read throughput and should only be used on trusted storage.
+    pub verify_checksums: bool,
+    /// Storage tier a point lookup may read from. With `ReadTier::Memtable`, keys that
+    /// were already flushed to SST files are reported as missing.
+    pub read_tier: ReadTier,
+}
+
+impl Default for ReadFlags {
+    fn default() -> Self {
+        ReadFlags {
+            verify_checksums: true,
+            read_tier: ReadTier::All,
+        }
+    }
+}
+
+impl ReadFlags {
+    fn read_options(&self) -> ReadOptions {
+        let mut opts = ReadOptions::default();
+        opts.set_verify_checksums(self.verify_checksums);
+        opts.set_read_tier(self.read_tier);
+        opts
+    }
+
+    fn memtable_only(&self) -> bool {
+        self.read_tier == ReadTier::Memtable
+    }
+}
+
+/// Per-request write tuning taken from the request `options`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriteFlags {
+    /// Write at low priority: RocksDB slows such writes down first while compactions fall behind,
+    /// so background writes leave the stall budget to foreground ones.
+    pub low_priority: bool,
+}
+
+impl WriteFlags {
+    fn write_options(&self) -> WriteOptions {
+        let mut opts = WriteOptions::default();
+        opts.set_low_pri(self.low_priority);
+        opts
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct BackupInfo {
     timestamp: i64,
@@ -76,46 +417,456 @@ impl From<BackupEngineInfo> for BackupInfo {
     }
 }
 
+/// One line of an `export` file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportRecord {
+    pub key: String,
+    pub value: String,
+    /// Set when `key` and `value` are base64-encoded because one of them is not valid UTF-8.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub base64: bool,
+}
+
+impl ExportRecord {
+    fn new(key: &[u8], value: &[u8]) -> Self {
+        match (std::str::from_utf8(key), std::str::from_utf8(value)) {
+            (Ok(key), Ok(value)) => ExportRecord {
+                key: key.to_string(),
+                value: value.to_string(),
+                base64: false,
+            },
+            _ => ExportRecord {
+                key: BASE64_STANDARD.encode(key),
+                value: BASE64_STANDARD.encode(value),
+                base64: true,
+            },
+        }
+    }
+
+    fn into_pair(self) -> Result<(Vec<u8>, Vec<u8>), String> {
+        if !self.base64 {
+            return Ok((self.key.into_bytes(), self.value.into_bytes()));
+        }
+        let key = BASE64_STANDARD
+            .decode(&self.key)
+            .map_err(|e| format!("Invalid base64 key: {}", e))?;
+        let value = BASE64_STANDARD
+            .decode(&self.value)
+            .map_err(|e| format!("Invalid base64 value: {}", e))?;
+        Ok((key, value))
+    }
+}
+
+/// Only the first errors are kept in an [`ImportReport`]; the rest are just counted.
+const MAX_IMPORT_ERRORS: usize = 100;
+
+/// Entries `warm_block_cache` reads per hold of the DB lock.
+const WARM_CHUNK_SIZE: u64 = 10_000;
+
+/// Outcome of an `import` run.
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    /// Keys left untouched because they already existed and `overwrite` was off.
+    pub skipped: usize,
+    /// Lines that could not be parsed or whose key is too long.
+    pub failed: usize,
+    pub errors: Vec<String>,
+}
+
+impl ImportReport {
+    fn record_error(&mut self, line: usize, error: String) {
+        self.failed += 1;
+        if self.errors.len() < MAX_IMPORT_ERRORS {
+            self.errors.push(format!("line {}: {}", line, error));
+        }
+    }
+}
+
+/// Condition a value must meet to be removed by `scan_delete`.
+#[derive(Debug)]
+pub enum ValuePredicate {
+    Prefix(String),
+    Contains(String),
+    /// The JSON document has `value` at the JSON Pointer `pointer`, e.g. `/expired`.
+    JsonEquals { pointer: String, value: Value },
+}
+
+impl ValuePredicate {
+    fn matches(&self, value: &[u8]) -> bool {
+        match self {
+            ValuePredicate::Prefix(prefix) => value.starts_with(prefix.as_bytes()),
+            ValuePredicate::Contains(needle) => value
+                .windows(needle.len().max(1))
+                .any(|window| window == needle.as_bytes()),
+            ValuePredicate::JsonEquals { pointer, value: expected } => {
+                serde_json::from_slice::<Value>(value)
+                    .is_ok_and(|doc| doc.pointer(pointer) == Some(expected))
+            }
+        }
+    }
+}
+
+/// The smallest key greater than every key starting with `prefix`, or `None` if there is none.
+fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut bound = prefix.to_vec();
+    while let Some(last) = bound.pop() {
+        if last < u8::MAX {
+            bound.push(last + 1);
+            return Some(bound);
+        }
+    }
+    None
+}
+
+/// CPUs this process may run on. In a container this honors the cgroup CPU quota as well as the
+/// CPU affinity mask, so a pod limited to 2 CPUs gets 2 threads rather than one per node core.
+fn available_cpus() -> usize {
+    std::thread::available_parallelism()
+        .map(|cpus| cpus.get())
+        .unwrap_or_else(|_| num_cpus::get())
+}
+
+/// Size of the SST files of a column family, the default one for `None`, including files that
+/// snapshots and iterators still hold on to.
+fn total_sst_size(
+    db: &DBWithThreadMode<MultiThreaded>,
+    cf: Option<&Arc<BoundColumnFamily>>,
+) -> Result<u64, String> {
+    match cf {
+        Some(cf) => db.property_int_value_cf(cf, "rocksdb.total-sst-files-size"),
+        None => db.property_int_value("rocksdb.total-sst-files-size"),
+    }
+    .map(|size| size.unwrap_or(0))
+    .map_err(|e| e.to_string())
+}
+
+/// Subdirectory of the database path that holds the current files while `swap_db` moves them.
+const SWAP_DIR: &str = "swap";
+
+// Moves the regular files only: the `backup` and `databases` subdirectories stay with `--dbpath`.
+fn move_files(from: &Path, to: &Path) -> Result<(), String> {
+    let entries =
+        std::fs::read_dir(from).map_err(|e| format!("Failed to read {}: {}", from.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        if !entry.file_type().map_err(|e| e.to_string())?.is_file() {
+            continue;
+        }
+        let target = to.join(entry.file_name());
+        std::fs::rename(entry.path(), &target).map_err(|e| {
+            format!("Failed to move {} to {}: {}", entry.path().display(), target.display(), e)
+        })?;
+    }
+    Ok(())
+}
+
+// Exchanges the files of `a` and `b` through `tmp`, moving everything back if a step fails.
+fn swap_files(a: &Path, b: &Path, tmp: &Path) -> Result<(), String> {
+    std::fs::create_dir(tmp).map_err(|e| format!("Failed to create {}: {}", tmp.display(), e))?;
+
+    let result = move_files(a, tmp).map_err(|e| (0, e));
+    let result = result.and_then(|_| move_files(b, a).map_err(|e| (1, e)));
+    let result = result.and_then(|_| move_files(tmp, b).map_err(|e| (2, e)));
+
+    if let Err((step, e)) = result {
+        let undo = match step {
+            0 => move_files(tmp, a),
+            1 => move_files(a, b).and_then(|_| move_files(tmp, a)),
+            _ => move_files(b, tmp)
+                .and_then(|_| move_files(a, b))
+                .and_then(|_| move_files(tmp, a)),
+        };
+        if let Err(undo_error) = undo {
+            return Err(format!(
+                "{}; moving the files back also failed, see {}: {}",
+                e,
+                tmp.display(),
+                undo_error
+            ));
+        }
+        let _ = std::fs::remove_dir(tmp);
+        return Err(e);
+    }
+
+    std::fs::remove_dir(tmp).map_err(|e| format!("Failed to remove {}: {}", tmp.display(), e))
+}
+
+/// A key and value returned by `scan_open` or `iterator_next_batch`, base64-encoded for `base64` iterators.
+pub type IteratorEntry = (String, String);
+
+type RawEntry = (Box<[u8]>, Box<[u8]>);
+
+/// Position, key range and scan direction of a server-side iterator.
+#[derive(Debug, Default)]
+struct IteratorState {
+    /// Current key, `None` until the iterator is first positioned.
+    position: Option<Vec<u8>>,
+    /// First key the iterator may return.
+    lower_bound: Option<Vec<u8>>,
+    /// Key the iterator stops before.
+    upper_bound: Option<Vec<u8>>,
+    /// Whether `iterator_next_batch` walks backward.
+    reverse: bool,
+    /// Whether keys and values are base64-encoded, both the ones returned and the keys sent to seek.
+    base64: bool,
+}
+
+impl IteratorState {
+    fn direction(&self) -> rust_rocksdb::Direction {
+        if self.reverse {
+            rust_rocksdb::Direction::Reverse
+        } else {
+            rust_rocksdb::Direction::Forward
+        }
+    }
+
+    fn read_options(&self, flags: ReadFlags) -> ReadOptions {
+        let mut opts = flags.read_options();
+        if let Some(lower_bound) = &self.lower_bound {
+            opts.set_iterate_lower_bound(lower_bound.clone());
+        }
+        if let Some(upper_bound) = &self.upper_bound {
+            opts.set_iterate_upper_bound(upper_bound.clone());
+        }
+        opts
+    }
+
+    fn decode_key(&self, key: String) -> Result<Vec<u8>, String> {
+        if !self.base64 {
+            return Ok(key.into_bytes());
+        }
+        BASE64_STANDARD
+            .decode(key)
+            .map_err(|e| format!("Invalid base64 key: {}", e))
+    }
+
+    fn encode_entries(&self, entries: Vec<RawEntry>) -> Vec<IteratorEntry> {
+        entries
+            .into_iter()
+            .map(|(key, value)| {
+                if self.base64 {
+                    (BASE64_STANDARD.encode(key), BASE64_STANDARD.encode(value))
+                } else {
+                    (
+                        String::from_utf8_lossy(&key).into_owned(),
+                        String::from_utf8_lossy(&value).into_owned(),
+                    )
+                }
+            })
+            .collect()
+    }
+
+    // Base64 never contains `:`, so the pair stays unambiguous; UTF-8 keeps the historical `invalid` placeholder.
+    fn format_entry(&self, key: &[u8], value: &[u8]) -> String {
+        if self.base64 {
+            return format!("{}:{}", BASE64_STANDARD.encode(key), BASE64_STANDARD.encode(value));
+        }
+        format!(
+            "{}:{}",
+            String::from_utf8(key.to_vec()).unwrap_or_else(|_| "invalid".to_string()),
+            String::from_utf8(value.to_vec()).unwrap_or_else(|_| "invalid".to_string())
+        )
+    }
+}
+
+// Collects up to `limit` entries in `direction`, starting at `seek` or else right after the stored
+// position. Each walk re-seeks from the stored key, which is skipped only if it still exists, so a
+// position deleted since the last walk does not cost its neighbour. The position moves to the last
+// entry returned; the flag reports that no entries are left in `direction`.
+fn walk_iterator(
+    db: &DBWithThreadMode<MultiThreaded>,
+    state: &mut IteratorState,
+    seek: Option<&[u8]>,
+    direction: rust_rocksdb::Direction,
+    limit: usize,
+    flags: ReadFlags,
+) -> Result<(Vec<RawEntry>, bool), String> {
+    let position = state.position.clone();
+    let (mode, skip) = match (seek, position.as_deref(), direction) {
+        (Some(key), _, direction) => (rust_rocksdb::IteratorMode::From(key, direction), None),
+        (None, Some(key), direction) => (rust_rocksdb::IteratorMode::From(key, direction), Some(key)),
+        (None, None, rust_rocksdb::Direction::Forward) => (rust_rocksdb::IteratorMode::Start, None),
+        (None, None, rust_rocksdb::Direction::Reverse) => (rust_rocksdb::IteratorMode::End, None),
+    };
+    let mut iter = db.iterator_opt(mode, state.read_options(flags));
+
+    let mut next = iter.next().transpose().map_err(|e| e.to_string())?;
+    if let (Some(key), Some((k, _))) = (skip, &next) {
+        if **k == *key {
+            next = iter.next().transpose().map_err(|e| e.to_string())?;
+        }
+    }
+
+    let mut entries = Vec::new();
+    let mut exhausted = true;
+    while let Some(entry) = next {
+        if entries.len() >= limit {
+            exhausted = false;
+            break;
+        }
+        entries.push(entry);
+        next = iter.next().transpose().map_err(|e| e.to_string())?;
+    }
+
+    if let Some((k, _)) = entries.last() {
+        state.position = Some(k.to_vec());
+    }
+    Ok((entries, exhausted))
+}
+
+/// Column family options `set_db_option` may change on a running database.
+pub const MUTABLE_OPTIONS: &[&str] = &[
+    "disable_auto_compactions",
+    "write_buffer_size",
+    "max_write_buffer_number",
+    "level0_file_num_compaction_trigger",
+    "level0_slowdown_writes_trigger",
+    "level0_stop_writes_trigger",
+    "target_file_size_base",
+    "max_bytes_for_level_base",
+    "soft_pending_compaction_bytes_limit",
+    "hard_pending_compaction_bytes_limit",
+];
+
+/// Key count and SST size estimates of one column family.
+#[derive(Debug, Serialize)]
+pub struct StorageStats {
+    pub estimate_num_keys: u64,
+    /// Bytes still reachable; the rest of `total_sst_size` is obsolete data waiting for compaction.
+    pub live_data_size: u64,
+    pub total_sst_size: u64,
+    /// Number of SST files per level, starting at level 0.
+    pub num_files_at_level: Vec<u64>,
+}
+
+/// Options a column family is running with, as RocksDB persisted them in its latest OPTIONS file.
+#[derive(Debug, Default, Serialize)]
+pub struct EffectiveOptions {
+    pub db_options: BTreeMap<String, String>,
+    pub cf_options: BTreeMap<String, String>,
+    pub table_options: BTreeMap<String, String>,
+}
+
+/// A bulk load started by `begin_bulk_load`.
+#[derive(Debug, Clone, Copy)]
+struct BulkLoad {
+    auto_compactions_disabled: bool,
+}
+
+/// Write stall state reported by RocksDB.
+#[derive(Debug)]
+pub struct WriteHealth {
+    /// Rate in bytes per second that writes are throttled to, `0` when they are not delayed.
+    pub delayed_write_rate: u64,
+    /// Writes are stopped until compaction catches up.
+    pub write_stopped: bool,
+}
+
+impl WriteHealth {
+    pub fn is_stalled(&self) -> bool {
+        self.write_stopped || self.delayed_write_rate > 0
+    }
+}
+
+struct NamedSnapshot {
+    // Tells a snapshot apart from a later one created under the same name.
+    id: u64,
+    snapshot: DbSnapshot,
+    info: SnapshotInfo,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotInfo {
+    pub name: String,
+    /// Sequence number of the last write the snapshot sees.
+    pub sequence_number: u64,
+    /// Unix time in seconds.
+    pub created_at: u64,
+    /// Unix time in seconds after which the snapshot is released; `None` keeps it until `release_snapshot`.
+    pub expires_at: Option<u64>,
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// The batch built by the `write_batch_*` actions, with the keys it writes as `(key, cf_name)`.
+#[derive(Default)]
+struct PendingBatch {
+    batch: WriteBatchWithTransaction<false>,
+    keys: Vec<(String, Option<String>)>,
+}
+
+/// Errors for a missing column family, iterator or named snapshot; requests failing with them get `NOT_FOUND`.
+pub const CF_NOT_FOUND: &str = "Column family not found";
+pub const ITERATOR_NOT_FOUND: &str = "Iterator ID not found";
+pub const SNAPSHOT_NOT_FOUND: &str = "Snapshot not found";
+
+/// Error of the write batch actions after `write_batch_destroy`.
+const BATCH_NOT_INITIALIZED: &str = "WriteBatch not initialized, send write_batch_create first";
+
 pub struct RocksDBManager {
     pub db: DbInstance,
     pub db_path: String,
-    write_batch: Mutex<Option<WriteBatchWithTransaction<false>>>,
-    iterators: Mutex<HashMap<usize, (Vec<u8>, rust_rocksdb::Direction)>>,
+    pub parallelism: i32,
+    pub atomic_flush: bool,
+    pub pipelined_write: bool,
+    pub rate_limiter_bytes_per_sec: Option<i64>,
+    pub compaction: CompactionConfig,
+    pub wal_dir: Option<String>,
+    pub db_paths: Vec<DbPath>,
+    pub backup_path: String,
+    write_batch: Mutex<Option<PendingBatch>>,
+    bulk_load: Mutex<Option<BulkLoad>>,
+    iterators: Mutex<HashMap<usize, IteratorState>>,
     iterator_id_counter: AtomicUsize,
     txn_db: Mutex<Option<Arc<TransactionDB>>>,
     transaction: Mutex<Option<Transaction<'static, TransactionDB>>>,
+    // Id of the most recently begun transaction.
+    transaction_id: AtomicU64,
     condvar: Condvar,
+    key_locks: KeyLocks,
+    snapshots: Mutex<HashMap<String, NamedSnapshot>>,
+    snapshot_id: AtomicU64,
+}
+
+impl Drop for RocksDBManager {
+    // Fields drop in order, so without this `db` could close before the snapshots on it are released.
+    fn drop(&mut self) {
+        if let Ok(snapshots) = self.snapshots.get_mut() {
+            snapshots.clear();
+        }
+    }
 }
 
 impl RocksDBManager {
-    fn begin_transaction_internal(
-        &self,
-    ) -> Result<(Arc<TransactionDB>, Transaction<'static, TransactionDB>), String> {
+    fn open_transaction_db(&self) -> Result<Arc<TransactionDB>, String> {
         let txn_db_opts = TransactionDBOptions::default();
         let mut opts = Options::default();
         opts.create_if_missing(true);
-        opts.set_max_open_files(1000);
+        opts.set_max_open_files(max_open_files(self.compaction));
         opts.set_log_level(rust_rocksdb::LogLevel::Warn);
+        opts.set_atomic_flush(self.atomic_flush);
+        opts.set_enable_pipelined_write(self.pipelined_write);
+        set_rate_limiter(&mut opts, self.rate_limiter_bytes_per_sec);
+        set_storage_paths(&mut opts, self.wal_dir.as_deref(), &self.db_paths)?;
 
         let cf_names = DBWithThreadMode::<MultiThreaded>::list_cf(&opts, &self.db_path)
             .unwrap_or(vec!["default".to_string()]);
         let cf_descriptors: Vec<ColumnFamilyDescriptor> = cf_names
             .iter()
-            .map(|name| {
-                let mut cf_opts = Options::default();
-                cf_opts.set_merge_operator_associative("json_merge", json_merge);
-                ColumnFamilyDescriptor::new(name, cf_opts)
-            })
+            .map(|name| ColumnFamilyDescriptor::new(name, column_family_options(self.compaction)))
             .collect();
 
         let transaction_db =
             TransactionDB::open_cf_descriptors(&opts, &txn_db_opts, &self.db_path, cf_descriptors)
                 .map_err(|e| e.to_string())?;
 
-        let transaction_db = Arc::new(transaction_db);
-        let transaction = create_transaction(&transaction_db);
-
-        Ok((transaction_db, transaction))
+        Ok(Arc::new(transaction_db))
     }
 
     fn put_in_transaction(
@@ -134,7 +885,7 @@ impl RocksDBManager {
                 let txn_db = txn_db_lock.as_ref().ok_or("No active transaction DB")?;
                 let cf = txn_db
                     .cf_handle(&cf_name)
-                    .ok_or("Column family not found")?;
+                    .ok_or(CF_NOT_FOUND)?;
                 txn.put_cf(&cf, key.as_bytes(), value.as_bytes())
                     .map_err(|e| e.to_string())
             }
@@ -144,21 +895,22 @@ impl RocksDBManager {
         }
     }
 
-    fn put_in_db(&self, key: &str, value: &str, cf_name: Option<String>) -> Result<(), String> {
+    fn put_in_db(&self, key: &str, value: &str, cf_name: Option<String>, flags: WriteFlags) -> Result<(), String> {
         let db = self
             .db
             .read()
             .map_err(|_| "Failed to read DB lock".to_string())?;
         let db = db.as_ref().ok_or("Database is not open")?;
+        let _guards = self.key_locks.lock(cf_name.as_deref(), &[key])?;
 
         match cf_name {
             Some(cf_name) => {
-                let cf = db.cf_handle(&cf_name).ok_or("Column family not found")?;
-                db.put_cf(&cf, key.as_bytes(), value.as_bytes())
+                let cf = db.cf_handle(&cf_name).ok_or(CF_NOT_FOUND)?;
+                db.put_cf_opt(&cf, key.as_bytes(), value.as_bytes(), &flags.write_options())
                     .map_err(|e| e.to_string())
             }
             None => db
-                .put(key.as_bytes(), value.as_bytes())
+                .put_opt(key.as_bytes(), value.as_bytes(), &flags.write_options())
                 .map_err(|e| e.to_string()),
         }
     }
@@ -169,6 +921,7 @@ impl RocksDBManager {
         key: &str,
         cf_name: Option<String>,
         default: Option<String>,
+        flags: ReadFlags,
     ) -> Result<Option<String>, String> {
         let get_value = |value: Option<Vec<u8>>| {
             value
@@ -176,6 +929,7 @@ impl RocksDBManager {
                 .transpose()
                 .map(|opt| opt.or(default.clone()))
         };
+        let read_opts = flags.read_options();
 
         match cf_name {
             Some(cf_name) => {
@@ -186,15 +940,22 @@ impl RocksDBManager {
                 let txn_db = txn_db_lock.as_ref().ok_or("No active transaction DB")?;
                 let cf = txn_db
                     .cf_handle(&cf_name)
-                    .ok_or("Column family not found")?;
-                txn.get_cf(&cf, key.as_bytes())
-                    .map_err(|e| e.to_string())
-                    .and_then(get_value)
-            }
-            None => txn
-                .get(key.as_bytes())
+                    .ok_or(CF_NOT_FOUND)?;
+                if flags.memtable_only() {
+                    seek_exact(txn.iterator_cf_opt(&cf, read_opts, iterator_mode(key)), key)
+                } else {
+                    txn.get_cf_opt(&cf, key.as_bytes(), &read_opts)
+                }
                 .map_err(|e| e.to_string())
-                .and_then(get_value),
+                .and_then(get_value)
+            }
+            None => if flags.memtable_only() {
+                seek_exact(txn.iterator_opt(iterator_mode(key), read_opts), key)
+            } else {
+                txn.get_opt(key.as_bytes(), &read_opts)
+            }
+            .map_err(|e| e.to_string())
+            .and_then(get_value),
         }
     }
 
@@ -203,6 +964,8 @@ impl RocksDBManager {
         key: &str,
         cf_name: Option<String>,
         default: Option<String>,
+        flags: ReadFlags,
+        snapshot: Option<&str>,
     ) -> Result<Option<String>, String> {
         let db = self
             .db
@@ -216,18 +979,37 @@ impl RocksDBManager {
                 .transpose()
                 .map(|opt| opt.or(default.clone()))
         };
+        let mut read_opts = flags.read_options();
+
+        // Plain reads skip the snapshots lock; the guard only has to outlive the read below.
+        let snapshots;
+        if let Some(name) = snapshot {
+            snapshots = self
+                .snapshots
+                .lock()
+                .map_err(|_| "Failed to acquire snapshots lock".to_string())?;
+            let named = snapshots.get(name).ok_or(SNAPSHOT_NOT_FOUND)?;
+            read_opts.set_snapshot(&named.snapshot);
+        }
 
         match cf_name {
             Some(cf_name) => {
-                let cf = db.cf_handle(&cf_name).ok_or("Column family not found")?;
-                db.get_cf(&cf, key.as_bytes())
-                    .map_err(|e| e.to_string())
-                    .and_then(get_value)
-            }
-            None => db
-                .get(key.as_bytes())
+                let cf = db.cf_handle(&cf_name).ok_or(CF_NOT_FOUND)?;
+                if flags.memtable_only() {
+                    seek_exact(db.iterator_cf_opt(&cf, read_opts, iterator_mode(key)), key)
+                } else {
+                    db.get_cf_opt(&cf, key.as_bytes(), &read_opts)
+                }
                 .map_err(|e| e.to_string())
-                .and_then(get_value),
+                .and_then(get_value)
+            }
+            None => if flags.memtable_only() {
+                seek_exact(db.iterator_opt(iterator_mode(key), read_opts), key)
+            } else {
+                db.get_opt(key.as_bytes(), &read_opts)
+            }
+            .map_err(|e| e.to_string())
+            .and_then(get_value),
         }
     }
 
@@ -247,7 +1029,7 @@ impl RocksDBManager {
                 .ok_or("Transaction database is not available")?;
             let cf = txn_db
                 .cf_handle(&cf_name)
-                .ok_or("Column family not found")?;
+                .ok_or(CF_NOT_FOUND)?;
             txn.delete_cf(&cf, key.as_bytes())
                 .map_err(|e| e.to_string())
         } else {
@@ -261,9 +1043,10 @@ impl RocksDBManager {
             .read()
             .map_err(|_| "Failed to read DB lock".to_string())?;
         let db = db.as_ref().ok_or("Database is not open")?;
+        let _guards = self.key_locks.lock(cf_name.as_deref(), &[key])?;
 
         if let Some(cf_name) = cf_name {
-            let cf = db.cf_handle(&cf_name).ok_or("Column family not found")?;
+            let cf = db.cf_handle(&cf_name).ok_or(CF_NOT_FOUND)?;
             db.delete_cf(&cf, key.as_bytes()).map_err(|e| e.to_string())
         } else {
             db.delete(key.as_bytes()).map_err(|e| e.to_string())
@@ -285,7 +1068,7 @@ impl RocksDBManager {
             let txn_db = txn_db_lock.as_ref().ok_or("No active transaction DB")?;
             let cf = txn_db
                 .cf_handle(&cf_name)
-                .ok_or("Column family not found")?;
+                .ok_or(CF_NOT_FOUND)?;
             txn.merge_cf(&cf, key.as_bytes(), value.as_bytes())
                 .map_err(|e| e.to_string())
         } else {
@@ -294,53 +1077,88 @@ impl RocksDBManager {
         }
     }
 
-    fn merge_in_db(&self, key: &str, value: &str, cf_name: Option<String>) -> Result<(), String> {
+    fn merge_in_db(&self, key: &str, value: &str, cf_name: Option<String>, flags: WriteFlags) -> Result<(), String> {
         let db = self
             .db
             .read()
             .map_err(|_| "Failed to read DB lock".to_string())?;
         let db = db.as_ref().ok_or("Database is not open")?;
+        let _guards = self.key_locks.lock(cf_name.as_deref(), &[key])?;
 
         if let Some(cf_name) = cf_name {
-            let cf = db.cf_handle(&cf_name).ok_or("Column family not found")?;
-            db.merge_cf(&cf, key.as_bytes(), value.as_bytes())
+            let cf = db.cf_handle(&cf_name).ok_or(CF_NOT_FOUND)?;
+            db.merge_cf_opt(&cf, key.as_bytes(), value.as_bytes(), &flags.write_options())
                 .map_err(|e| e.to_string())
         } else {
-            db.merge(key.as_bytes(), value.as_bytes())
+            db.merge_opt(key.as_bytes(), value.as_bytes(), &flags.write_options())
                 .map_err(|e| e.to_string())
         }
     }
 }
 
 impl RocksDBManager {
-    pub fn new(db_path: &str, ttl_secs: Option<u64>) -> Result<Self, String> {
+    pub fn new(db_path: &str, ttl_secs: Option<u64>, config: DbConfig) -> Result<Self, String> {
         info!(
-            "Initializing RocksDBManager with db_path: {}, ttl_secs: {:?}",
-            db_path, ttl_secs
+            "Initializing RocksDBManager with db_path: {}, ttl_secs: {:?}, config: {:?}",
+            db_path, ttl_secs, config
         );
 
+        // RocksDB creates only the last path component, so make sure the parents exist.
+        if let Some(parent) = Path::new(db_path).parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                format!(
+                    "Failed to create parent directory {} for database: {}",
+                    parent.display(),
+                    e
+                )
+            })?;
+        }
+
+        for dir in config.storage_dirs() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create directory {}: {}", dir, e))?;
+        }
+
+        let parallelism = config.parallelism.unwrap_or_else(|| available_cpus() as i32);
+        info!("Using {} background threads", parallelism);
+        if config.rate_limiter_bytes_per_sec.is_some_and(|rate| rate <= 0) {
+            return Err("Rate limiter must allow more than 0 bytes per second".to_string());
+        }
+        if config.compaction.num_levels.is_some_and(|levels| levels < 1) {
+            return Err("Number of levels must be at least 1".to_string());
+        }
+        if let Some(max_size) = config.compaction.fifo_max_size {
+            if config.compaction.style != CompactionStyle::Fifo {
+                return Err("FIFO max size requires the fifo compaction style".to_string());
+            }
+            if max_size == 0 {
+                return Err("FIFO max size must be more than 0 bytes".to_string());
+            }
+        }
+
         let cache = Cache::new_lru_cache(512 * 1024 * 1024); // 512 MB
         let mut opts = Options::default();
         opts.set_row_cache(&cache);
         opts.create_if_missing(true);
         opts.set_merge_operator_associative("json_merge", json_merge);
-        opts.increase_parallelism(num_cpus::get() as i32);
-        opts.optimize_level_style_compaction(512 * 1024 * 1024); // 512 MB
+        opts.increase_parallelism(parallelism);
+        if config.compaction.style == CompactionStyle::Level {
+            opts.optimize_level_style_compaction(512 * 1024 * 1024); // 512 MB
+        }
         opts.set_compression_type(DBCompressionType::Snappy);
         opts.set_write_buffer_size(64 * 1024 * 1024); // 64 MB
         opts.set_max_write_buffer_number(3);
         opts.set_min_write_buffer_number_to_merge(1);
-        opts.set_max_open_files(1000);
+        opts.set_max_open_files(max_open_files(config.compaction));
+        opts.set_atomic_flush(config.atomic_flush);
+        opts.set_enable_pipelined_write(config.pipelined_write);
+        set_rate_limiter(&mut opts, config.rate_limiter_bytes_per_sec);
+        set_storage_paths(&mut opts, config.wal_dir.as_deref(), &config.db_paths)?;
 
         let cf_names = DBWithThreadMode::<MultiThreaded>::list_cf(&opts, db_path)
             .unwrap_or(vec!["default".to_string()]);
         let cf_descriptors: Vec<ColumnFamilyDescriptor> = cf_names
             .iter()
-            .map(|name| {
-                let mut cf_opts = Options::default();
-                cf_opts.set_merge_operator_associative("json_merge", json_merge);
-                ColumnFamilyDescriptor::new(name, cf_opts)
-            })
+            .map(|name| ColumnFamilyDescriptor::new(name, column_family_options(config.compaction)))
             .collect();
 
         let db = match ttl_secs {
@@ -372,51 +1190,70 @@ impl RocksDBManager {
         Ok(RocksDBManager {
             db,
             db_path: db_path.to_string(),
-            write_batch: Mutex::new(Some(WriteBatchWithTransaction::default())),
+            parallelism,
+            atomic_flush: config.atomic_flush,
+            pipelined_write: config.pipelined_write,
+            rate_limiter_bytes_per_sec: config.rate_limiter_bytes_per_sec,
+            compaction: config.compaction,
+            wal_dir: config.wal_dir,
+            db_paths: config.db_paths,
+            backup_path: config
+                .backup_path
+                .unwrap_or_else(|| format!("{}/backup", db_path)),
+            write_batch: Mutex::new(Some(PendingBatch::default())),
+            bulk_load: Mutex::new(None),
             iterators,
             iterator_id_counter,
             txn_db: Mutex::new(None),
             transaction: Mutex::new(None),
+            transaction_id: AtomicU64::new(0),
             condvar: Condvar::new(),
+            key_locks: KeyLocks::new(),
+            snapshots: Mutex::new(HashMap::new()),
+            snapshot_id: AtomicU64::new(0),
         })
     }
 
-    pub fn begin_transaction(&self) -> Result<(), String> {
+    /// Begins a transaction and returns its id.
+    ///
+    /// It reuses the transaction DB a `keep_open` commit or rollback left open; otherwise the DB is closed and
+    /// reopened as a transaction DB.
+    pub fn begin_transaction(&self) -> Result<u64, String> {
         info!("Beginning new transaction");
 
-        let mut txn_db_lock = self
-            .txn_db
-            .lock()
-            .map_err(|_| "Failed to acquire transaction DB lock".to_string())?;
+        // Same lock order as commit and rollback: the transaction first, then the transaction DB.
         let mut transaction_lock = self
             .transaction
             .lock()
             .map_err(|_| "Failed to acquire transaction lock".to_string())?;
-
-        while txn_db_lock.is_some() || transaction_lock.is_some() {
-            txn_db_lock = self
-                .condvar
-                .wait(txn_db_lock)
-                .map_err(|_| "Failed to wait on condition variable for txn_db_lock".to_string())?;
+        while transaction_lock.is_some() {
             transaction_lock = self.condvar.wait(transaction_lock).map_err(|_| {
                 "Failed to wait on condition variable for transaction_lock".to_string()
             })?;
         }
+        let mut txn_db_lock = self
+            .txn_db
+            .lock()
+            .map_err(|_| "Failed to acquire transaction DB lock".to_string())?;
 
-        self.close().map_err(|e| e.to_string())?;
-
-        let (transaction_db, transaction) = self
-            .begin_transaction_internal()
-            .map_err(|e| e.to_string())?;
-
-        *txn_db_lock = Some(transaction_db);
-        *transaction_lock = Some(transaction);
+        let transaction_db = match txn_db_lock.as_ref() {
+            Some(transaction_db) => transaction_db.clone(),
+            None => {
+                self.close().map_err(|e| e.to_string())?;
+                let transaction_db = self.open_transaction_db()?;
+                *txn_db_lock = Some(transaction_db.clone());
+                transaction_db
+            }
+        };
+        *transaction_lock = Some(create_transaction(&transaction_db));
 
-        Ok(())
+        Ok(self.transaction_id.fetch_add(1, Ordering::SeqCst) + 1)
     }
 
-    pub fn commit_transaction(&self) -> Result<(), String> {
-        info!("Committing transaction");
+    /// Commits the active transaction. With `keep_open` the transaction DB stays open for the next
+    /// `begin_transaction` instead of reopening the DB; see `release_transaction_db`.
+    pub fn commit_transaction(&self, keep_open: bool) -> Result<(), String> {
+        info!("Committing transaction, keep_open: {}", keep_open);
 
         let mut transaction_lock = self
             .transaction
@@ -435,21 +1272,24 @@ impl RocksDBManager {
             .txn_db
             .lock()
             .map_err(|_| "Failed to acquire transaction DB lock".to_string())?;
-        *txn_db_lock = None;
+        if !keep_open {
+            *txn_db_lock = None;
+        }
         *transaction_lock = None;
         self.condvar.notify_all();
 
-        if result.is_ok() {
+        if result.is_ok() && !keep_open {
             self.reopen().map_err(|e| e.to_string())?;
         }
 
         result
     }
 
-    pub fn rollback_transaction(&self) -> Result<(), String> {
-        info!("Rolling back transaction");
+    /// Rolls back the active transaction; `keep_open` works as in `commit_transaction`.
+    pub fn rollback_transaction(&self, keep_open: bool) -> Result<(), String> {
+        info!("Rolling back transaction, keep_open: {}", keep_open);
 
-        let mut transaction_lock = self
+        let transaction_lock = self
             .transaction
             .lock()
             .map_err(|_| "Failed to acquire transaction lock".to_string())?;
@@ -457,38 +1297,106 @@ impl RocksDBManager {
             return Err("No active transaction to rollback".to_string());
         }
 
-        let txn = transaction_lock
-            .take()
-            .ok_or("Failed to take active transaction".to_string())?;
+        self.rollback_locked(transaction_lock, keep_open)
+    }
 
-        txn.rollback().map_err(|e| e.to_string())?;
-        let result = txn.commit().map_err(|e| e.to_string());
+    /// Whether the transaction begun with `id` is still active.
+    pub fn is_transaction_active(&self, id: u64) -> bool {
+        self.transaction.lock().is_ok_and(|transaction| transaction.is_some())
+            && self.transaction_id.load(Ordering::SeqCst) == id
+    }
 
-        let mut txn_db_lock = self
-            .txn_db
+    /// Rolls back the transaction `id` if it is still active; returns whether it was.
+    pub fn rollback_transaction_if_active(&self, id: u64) -> Result<bool, String> {
+        let transaction_lock = self
+            .transaction
             .lock()
-            .map_err(|_| "Failed to acquire transaction DB lock".to_string())?;
-        *txn_db_lock = None;
+            .map_err(|_| "Failed to acquire transaction lock".to_string())?;
+        if transaction_lock.is_none() || self.transaction_id.load(Ordering::SeqCst) != id {
+            return Ok(false);
+        }
+
+        info!("Rolling back abandoned transaction {}", id);
+        self.rollback_locked(transaction_lock, false).map(|_| true)
+    }
+
+    fn rollback_locked(
+        &self,
+        mut transaction_lock: MutexGuard<Option<Transaction<'static, TransactionDB>>>,
+        keep_open: bool,
+    ) -> Result<(), String> {
+        let txn = transaction_lock
+            .take()
+            .ok_or("Failed to take active transaction".to_string())?;
+
+        txn.rollback().map_err(|e| e.to_string())?;
+        let result = txn.commit().map_err(|e| e.to_string());
+
+        let mut txn_db_lock = self
+            .txn_db
+            .lock()
+            .map_err(|_| "Failed to acquire transaction DB lock".to_string())?;
+        if !keep_open {
+            *txn_db_lock = None;
+        }
         *transaction_lock = None;
         self.condvar.notify_all();
 
-        if result.is_ok() {
+        if result.is_ok() && !keep_open {
             self.reopen().map_err(|e| e.to_string())?;
         }
 
         result
     }
 
+    /// Closes a transaction DB left open by a `keep_open` commit or rollback of transaction `id` and reopens
+    /// the DB, unless another transaction began since. Returns whether it did.
+    pub fn release_transaction_db(&self, id: u64) -> Result<bool, String> {
+        if self.transaction_id.load(Ordering::SeqCst) != id {
+            return Ok(false);
+        }
+        self.release_idle_transaction_db()
+    }
+
+    /// Closes a transaction DB left open by a `keep_open` commit or rollback and reopens the DB, unless a
+    /// transaction is active. Returns whether it did; while the DB is open this only checks that it is.
+    pub fn release_idle_transaction_db(&self) -> Result<bool, String> {
+        if self.is_open() {
+            return Ok(false);
+        }
+        let transaction_lock = self
+            .transaction
+            .lock()
+            .map_err(|_| "Failed to acquire transaction lock".to_string())?;
+        if transaction_lock.is_some() {
+            return Ok(false);
+        }
+
+        let mut txn_db_lock = self
+            .txn_db
+            .lock()
+            .map_err(|_| "Failed to acquire transaction DB lock".to_string())?;
+        if txn_db_lock.take().is_none() {
+            return Ok(false);
+        }
+
+        info!("Releasing the transaction DB left open by a transaction");
+        self.reopen()?;
+        Ok(true)
+    }
+
+    /// Writes `key`; `flags` apply outside transactions only.
     pub fn put(
         &self,
         key: String,
         value: String,
         cf_name: Option<String>,
         txn: Option<bool>,
+        flags: WriteFlags,
     ) -> Result<(), String> {
         debug!(
-            "Putting key: {}, value: {}, cf_name: {:?}, txn: {:?}",
-            key, value, cf_name, txn
+            "Putting key: {}, value: {}, cf_name: {:?}, txn: {:?}, flags: {:?}",
+            key, value, cf_name, txn, flags
         );
         if txn.unwrap_or(false) {
             let mut transaction_lock = self
@@ -505,7 +1413,7 @@ impl RocksDBManager {
                         .wait(transaction_lock)
                         .map_err(|_| "Failed to wait on condition variable".to_string())?;
                 }
-                return self.put(key, value, cf_name, txn); // Retry the operation
+                return self.put(key, value, cf_name, txn, flags); // Retry the operation
             }
         }
 
@@ -517,7 +1425,7 @@ impl RocksDBManager {
             return Err("Database is not open".to_string());
         }
 
-        self.put_in_db(&key, &value, cf_name)
+        self.put_in_db(&key, &value, cf_name, flags)
     }
 
     pub fn get(
@@ -526,10 +1434,11 @@ impl RocksDBManager {
         cf_name: Option<String>,
         default: Option<String>,
         txn: Option<bool>,
+        flags: ReadFlags,
     ) -> Result<Option<String>, String> {
         debug!(
-            "Getting key: {}, cf_name: {:?}, default: {:?}, txn: {:?}",
-            key, cf_name, default, txn
+            "Getting key: {}, cf_name: {:?}, default: {:?}, txn: {:?}, flags: {:?}",
+            key, cf_name, default, txn, flags
         );
         if txn.unwrap_or(false) {
             let mut transaction_lock = self
@@ -538,7 +1447,7 @@ impl RocksDBManager {
                 .map_err(|_| "Failed to acquire transaction lock".to_string())?;
 
             if let Some(txn) = transaction_lock.as_ref() {
-                return self.get_in_transaction(txn, &key, cf_name, default);
+                return self.get_in_transaction(txn, &key, cf_name, default, flags);
             } else {
                 while transaction_lock.is_some() {
                     transaction_lock = self
@@ -546,7 +1455,7 @@ impl RocksDBManager {
                         .wait(transaction_lock)
                         .map_err(|_| "Failed to wait on condition variable".to_string())?;
                 }
-                return self.get(key, cf_name, default, txn); // Retry the operation
+                return self.get(key, cf_name, default, txn, flags); // Retry the operation
             }
         }
         let db = self
@@ -557,7 +1466,156 @@ impl RocksDBManager {
             return Err("Database is not open".to_string());
         }
 
-        self.get_in_db(&key, cf_name, default)
+        self.get_in_db(&key, cf_name, default, flags, None)
+    }
+
+    /// Reads several keys in one batch; missing keys are `None`.
+    pub fn get_many(
+        &self,
+        keys: &[String],
+        cf_name: Option<String>,
+        flags: ReadFlags,
+    ) -> Result<Vec<Option<String>>, String> {
+        debug!("Getting {} keys, cf_name: {:?}", keys.len(), cf_name);
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open")?;
+
+        let read_opts = flags.read_options();
+        let values = match cf_name {
+            Some(cf_name) => {
+                let cf = db.cf_handle(&cf_name).ok_or(CF_NOT_FOUND)?;
+                db.multi_get_cf_opt(keys.iter().map(|key| (&cf, key)), &read_opts)
+            }
+            None => db.multi_get_opt(keys, &read_opts),
+        };
+
+        values
+            .into_iter()
+            .map(|value| {
+                value
+                    .map_err(|e| e.to_string())?
+                    .map(|v| String::from_utf8(v).map_err(|e| e.to_string()))
+                    .transpose()
+            })
+            .collect()
+    }
+
+    /// Reads a key as it was when the named snapshot was created.
+    pub fn get_at_snapshot(
+        &self,
+        snapshot: &str,
+        key: &str,
+        cf_name: Option<String>,
+        flags: ReadFlags,
+    ) -> Result<Option<String>, String> {
+        debug!(
+            "Getting key: {} at snapshot: {}, cf_name: {:?}",
+            key, snapshot, cf_name
+        );
+        self.get_in_db(key, cf_name, None, flags, Some(snapshot))
+    }
+
+    /// Pins the current state of the database under `name` until it is released or `ttl` passes.
+    pub fn create_snapshot(
+        &self,
+        name: &str,
+        ttl: Option<Duration>,
+    ) -> Result<(u64, SnapshotInfo), String> {
+        info!("Creating snapshot: {}, ttl: {:?}", name, ttl);
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open")?;
+        let mut snapshots = self
+            .snapshots
+            .lock()
+            .map_err(|_| "Failed to acquire snapshots lock".to_string())?;
+        if snapshots.contains_key(name) {
+            return Err("Snapshot already exists".to_string());
+        }
+
+        let created_at = unix_time();
+        let info = SnapshotInfo {
+            name: name.to_string(),
+            sequence_number: db.latest_sequence_number(),
+            created_at,
+            expires_at: ttl.map(|ttl| created_at + ttl.as_secs()),
+        };
+        let snapshot = unsafe { std::mem::transmute::<SnapshotWithThreadMode<_>, DbSnapshot>(db.snapshot()) };
+        let id = self.snapshot_id.fetch_add(1, Ordering::SeqCst) + 1;
+        snapshots.insert(
+            name.to_string(),
+            NamedSnapshot {
+                id,
+                snapshot,
+                info: info.clone(),
+            },
+        );
+        Ok((id, info))
+    }
+
+    /// Releases the named snapshot; returns whether it existed.
+    pub fn release_snapshot(&self, name: &str) -> Result<bool, String> {
+        info!("Releasing snapshot: {}", name);
+        let mut snapshots = self
+            .snapshots
+            .lock()
+            .map_err(|_| "Failed to acquire snapshots lock".to_string())?;
+        Ok(snapshots.remove(name).is_some())
+    }
+
+    /// Releases the named snapshot only if it is still the one created with `id`.
+    pub fn release_snapshot_if_current(&self, name: &str, id: u64) -> Result<bool, String> {
+        let mut snapshots = self
+            .snapshots
+            .lock()
+            .map_err(|_| "Failed to acquire snapshots lock".to_string())?;
+        if snapshots.get(name).is_some_and(|named| named.id == id) {
+            snapshots.remove(name);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Whether the named snapshot created with `id` has not been released yet.
+    pub fn is_snapshot_current(&self, name: &str, id: u64) -> bool {
+        self.snapshots
+            .lock()
+            .is_ok_and(|snapshots| snapshots.get(name).is_some_and(|named| named.id == id))
+    }
+
+    pub fn list_snapshots(&self) -> Result<Vec<SnapshotInfo>, String> {
+        let snapshots = self
+            .snapshots
+            .lock()
+            .map_err(|_| "Failed to acquire snapshots lock".to_string())?;
+        let mut infos: Vec<SnapshotInfo> = snapshots.values().map(|named| named.info.clone()).collect();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(infos)
+    }
+
+    // Every change of the open DB goes through here, so no snapshot outlives the DB it borrows.
+    fn replace_db(
+        &self,
+        db_lock: &mut RwLockWriteGuard<Option<DBWithThreadMode<MultiThreaded>>>,
+        new_db: Option<DBWithThreadMode<MultiThreaded>>,
+    ) -> Result<(), String> {
+        let mut snapshots = self
+            .snapshots
+            .lock()
+            .map_err(|_| "Failed to acquire snapshots lock".to_string())?;
+        if !snapshots.is_empty() {
+            info!("Releasing {} snapshots", snapshots.len());
+            snapshots.clear();
+        }
+        **db_lock = new_db;
+        Ok(())
     }
 
     pub fn delete(
@@ -600,16 +1658,18 @@ impl RocksDBManager {
         self.delete_in_db(&key, cf_name)
     }
 
+    /// Merges `value` into `key`; `flags` apply outside transactions only.
     pub fn merge(
         &self,
         key: String,
         value: String,
         cf_name: Option<String>,
         txn: Option<bool>,
+        flags: WriteFlags,
     ) -> Result<(), String> {
         debug!(
-            "Merging key: {}, value: {}, cf_name: {:?}, txn: {:?}",
-            key, value, cf_name, txn
+            "Merging key: {}, value: {}, cf_name: {:?}, txn: {:?}, flags: {:?}",
+            key, value, cf_name, txn, flags
         );
         if txn.unwrap_or(false) {
             let mut transaction_lock = self
@@ -626,7 +1686,7 @@ impl RocksDBManager {
                         .wait(transaction_lock)
                         .map_err(|_| "Failed to wait on condition variable".to_string())?;
                 }
-                return self.merge(key, value, cf_name, txn); // Retry the operation
+                return self.merge(key, value, cf_name, txn, flags); // Retry the operation
             }
         }
 
@@ -640,15 +1700,25 @@ impl RocksDBManager {
 
         debug!("1111, {:?}", value);
 
-        self.merge_in_db(&key, &value, cf_name)
+        self.merge_in_db(&key, &value, cf_name, flags)
     }
 
-    pub fn get_property(
+    /// Merges `operand` into `key` with `op`, bypassing the column family's merge operator.
+    ///
+    /// The outer error is a database failure; the inner one means the operand and the stored value do not combine,
+    /// in which case nothing is written.
+    pub fn merge_with_op(
         &self,
-        property: String,
+        key: String,
+        operand: &str,
+        op: MergeOp,
         cf_name: Option<String>,
-    ) -> Result<Option<String>, String> {
-        debug!("get property with id: {}, cf_name: {:?}", property, cf_name);
+        flags: WriteFlags,
+    ) -> Result<Result<String, String>, String> {
+        debug!(
+            "Merging key: {} with {:?}, operand: {}, cf_name: {:?}",
+            key, op, operand, cf_name
+        );
 
         let db = self
             .db
@@ -656,19 +1726,142 @@ impl RocksDBManager {
             .map_err(|_| "Failed to read DB lock".to_string())?;
         let db = db.as_ref().ok_or("Database is not open".to_string())?;
 
-        let result = match cf_name {
+        let _guards = self.key_locks.lock(cf_name.as_deref(), &[&key])?;
+
+        let cf = match &cf_name {
+            Some(cf_name) => Some(db.cf_handle(cf_name).ok_or(CF_NOT_FOUND)?),
+            None => None,
+        };
+        let current = match &cf {
+            Some(cf) => db.get_cf(cf, &key),
+            None => db.get(&key),
+        }
+        .map_err(|e| e.to_string())?
+        .map(|value| String::from_utf8(value).map_err(|e| e.to_string()))
+        .transpose()?;
+
+        let value = match op.apply(current.as_deref(), operand) {
+            Ok(value) => value,
+            Err(e) => return Ok(Err(e)),
+        };
+
+        match &cf {
+            Some(cf) => db.put_cf_opt(cf, &key, &value, &flags.write_options()),
+            None => db.put_opt(&key, &value, &flags.write_options()),
+        }
+        .map_err(|e| e.to_string())?;
+
+        Ok(Ok(value))
+    }
+
+    pub fn rename(
+        &self,
+        key: String,
+        new_key: String,
+        cf_name: Option<String>,
+    ) -> Result<bool, String> {
+        debug!(
+            "Renaming key: {} to {}, cf_name: {:?}",
+            key, new_key, cf_name
+        );
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        let _guards = self.key_locks.lock(cf_name.as_deref(), &[&key, &new_key])?;
+
+        // The delete and the put go into one batch, so readers never see both keys or neither.
+        let mut batch = WriteBatchWithTransaction::<false>::default();
+        match cf_name {
             Some(cf_name) => {
-                let cf = db.cf_handle(&cf_name).ok_or("Column family not found")?;
-                db.property_value_cf(&cf, &property)
+                let cf = db.cf_handle(&cf_name).ok_or(CF_NOT_FOUND)?;
+                let Some(value) = db.get_cf(&cf, &key).map_err(|e| e.to_string())? else {
+                    return Ok(false);
+                };
+                batch.delete_cf(&cf, &key);
+                batch.put_cf(&cf, &new_key, value);
             }
-            None => db.property_value(&property),
+            None => {
+                let Some(value) = db.get(&key).map_err(|e| e.to_string())? else {
+                    return Ok(false);
+                };
+                batch.delete(&key);
+                batch.put(&new_key, value);
+            }
+        }
+        db.write(batch).map_err(|e| e.to_string())?;
+
+        Ok(true)
+    }
+
+    /// Stores the value of `key` under `new_key` as well, in `new_cf_name` or the source column family.
+    /// Returns false, writing nothing, if `key` is missing.
+    pub fn copy(
+        &self,
+        key: String,
+        new_key: String,
+        cf_name: Option<String>,
+        new_cf_name: Option<String>,
+    ) -> Result<bool, String> {
+        debug!(
+            "Copying key: {} to {}, cf_name: {:?}, new_cf_name: {:?}",
+            key, new_key, cf_name, new_cf_name
+        );
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        let new_cf_name = new_cf_name.or_else(|| cf_name.clone());
+        let cf = cf_name
+            .as_deref()
+            .map(|cf_name| db.cf_handle(cf_name).ok_or(CF_NOT_FOUND))
+            .transpose()?;
+        let new_cf = new_cf_name
+            .as_deref()
+            .map(|cf_name| db.cf_handle(cf_name).ok_or(CF_NOT_FOUND))
+            .transpose()?;
+
+        // Both keys stay locked from the read to the write, so a `put` to either cannot slip in between.
+        let _guards = self
+            .key_locks
+            .lock_entries(&[(cf_name.as_deref(), &key), (new_cf_name.as_deref(), &new_key)])?;
+
+        let value = match &cf {
+            Some(cf) => db.get_cf(cf, &key),
+            None => db.get(&key),
+        }
+        .map_err(|e| e.to_string())?;
+        let Some(value) = value else {
+            return Ok(false);
         };
 
-        result.map_err(|e| e.to_string())
+        match &new_cf {
+            Some(cf) => db.put_cf(cf, &new_key, value),
+            None => db.put(&new_key, value),
+        }
+        .map_err(|e| e.to_string())?;
+
+        Ok(true)
     }
 
-    pub fn get_all(&self, query: Option<String>) -> Result<Vec<String>, String> {
-        debug!("Get all keys with query: {:?}", query);
+    /// Returns the value of `key`, first storing `value` under it if the key is missing.
+    /// The second element is true when `value` was inserted.
+    pub fn get_or_put(
+        &self,
+        key: String,
+        value: String,
+        cf_name: Option<String>,
+    ) -> Result<(String, bool), String> {
+        debug!(
+            "Getting key: {} or putting value: {}, cf_name: {:?}",
+            key, value, cf_name
+        );
 
         let db = self
             .db
@@ -676,151 +1869,1178 @@ impl RocksDBManager {
             .map_err(|_| "Failed to read DB lock".to_string())?;
         let db = db.as_ref().ok_or("Database is not open".to_string())?;
 
-        let iter = db.iterator(rust_rocksdb::IteratorMode::Start);
-
-        let keys: Vec<String> = iter
-            .filter_map(|result| {
-                result.ok().and_then(|(key, value)| {
-                    let key_str = String::from_utf8(key.to_vec()).ok()?;
-                    let value_str = String::from_utf8(value.to_vec()).ok()?;
-                    match &query {
-                        Some(q) if key_str.contains(q) || value_str.contains(q) => Some(key_str),
-                        None => Some(key_str),
-                        _ => None,
-                    }
-                })
-            })
-            .collect();
+        let _guards = self.key_locks.lock(cf_name.as_deref(), &[&key])?;
 
-        debug!("Get all result: {:?}", keys);
-        Ok(keys)
+        let current = match &cf_name {
+            Some(cf_name) => {
+                let cf = db.cf_handle(cf_name).ok_or(CF_NOT_FOUND)?;
+                let current = db.get_cf(&cf, &key).map_err(|e| e.to_string())?;
+                if current.is_none() {
+                    db.put_cf(&cf, &key, &value).map_err(|e| e.to_string())?;
+                }
+                current
+            }
+            None => {
+                let current = db.get(&key).map_err(|e| e.to_string())?;
+                if current.is_none() {
+                    db.put(&key, &value).map_err(|e| e.to_string())?;
+                }
+                current
+            }
+        };
+
+        match current {
+            Some(current) => Ok((String::from_utf8_lossy(&current).into_owned(), false)),
+            None => Ok((value, true)),
+        }
     }
 
-    pub fn get_keys(
+    /// Stores `value` under `key` and returns the value it replaced. With `txn` the old value is
+    /// read with `get_for_update` inside the active transaction, which locks the key until commit.
+    pub fn getset(
         &self,
-        start: usize,
-        limit: usize,
-        query: Option<String>,
-    ) -> Result<Vec<String>, String> {
+        key: String,
+        value: String,
+        cf_name: Option<String>,
+        txn: Option<bool>,
+    ) -> Result<Option<String>, String> {
         debug!(
-            "Get keys with start: {}, limit: {}, query: {:?}",
-            start, limit, query
+            "Getting and setting key: {}, value: {}, cf_name: {:?}, txn: {:?}",
+            key, value, cf_name, txn
         );
-        let mut keys = self.get_all(query)?;
-        keys = keys.into_iter().skip(start).take(limit).collect();
-        debug!("Get keys result: {:?}", keys);
-        Ok(keys)
+
+        let previous = if txn.unwrap_or(false) {
+            let transaction_lock = self
+                .transaction
+                .lock()
+                .map_err(|_| "Failed to acquire transaction lock".to_string())?;
+            let transaction = transaction_lock.as_ref().ok_or("No active transaction")?;
+
+            let previous = match &cf_name {
+                Some(cf_name) => {
+                    let txn_db_lock = self
+                        .txn_db
+                        .lock()
+                        .map_err(|_| "Failed to acquire transaction DB lock".to_string())?;
+                    let txn_db = txn_db_lock.as_ref().ok_or("No active transaction DB")?;
+                    let cf = txn_db.cf_handle(cf_name).ok_or(CF_NOT_FOUND)?;
+                    transaction.get_for_update_cf(&cf, &key, true)
+                }
+                None => transaction.get_for_update(&key, true),
+            }
+            .map_err(|e| e.to_string())?;
+            self.put_in_transaction(transaction, &key, &value, cf_name)?;
+            previous
+        } else {
+            let db = self
+                .db
+                .read()
+                .map_err(|_| "Failed to read DB lock".to_string())?;
+            let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+            let _guards = self.key_locks.lock(cf_name.as_deref(), &[&key])?;
+
+            match &cf_name {
+                Some(cf_name) => {
+                    let cf = db.cf_handle(cf_name).ok_or(CF_NOT_FOUND)?;
+                    let previous = db.get_cf(&cf, &key).map_err(|e| e.to_string())?;
+                    db.put_cf(&cf, &key, &value).map_err(|e| e.to_string())?;
+                    previous
+                }
+                None => {
+                    let previous = db.get(&key).map_err(|e| e.to_string())?;
+                    db.put(&key, &value).map_err(|e| e.to_string())?;
+                    previous
+                }
+            }
+        };
+
+        Ok(previous.map(|previous| String::from_utf8_lossy(&previous).into_owned()))
     }
 
-    pub fn close(&self) -> Result<(), String> {
-        info!("Closing database");
-        let mut db_lock = self
+    /// Adds `by` to the integer stored under `key`, a missing key counting as `0`, and returns
+    /// the new value. Returns `None` without writing when the stored value is not an integer
+    /// or the result does not fit in an `i64`.
+    pub fn increment(
+        &self,
+        key: String,
+        by: i64,
+        cf_name: Option<String>,
+    ) -> Result<Option<i64>, String> {
+        debug!(
+            "Incrementing key: {} by {}, cf_name: {:?}",
+            key, by, cf_name
+        );
+
+        let db = self
             .db
-            .write()
-            .map_err(|_| "Failed to write DB lock".to_string())?;
-        *db_lock = None;
-        Ok(())
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        let _guards = self.key_locks.lock(cf_name.as_deref(), &[&key])?;
+
+        let cf = match &cf_name {
+            Some(cf_name) => Some(db.cf_handle(cf_name).ok_or(CF_NOT_FOUND)?),
+            None => None,
+        };
+        let current = match &cf {
+            Some(cf) => db.get_pinned_cf(cf, &key),
+            None => db.get_pinned(&key),
+        }
+        .map_err(|e| e.to_string())?;
+
+        let current = match current {
+            Some(value) => match std::str::from_utf8(&value).ok().and_then(|v| v.trim().parse::<i64>().ok()) {
+                Some(value) => value,
+                None => return Ok(None),
+            },
+            None => 0,
+        };
+        let Some(value) = current.checked_add(by) else {
+            return Ok(None);
+        };
+
+        match &cf {
+            Some(cf) => db.put_cf(cf, &key, value.to_string()),
+            None => db.put(&key, value.to_string()),
+        }
+        .map_err(|e| e.to_string())?;
+
+        Ok(Some(value))
     }
 
-    pub fn reopen(&self) -> Result<(), String> {
-        info!("Reopening database with db_path: {}", self.db_path);
+    /// Deletes `key` only if its value is `expected` and returns whether it did. With `txn` the value is read with
+    /// `get_for_update` in the active transaction, so the key stays locked until the transaction ends.
+    pub fn delete_if(
+        &self,
+        key: String,
+        expected: String,
+        cf_name: Option<String>,
+        txn: Option<bool>,
+    ) -> Result<bool, String> {
+        debug!(
+            "Deleting key: {} if value matches: {}, cf_name: {:?}, txn: {:?}",
+            key, expected, cf_name, txn
+        );
 
-        let mut opts = Options::default();
-        opts.create_if_missing(true);
-        opts.set_merge_operator_associative("json_merge", json_merge);
+        if txn.unwrap_or(false) {
+            let transaction_lock = self
+                .transaction
+                .lock()
+                .map_err(|_| "Failed to acquire transaction lock".to_string())?;
+            let transaction = transaction_lock.as_ref().ok_or("No active transaction")?;
+
+            let current = match &cf_name {
+                Some(cf_name) => {
+                    let txn_db_lock = self
+                        .txn_db
+                        .lock()
+                        .map_err(|_| "Failed to acquire transaction DB lock".to_string())?;
+                    let txn_db = txn_db_lock.as_ref().ok_or("No active transaction DB")?;
+                    let cf = txn_db.cf_handle(cf_name).ok_or(CF_NOT_FOUND)?;
+                    transaction.get_for_update_cf(&cf, &key, true)
+                }
+                None => transaction.get_for_update(&key, true),
+            }
+            .map_err(|e| e.to_string())?;
+            if current.as_deref() != Some(expected.as_bytes()) {
+                return Ok(false);
+            }
+            self.delete_in_transaction(transaction, &key, cf_name)?;
+            return Ok(true);
+        }
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        let _guards = self.key_locks.lock(cf_name.as_deref(), &[&key])?;
+
+        match cf_name {
+            Some(cf_name) => {
+                let cf = db.cf_handle(&cf_name).ok_or(CF_NOT_FOUND)?;
+                let current = db.get_pinned_cf(&cf, &key).map_err(|e| e.to_string())?;
+                if current.as_deref() != Some(expected.as_bytes()) {
+                    return Ok(false);
+                }
+                db.delete_cf(&cf, &key).map_err(|e| e.to_string())?;
+            }
+            None => {
+                let current = db.get_pinned(&key).map_err(|e| e.to_string())?;
+                if current.as_deref() != Some(expected.as_bytes()) {
+                    return Ok(false);
+                }
+                db.delete(&key).map_err(|e| e.to_string())?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Deletes every key of a column family in write batches of `batch_size` keys and returns how many it deleted.
+    /// Keys written after it started are kept.
+    pub fn truncate(&self, cf_name: Option<String>, batch_size: usize) -> Result<u64, String> {
+        info!("Truncating cf_name: {:?}", cf_name);
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+        let cf = match &cf_name {
+            Some(cf_name) => Some(db.cf_handle(cf_name).ok_or(CF_NOT_FOUND)?),
+            None => None,
+        };
+
+        // A range delete would be a single write, but RocksDB rejects it on a database with a row cache,
+        // so the keys are deleted one by one without reading their values.
+        let mut iter = match &cf {
+            Some(cf) => db.raw_iterator_cf(cf),
+            None => db.raw_iterator(),
+        };
+        iter.seek_to_first();
+
+        let mut deleted = 0;
+        let mut batch = WriteBatchWithTransaction::<false>::default();
+        while let Some(key) = iter.key() {
+            match &cf {
+                Some(cf) => batch.delete_cf(cf, key),
+                None => batch.delete(key),
+            }
+            if batch.len() >= batch_size {
+                deleted += batch.len() as u64;
+                db.write(std::mem::take(&mut batch)).map_err(|e| e.to_string())?;
+            }
+            iter.next();
+        }
+        iter.status().map_err(|e| e.to_string())?;
+        deleted += batch.len() as u64;
+        db.write(batch).map_err(|e| e.to_string())?;
+
+        Ok(deleted)
+    }
+
+    /// Truncates every column family and returns the total number of deleted keys.
+    pub fn truncate_all(&self, batch_size: usize) -> Result<u64, String> {
+        let mut count = 0;
+        for cf_name in self.list_column_families()? {
+            let cf_name = (cf_name != DEFAULT_COLUMN_FAMILY_NAME).then_some(cf_name);
+            count += self.truncate(cf_name, batch_size)?;
+        }
+        Ok(count)
+    }
+
+    pub fn scan_delete(
+        &self,
+        start: Option<String>,
+        end: Option<String>,
+        cf_name: Option<String>,
+        predicate: &ValuePredicate,
+        batch_size: usize,
+    ) -> Result<usize, String> {
+        info!(
+            "Scan delete from {:?} to {:?} with cf_name: {:?}, predicate: {:?}",
+            start, end, cf_name, predicate
+        );
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+        let cf = match &cf_name {
+            Some(cf_name) => Some(db.cf_handle(cf_name).ok_or(CF_NOT_FOUND)?),
+            None => None,
+        };
+
+        let start = start.unwrap_or_default();
+        let mode = rust_rocksdb::IteratorMode::From(start.as_bytes(), rust_rocksdb::Direction::Forward);
+        let iter = match &cf {
+            Some(cf) => db.iterator_cf(cf, mode),
+            None => db.iterator(mode),
+        };
+
+        let mut deleted = 0;
+        let mut candidates = Vec::new();
+        for item in iter {
+            let (key, value) = item.map_err(|e| e.to_string())?;
+            if end.as_ref().is_some_and(|end| *key >= *end.as_bytes()) {
+                break;
+            }
+            if predicate.matches(&value) {
+                candidates.push(key);
+            }
+            if candidates.len() >= batch_size {
+                deleted += self.delete_matching(db, cf.as_ref(), cf_name.as_deref(), &candidates, predicate)?;
+                candidates.clear();
+            }
+        }
+        deleted += self.delete_matching(db, cf.as_ref(), cf_name.as_deref(), &candidates, predicate)?;
+
+        debug!("Scan delete removed {} keys", deleted);
+        Ok(deleted)
+    }
+
+    // Deletes the scanned candidates that still match once their keys are locked.
+    fn delete_matching(
+        &self,
+        db: &DBWithThreadMode<MultiThreaded>,
+        cf: Option<&Arc<BoundColumnFamily<'_>>>,
+        cf_name: Option<&str>,
+        keys: &[Box<[u8]>],
+        predicate: &ValuePredicate,
+    ) -> Result<usize, String> {
+        if keys.is_empty() {
+            return Ok(0);
+        }
+
+        let names: Vec<String> = keys
+            .iter()
+            .map(|key| String::from_utf8_lossy(key).into_owned())
+            .collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let _guards = self.key_locks.lock(cf_name, &names)?;
+
+        let mut batch = WriteBatchWithTransaction::<false>::default();
+        let mut count = 0;
+        for key in keys {
+            let current = match cf {
+                Some(cf) => db.get_pinned_cf(cf, key),
+                None => db.get_pinned(key),
+            }
+            .map_err(|e| e.to_string())?;
+
+            if current.is_some_and(|value| predicate.matches(&value)) {
+                match cf {
+                    Some(cf) => batch.delete_cf(cf, key),
+                    None => batch.delete(key),
+                }
+                count += 1;
+            }
+        }
+        db.write(batch).map_err(|e| e.to_string())?;
+
+        Ok(count)
+    }
+
+    pub fn get_property(
+        &self,
+        property: String,
+        cf_name: Option<String>,
+    ) -> Result<Option<String>, String> {
+        debug!("get property with id: {}, cf_name: {:?}", property, cf_name);
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        let result = match cf_name {
+            Some(cf_name) => {
+                let cf = db.cf_handle(&cf_name).ok_or(CF_NOT_FOUND)?;
+                db.property_value_cf(&cf, &property)
+            }
+            None => db.property_value(&property),
+        };
+
+        result.map_err(|e| e.to_string())
+    }
+
+    /// Reads several properties under one DB lock; unknown properties map to `None`.
+    pub fn get_properties(
+        &self,
+        properties: Vec<String>,
+        cf_name: Option<String>,
+    ) -> Result<Vec<(String, Option<String>)>, String> {
+        debug!("get properties {:?}, cf_name: {:?}", properties, cf_name);
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+        let cf = match &cf_name {
+            Some(cf_name) => Some(db.cf_handle(cf_name).ok_or(CF_NOT_FOUND)?),
+            None => None,
+        };
+
+        properties
+            .into_iter()
+            .map(|property| {
+                let value = match &cf {
+                    Some(cf) => db.property_value_cf(cf, &property),
+                    None => db.property_value(&property),
+                };
+                value
+                    .map(|value| (property, value))
+                    .map_err(|e| e.to_string())
+            })
+            .collect()
+    }
+
+    pub fn write_health(&self) -> Result<WriteHealth, String> {
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        let property = |name: &str| -> Result<u64, String> {
+            db.property_int_value(name)
+                .map(|value| value.unwrap_or(0))
+                .map_err(|e| e.to_string())
+        };
+
+        Ok(WriteHealth {
+            delayed_write_rate: property("rocksdb.actual-delayed-write-rate")?,
+            write_stopped: property("rocksdb.is-write-stopped")? != 0,
+        })
+    }
+
+    pub fn storage_stats(&self, cf_name: Option<String>) -> Result<StorageStats, String> {
+        debug!("storage stats with cf_name: {:?}", cf_name);
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+        let cf = match &cf_name {
+            Some(cf_name) => Some(db.cf_handle(cf_name).ok_or(CF_NOT_FOUND)?),
+            None => None,
+        };
+
+        let property = |name: &str| -> Result<Option<u64>, String> {
+            match &cf {
+                Some(cf) => db.property_int_value_cf(cf, name),
+                None => db.property_int_value(name),
+            }
+            .map_err(|e| e.to_string())
+        };
+
+        // RocksDB reports no value past the last configured level.
+        let mut num_files_at_level = Vec::new();
+        while let Some(files) =
+            property(&format!("rocksdb.num-files-at-level{}", num_files_at_level.len()))?
+        {
+            num_files_at_level.push(files);
+        }
+
+        Ok(StorageStats {
+            estimate_num_keys: property("rocksdb.estimate-num-keys")?.unwrap_or(0),
+            live_data_size: property("rocksdb.estimate-live-data-size")?.unwrap_or(0),
+            total_sst_size: property("rocksdb.total-sst-files-size")?.unwrap_or(0),
+            num_files_at_level,
+        })
+    }
+
+    /// Reads the effective options of a column family from the newest OPTIONS file, which RocksDB
+    /// rewrites on open and on every `set_db_option`.
+    pub fn get_options(&self, cf_name: Option<String>) -> Result<EffectiveOptions, String> {
+        debug!("Get options with cf_name: {:?}", cf_name);
+
+        let cf_name = cf_name.unwrap_or_else(|| DEFAULT_COLUMN_FAMILY_NAME.to_string());
+        if !self.list_column_families()?.contains(&cf_name) {
+            return Err(CF_NOT_FOUND.to_string());
+        }
+
+        // OPTIONS-<number>, the highest number being the current one.
+        let path = std::fs::read_dir(&self.db_path)
+            .map_err(|e| e.to_string())?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                let number = name.strip_prefix("OPTIONS-")?.parse::<u64>().ok()?;
+                Some((number, entry.path()))
+            })
+            .max_by_key(|(number, _)| *number)
+            .map(|(_, path)| path)
+            .ok_or("No OPTIONS file found".to_string())?;
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+        let cf_section = format!("CFOptions \"{}\"", cf_name);
+        let table_section = format!("TableOptions/BlockBasedTable \"{}\"", cf_name);
+        let mut options = EffectiveOptions::default();
+        let mut section: Option<&mut BTreeMap<String, String>> = None;
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                section = if name == "DBOptions" {
+                    Some(&mut options.db_options)
+                } else if name == cf_section {
+                    Some(&mut options.cf_options)
+                } else if name == table_section {
+                    Some(&mut options.table_options)
+                } else {
+                    None
+                };
+                continue;
+            }
+            if let (Some(section), Some((name, value))) = (section.as_mut(), line.split_once('=')) {
+                section.insert(name.trim().to_string(), value.trim().to_string());
+            }
+        }
+
+        Ok(options)
+    }
+
+    pub fn get_all(&self, query: Option<String>, flags: ReadFlags) -> Result<Vec<String>, String> {
+        debug!("Get all keys with query: {:?}, flags: {:?}", query, flags);
+        let keys = self.scan_keys(query.as_deref(), 0, usize::MAX, flags)?;
+        debug!("Get all result: {:?}", keys);
+        Ok(keys)
+    }
+
+    /// Returns the UTF-8 keys containing `query`, or whose UTF-8 value contains it, after skipping `start`
+    /// matches, at most `limit` of them. Values are only read for keys that do not match by
+    /// themselves, so listing keys without a query never copies a value.
+    fn scan_keys(
+        &self,
+        query: Option<&str>,
+        start: usize,
+        limit: usize,
+        flags: ReadFlags,
+    ) -> Result<Vec<String>, String> {
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        let mut keys = Vec::new();
+        let mut skipped = 0;
+        let mut iter = db.raw_iterator_opt(flags.read_options());
+        iter.seek_to_first();
+        while keys.len() < limit {
+            let Some(key) = iter.key() else {
+                break;
+            };
+            if let Ok(key) = std::str::from_utf8(key) {
+                let matched = match query {
+                    Some(q) => {
+                        key.contains(q)
+                            || iter
+                                .value()
+                                .and_then(|value| std::str::from_utf8(value).ok())
+                                .is_some_and(|value| value.contains(q))
+                    }
+                    None => true,
+                };
+                if matched {
+                    if skipped < start {
+                        skipped += 1;
+                    } else {
+                        keys.push(key.to_string());
+                    }
+                }
+            }
+            iter.next();
+        }
+        iter.status().map_err(|e| e.to_string())?;
+
+        Ok(keys)
+    }
+
+    /// Like `get_all`, but returns every matching key with its value as raw bytes: a
+    /// big-endian `u32` key length, the key, a `u32` value length and the value, repeated.
+    /// Keys and values that are not UTF-8 are included.
+    pub fn get_all_binary(&self, query: Option<String>, flags: ReadFlags) -> Result<Vec<u8>, String> {
+        debug!("Get all entries as binary with query: {:?}, flags: {:?}", query, flags);
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        let contains = |haystack: &[u8], needle: &[u8]| {
+            needle.is_empty() || haystack.windows(needle.len()).any(|window| window == needle)
+        };
+
+        let mut data = Vec::new();
+        for item in db.iterator_opt(rust_rocksdb::IteratorMode::Start, flags.read_options()) {
+            let (key, value) = item.map_err(|e| e.to_string())?;
+            if let Some(q) = &query {
+                if !contains(&key, q.as_bytes()) && !contains(&value, q.as_bytes()) {
+                    continue;
+                }
+            }
+            for part in [&key, &value] {
+                let len = u32::try_from(part.len()).map_err(|_| "Entry too large".to_string())?;
+                data.extend_from_slice(&len.to_be_bytes());
+                data.extend_from_slice(part);
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// Returns the entries from `start` (inclusive, default `prefix`) up to `end` (exclusive)
+    /// whose key starts with `prefix`, in key order, at most `limit` of them. A key that is not
+    /// valid UTF-8 is an error.
+    pub fn get_range(
+        &self,
+        cf_name: Option<String>,
+        prefix: Option<String>,
+        start: Option<String>,
+        end: Option<String>,
+        limit: Option<usize>,
+        flags: ReadFlags,
+    ) -> Result<Vec<(String, String)>, String> {
+        debug!(
+            "Get range with cf_name: {:?}, prefix: {:?}, start: {:?}, end: {:?}, limit: {:?}",
+            cf_name, prefix, start, end, limit
+        );
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        let prefix = prefix.unwrap_or_default();
+        let start = start.unwrap_or_else(|| prefix.clone());
+        let mode = rust_rocksdb::IteratorMode::From(start.as_bytes(), rust_rocksdb::Direction::Forward);
+        let iter = match cf_name {
+            Some(cf_name) => {
+                let cf = db.cf_handle(&cf_name).ok_or(CF_NOT_FOUND)?;
+                db.iterator_cf_opt(&cf, flags.read_options(), mode)
+            }
+            None => db.iterator_opt(mode, flags.read_options()),
+        };
+
+        let mut entries = Vec::new();
+        for item in iter {
+            if limit.is_some_and(|limit| entries.len() >= limit) {
+                break;
+            }
+            let (key, value) = item.map_err(|e| e.to_string())?;
+            if !key.starts_with(prefix.as_bytes()) {
+                // Keys before the prefix are skipped when `start` sorts before it.
+                if *key < *prefix.as_bytes() {
+                    continue;
+                }
+                break;
+            }
+            if end.as_ref().is_some_and(|end| *key >= *end.as_bytes()) {
+                break;
+            }
+            // A lossy conversion could turn distinct binary keys into the same string.
+            let key = String::from_utf8(key.into_vec()).map_err(|e| {
+                format!(
+                    "Key {} is not valid UTF-8; scan binary keys with encoding base64 instead",
+                    String::from_utf8_lossy(e.as_bytes())
+                )
+            })?;
+            entries.push((key, String::from_utf8_lossy(&value).into_owned()));
+        }
+
+        Ok(entries)
+    }
+
+    /// Returns the last `count` entries whose key starts with `prefix`, in descending key order.
+    pub fn tail(
+        &self,
+        cf_name: Option<String>,
+        prefix: Option<String>,
+        count: usize,
+        flags: ReadFlags,
+    ) -> Result<Vec<(String, String)>, String> {
+        debug!(
+            "Tail with cf_name: {:?}, prefix: {:?}, count: {}",
+            cf_name, prefix, count
+        );
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        let prefix = prefix.unwrap_or_default();
+        let mut read_options = flags.read_options();
+        // The first key after the prefix range; without one the range runs to the last key.
+        if let Some(upper_bound) = prefix_successor(prefix.as_bytes()) {
+            read_options.set_iterate_upper_bound(upper_bound);
+        }
+        let mode = rust_rocksdb::IteratorMode::End;
+        let iter = match cf_name {
+            Some(cf_name) => {
+                let cf = db.cf_handle(&cf_name).ok_or(CF_NOT_FOUND)?;
+                db.iterator_cf_opt(&cf, read_options, mode)
+            }
+            None => db.iterator_opt(mode, read_options),
+        };
+
+        let mut entries = Vec::new();
+        for item in iter {
+            if entries.len() >= count {
+                break;
+            }
+            let (key, value) = item.map_err(|e| e.to_string())?;
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
+            }
+            entries.push((
+                String::from_utf8_lossy(&key).into_owned(),
+                String::from_utf8_lossy(&value).into_owned(),
+            ));
+        }
+
+        Ok(entries)
+    }
+
+    pub fn get_keys(
+        &self,
+        start: usize,
+        limit: usize,
+        query: Option<String>,
+        flags: ReadFlags,
+    ) -> Result<Vec<String>, String> {
+        debug!(
+            "Get keys with start: {}, limit: {}, query: {:?}",
+            start, limit, query
+        );
+        let keys = self.scan_keys(query.as_deref(), start, limit, flags)?;
+        debug!("Get keys result: {:?}", keys);
+        Ok(keys)
+    }
+
+    pub fn export(
+        &self,
+        path: &str,
+        cf_name: Option<String>,
+        prefix: Option<String>,
+        flags: ReadFlags,
+    ) -> Result<usize, String> {
+        info!(
+            "Exporting to {} with cf_name: {:?}, prefix: {:?}",
+            path, cf_name, prefix
+        );
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        let prefix = prefix.unwrap_or_default();
+        let mode = rust_rocksdb::IteratorMode::From(prefix.as_bytes(), rust_rocksdb::Direction::Forward);
+        let iter = match cf_name {
+            Some(cf_name) => {
+                let cf = db.cf_handle(&cf_name).ok_or(CF_NOT_FOUND)?;
+                db.iterator_cf_opt(&cf, flags.read_options(), mode)
+            }
+            None => db.iterator_opt(mode, flags.read_options()),
+        };
+
+        let file = File::create(path)
+            .map_err(|e| format!("Failed to create export file {}: {}", path, e))?;
+        let mut writer = BufWriter::new(file);
+
+        let mut count = 0;
+        for item in iter {
+            let (key, value) = item.map_err(|e| e.to_string())?;
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
+            }
+            serde_json::to_writer(&mut writer, &ExportRecord::new(&key, &value))
+                .map_err(|e| e.to_string())?;
+            writer.write_all(b"\n").map_err(|e| e.to_string())?;
+            count += 1;
+        }
+        writer.flush().map_err(|e| e.to_string())?;
+
+        debug!("Exported {} entries to {}", count, path);
+        Ok(count)
+    }
+
+    pub fn import(
+        &self,
+        path: &str,
+        cf_name: Option<String>,
+        overwrite: bool,
+        batch_size: usize,
+        max_key_size: usize,
+    ) -> Result<ImportReport, String> {
+        info!(
+            "Importing from {} with cf_name: {:?}, overwrite: {}, batch_size: {}, max_key_size: {}",
+            path, cf_name, overwrite, batch_size, max_key_size
+        );
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        let cf = match &cf_name {
+            Some(cf_name) => Some(db.cf_handle(cf_name).ok_or(CF_NOT_FOUND)?),
+            None => None,
+        };
+
+        let file = File::open(path)
+            .map_err(|e| format!("Failed to open import file {}: {}", path, e))?;
+        let reader = BufReader::new(file);
+
+        let mut report = ImportReport::default();
+        let mut batch = WriteBatchWithTransaction::<false>::default();
+        // Keys of the unwritten batch, which the existence check below cannot see yet.
+        let mut batched = HashSet::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let parsed = serde_json::from_str::<ExportRecord>(&line)
+                .map_err(|e| e.to_string())
+                .and_then(ExportRecord::into_pair);
+            let (key, value) = match parsed {
+                Ok(pair) => pair,
+                Err(e) => {
+                    report.record_error(index + 1, e);
+                    continue;
+                }
+            };
+            if max_key_size > 0 && key.len() > max_key_size {
+                report.record_error(
+                    index + 1,
+                    format!("key is {} bytes, longer than the maximum of {} bytes", key.len(), max_key_size),
+                );
+                continue;
+            }
+
+            if !overwrite {
+                let existing = match &cf {
+                    Some(cf) => db.get_pinned_cf(cf, &key),
+                    None => db.get_pinned(&key),
+                }
+                .map_err(|e| e.to_string())?;
+                if existing.is_some() || batched.contains(&key) {
+                    report.skipped += 1;
+                    continue;
+                }
+            }
+
+            match &cf {
+                Some(cf) => batch.put_cf(cf, &key, &value),
+                None => batch.put(&key, &value),
+            }
+            report.imported += 1;
+            if !overwrite {
+                batched.insert(key);
+            }
+
+            if batch.len() >= batch_size {
+                db.write(std::mem::take(&mut batch))
+                    .map_err(|e| e.to_string())?;
+                batched.clear();
+            }
+        }
+        if !batch.is_empty() {
+            db.write(batch).map_err(|e| e.to_string())?;
+        }
+
+        debug!("Import result: {:?}", report);
+        Ok(report)
+    }
+
+    /// Whether the database is open; it is closed briefly while a backup is restored.
+    pub fn is_open(&self) -> bool {
+        self.db.read().map(|db| db.is_some()).unwrap_or(false)
+    }
+
+    pub fn close(&self) -> Result<(), String> {
+        info!("Closing database");
+        let mut db_lock = self
+            .db
+            .write()
+            .map_err(|_| "Failed to write DB lock".to_string())?;
+        self.replace_db(&mut db_lock, None)?;
+        Ok(())
+    }
+
+    pub fn reopen(&self) -> Result<(), String> {
+        info!("Reopening database with db_path: {}", self.db_path);
+
+        let new_db = self.open_db_path()?;
+        let mut db_lock = self
+            .db
+            .write()
+            .map_err(|_| "Failed to write DB lock".to_string())?;
+        self.replace_db(&mut db_lock, Some(new_db))?;
+
+        info!("Database reopened successfully");
+        Ok(())
+    }
+
+    fn open_db_path(&self) -> Result<DBWithThreadMode<MultiThreaded>, String> {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.set_merge_operator_associative("json_merge", json_merge);
+        opts.set_atomic_flush(self.atomic_flush);
+        opts.set_enable_pipelined_write(self.pipelined_write);
+        set_rate_limiter(&mut opts, self.rate_limiter_bytes_per_sec);
+        set_storage_paths(&mut opts, self.wal_dir.as_deref(), &self.db_paths)?;
+
+        let cf_names = DBWithThreadMode::<MultiThreaded>::list_cf(&opts, &self.db_path)
+            .unwrap_or(vec!["default".to_string()]);
+        let cf_descriptors: Vec<ColumnFamilyDescriptor> = cf_names
+            .iter()
+            .map(|name| ColumnFamilyDescriptor::new(name, column_family_options(self.compaction)))
+            .collect();
+
+        DBWithThreadMode::<MultiThreaded>::open_cf_descriptors(&opts, &self.db_path, cf_descriptors)
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn reload(&self) -> Result<(), String> {
+        info!("Reloading database");
+        self.close().map_err(|_| "Failed to close db".to_string())?;
+        self.reopen()
+            .map_err(|_| "Failed to reopen db".to_string())?;
+
+        info!("Database reloaded successfully");
+        Ok(())
+    }
+
+    /// Replaces the database files with the ones in `staging_path` and reopens.
+    ///
+    /// The staging database is opened read-only first, so a broken one, or one holding a key longer than
+    /// `max_key_size` bytes (`0` for no limit), is rejected before anything moves.
+    /// Afterwards `staging_path` holds the previous database, which makes a second swap an undo.
+    pub fn swap_db(&self, staging_path: &str, max_key_size: usize) -> Result<(), String> {
+        info!("Swapping database with {}", staging_path);
+
+        let db_path = Path::new(&self.db_path);
+        let staging = Path::new(staging_path);
+        let same_dir = match (db_path.canonicalize(), staging.canonicalize()) {
+            (Ok(db_path), Ok(staging)) => db_path == staging,
+            (_, Err(e)) => return Err(format!("Failed to open {}: {}", staging_path, e)),
+            (Err(e), _) => return Err(format!("Failed to open {}: {}", self.db_path, e)),
+        };
+        if same_dir {
+            return Err("Staging path must differ from the database path".to_string());
+        }
+        // The swapped files would mix with the WAL and table files kept elsewhere.
+        if self.wal_dir.is_some() || !self.db_paths.is_empty() {
+            return Err("Cannot swap a database with a separate WAL directory or table file paths".to_string());
+        }
+
+        if self.txn_db.lock().map_err(|_| "Failed to acquire transaction DB lock")?.is_some() {
+            return Err("Cannot swap the database during a transaction".to_string());
+        }
+        if self.bulk_load.lock().map_err(|_| "Failed to acquire bulk load lock")?.is_some() {
+            return Err("Cannot swap the database during a bulk load".to_string());
+        }
 
-        let cf_names = DBWithThreadMode::<MultiThreaded>::list_cf(&opts, &self.db_path)
-            .unwrap_or(vec!["default".to_string()]);
-        let cf_descriptors: Vec<ColumnFamilyDescriptor> = cf_names
-            .iter()
-            .map(|name| {
-                let mut cf_opts = Options::default();
-                cf_opts.set_merge_operator_associative("json_merge", json_merge);
-                ColumnFamilyDescriptor::new(name, cf_opts)
-            })
-            .collect();
+        self.verify_staging_db(staging_path, max_key_size)?;
 
-        let new_db = DBWithThreadMode::<MultiThreaded>::open_cf_descriptors(
-            &opts,
-            &self.db_path,
-            cf_descriptors,
-        )
-        .map_err(|e| e.to_string())?;
+        // Hold the write lock throughout, so no request sees a half-moved directory.
         let mut db_lock = self
             .db
             .write()
             .map_err(|_| "Failed to write DB lock".to_string())?;
-        *db_lock = Some(new_db);
+        // Dropping the handle releases the RocksDB lock file.
+        self.replace_db(&mut db_lock, None)?;
+
+        let swap_dir = db_path.join(SWAP_DIR);
+        if let Err(e) = swap_files(db_path, staging, &swap_dir) {
+            error!("Failed to swap database with {}: {}", staging_path, e);
+            // The files were moved back unless the error says otherwise.
+            if db_path.join("CURRENT").is_file() {
+                let db = self.open_db_path()?;
+                self.replace_db(&mut db_lock, Some(db))?;
+            }
+            return Err(format!("Failed to swap database: {}", e));
+        }
 
-        info!("Database reopened successfully");
-        Ok(())
+        match self.open_db_path() {
+            Ok(new_db) => {
+                self.replace_db(&mut db_lock, Some(new_db))?;
+                info!("Database swapped with {}", staging_path);
+                Ok(())
+            }
+            Err(e) => {
+                error!("Swapped database failed to open: {}", e);
+                swap_files(db_path, staging, &swap_dir).map_err(|undo_error| {
+                    format!("Swapped database failed to open: {}; {}", e, undo_error)
+                })?;
+                let db = self.open_db_path()?;
+                self.replace_db(&mut db_lock, Some(db))?;
+                Err(format!("Swapped database failed to open, kept the current one: {}", e))
+            }
+        }
     }
 
-    pub fn reload(&self) -> Result<(), String> {
-        info!("Reloading database");
-        self.close().map_err(|_| "Failed to close db".to_string())?;
-        self.reopen()
-            .map_err(|_| "Failed to reopen db".to_string())?;
+    fn verify_staging_db(&self, staging_path: &str, max_key_size: usize) -> Result<(), String> {
+        let opts = Options::default();
+        let cf_names = DBWithThreadMode::<MultiThreaded>::list_cf(&opts, staging_path)
+            .map_err(|e| format!("{} is not a valid database: {}", staging_path, e))?;
+        let cf_descriptors: Vec<ColumnFamilyDescriptor> = cf_names
+            .iter()
+            .map(|name| ColumnFamilyDescriptor::new(name, column_family_options(self.compaction)))
+            .collect();
 
-        info!("Database reloaded successfully");
+        let staging = DBWithThreadMode::<MultiThreaded>::open_cf_descriptors_read_only(
+            &column_family_options(self.compaction),
+            staging_path,
+            cf_descriptors,
+            false,
+        )
+        .map_err(|e| format!("{} is not a valid database: {}", staging_path, e))?;
+        if max_key_size == 0 {
+            return Ok(());
+        }
+
+        for cf_name in &cf_names {
+            let cf = staging.cf_handle(cf_name).ok_or(CF_NOT_FOUND)?;
+            let mut iter = staging.raw_iterator_cf(&cf);
+            iter.seek_to_first();
+            while let Some(key) = iter.key() {
+                if key.len() > max_key_size {
+                    return Err(format!(
+                        "{} holds a key of {} bytes in column family {}, longer than the maximum of {} bytes",
+                        staging_path,
+                        key.len(),
+                        cf_name,
+                        max_key_size
+                    ));
+                }
+                iter.next();
+            }
+            iter.status().map_err(|e| e.to_string())?;
+        }
         Ok(())
     }
 
     pub fn list_column_families(&self) -> Result<Vec<String>, String> {
         debug!("Listing column families for path: {}", self.db_path.clone());
         let opts = Options::default();
-        let result = DBWithThreadMode::<MultiThreaded>::list_cf(&opts, self.db_path.clone())
-            .map_err(|e| e.to_string());
-        debug!("List column families result: {:?}", result);
-        result
+        let mut cfs = DBWithThreadMode::<MultiThreaded>::list_cf(&opts, self.db_path.clone())
+            .map_err(|e| e.to_string())?;
+        // `list_cf` follows the manifest order, which changes as families are dropped and re-created.
+        cfs.sort_by(|a, b| {
+            (a != DEFAULT_COLUMN_FAMILY_NAME, a).cmp(&(b != DEFAULT_COLUMN_FAMILY_NAME, b))
+        });
+        debug!("List column families result: {:?}", cfs);
+        Ok(cfs)
+    }
+
+    /// Reads every entry of every column family, or only those under `prefixes`, so that their
+    /// blocks are in the block cache before traffic arrives. Returns the number of entries read.
+    pub fn warm_block_cache(&self, prefixes: &[String]) -> Result<u64, String> {
+        info!("Warming the block cache, prefixes: {:?}", prefixes);
+
+        let cf_names = self.list_column_families()?;
+        let whole_range = [String::new()];
+        let prefixes = if prefixes.is_empty() { &whole_range[..] } else { prefixes };
+
+        let mut entries = 0;
+        for cf_name in &cf_names {
+            for prefix in prefixes {
+                // The DB lock is taken per chunk, so a reopen, e.g. by `begin_transaction`, waits for one chunk
+                // at most; once the database is closed the warm-up stops.
+                let mut next = Some(prefix.as_bytes().to_vec());
+                while let Some(start) = next.take() {
+                    let db = self
+                        .db
+                        .read()
+                        .map_err(|_| "Failed to read DB lock".to_string())?;
+                    let Some(db) = db.as_ref() else {
+                        info!("Stopped warming the block cache after {} entries, the database was closed", entries);
+                        return Ok(entries);
+                    };
+                    let Some(cf) = db.cf_handle(cf_name) else {
+                        break;
+                    };
+
+                    let mut read_opts = ReadOptions::default();
+                    if let Some(upper_bound) = prefix_successor(prefix.as_bytes()) {
+                        read_opts.set_iterate_upper_bound(upper_bound);
+                    }
+                    let mut iter = db.raw_iterator_cf_opt(&cf, read_opts);
+                    iter.seek(&start);
+                    let mut chunk = 0;
+                    while let Some(key) = iter.key() {
+                        if chunk == WARM_CHUNK_SIZE {
+                            next = Some(key.to_vec());
+                            break;
+                        }
+                        chunk += 1;
+                        iter.next();
+                    }
+                    iter.status().map_err(|e| e.to_string())?;
+                    entries += chunk;
+                }
+            }
+        }
+
+        info!("Block cache warmed with {} entries", entries);
+        Ok(entries)
     }
 
-    pub fn create_column_family(&self, cf_name: String) -> Result<(), String> {
+    /// Returns `false` if the column family already existed.
+    pub fn create_column_family(&self, cf_name: String) -> Result<bool, String> {
         info!("Creating column family: {}", cf_name);
 
-        let mut db = self
+        // Creating and dropping column families is safe alongside other operations, so a
+        // running compaction does not hold this up.
+        let db = self
             .db
-            .write()
-            .map_err(|_| "Failed to write DB lock".to_string())?;
-        let db = db.as_mut().ok_or("Database is not open".to_string())?;
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
 
         let result = if db.cf_handle(&cf_name).is_some() {
-            Ok(())
+            Ok(false)
         } else {
-            let mut opts = Options::default();
-            opts.set_merge_operator_associative("json_merge", json_merge);
-            db.create_cf(&cf_name, &opts).map_err(|e| e.to_string())
+            db.create_cf(&cf_name, &column_family_options(self.compaction))
+                .map(|_| true)
+                .map_err(|e| e.to_string())
         };
 
         debug!("Create column family result: {:?}", result);
         result
     }
 
-    pub fn drop_column_family(&self, cf_name: String) -> Result<(), String> {
+    /// Returns `false` if there was no such column family.
+    pub fn drop_column_family(&self, cf_name: String) -> Result<bool, String> {
         info!("Dropping column family: {}", cf_name);
 
-        let mut db = self
+        let db = self
             .db
-            .write()
-            .map_err(|_| "Failed to write DB lock".to_string())?;
-        let db = db.as_mut().ok_or("Database is not open".to_string())?;
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
 
         let result = if db.cf_handle(&cf_name).is_some() {
-            db.drop_cf(&cf_name).map_err(|e| e.to_string())
+            db.drop_cf(&cf_name)
+                .map(|_| true)
+                .map_err(|e| e.to_string())
         } else {
-            Ok(())
+            Ok(false)
         };
 
         debug!("Drop column family result: {:?}", result);
         result
     }
 
+    /// Compacts `[start, end)`, unbounded where `None`, and returns the total SST size before and after.
     pub fn compact_range(
         &self,
         start: Option<String>,
         end: Option<String>,
         cf_name: Option<String>,
-    ) -> Result<(), String> {
+    ) -> Result<(u64, u64), String> {
         debug!(
             "Compacting range with start: {:?}, end: {:?}, cf_name: {:?}",
             start, end, cf_name
@@ -831,23 +3051,172 @@ impl RocksDBManager {
             .read()
             .map_err(|_| "Failed to read DB lock".to_string())?;
         let db = db.as_ref().ok_or("Database is not open".to_string())?;
+        let cf = match &cf_name {
+            Some(cf_name) => Some(db.cf_handle(cf_name).ok_or(CF_NOT_FOUND.to_string())?),
+            None => None,
+        };
 
-        let result = match cf_name {
+        let before = total_sst_size(db, cf.as_ref())?;
+        match &cf {
+            Some(cf) => db.compact_range_cf(cf, start.as_deref(), end.as_deref()),
+            None => db.compact_range(start.as_deref(), end.as_deref()),
+        }
+        let after = total_sst_size(db, cf.as_ref())?;
+
+        debug!("Compact range shrank SST files from {} to {} bytes", before, after);
+        Ok((before, after))
+    }
+
+    /// Drops the SST files whose keys all lie in `[start, end]` and returns how many bytes of SST files were freed.
+    pub fn delete_files_in_range(
+        &self,
+        start: &str,
+        end: &str,
+        cf_name: Option<String>,
+    ) -> Result<u64, String> {
+        info!(
+            "Deleting files in range start: {}, end: {}, cf_name: {:?}",
+            start, end, cf_name
+        );
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+        let cf = match &cf_name {
+            Some(cf_name) => Some(db.cf_handle(cf_name).ok_or(CF_NOT_FOUND)?),
+            None => None,
+        };
+
+        let before = total_sst_size(db, cf.as_ref())?;
+        match &cf {
+            Some(cf) => db.delete_file_in_range_cf(cf, start, end),
+            None => db.delete_file_in_range(start, end),
+        }
+        .map_err(|e| e.to_string())?;
+
+        // Flushes finishing meanwhile can grow the total, so this is a lower bound.
+        Ok(before.saturating_sub(total_sst_size(db, cf.as_ref())?))
+    }
+
+    pub fn sync_wal(&self) -> Result<(), String> {
+        debug!("Syncing WAL");
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        db.flush_wal(true).map_err(|e| e.to_string())
+    }
+
+    /// Changes one of `MUTABLE_OPTIONS` of a column family without reopening the database.
+    pub fn set_db_option(&self, name: &str, value: &str, cf_name: Option<String>) -> Result<(), String> {
+        info!(
+            "Setting option {} to {}, cf_name: {:?}",
+            name, value, cf_name
+        );
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        match cf_name {
             Some(cf_name) => {
-                let cf = db
-                    .cf_handle(&cf_name)
-                    .ok_or("Column family not found".to_string())?;
-                db.compact_range_cf(&cf, start.as_deref(), end.as_deref());
-                Ok(())
+                let cf = db.cf_handle(&cf_name).ok_or(CF_NOT_FOUND)?;
+                db.set_options_cf(&cf, &[(name, value)])
             }
-            None => {
-                db.compact_range(start.as_deref(), end.as_deref());
-                Ok(())
+            None => db.set_options(&[(name, value)]),
+        }
+        .map_err(|e| e.to_string())
+    }
+
+    /// Starts a bulk load: `write_batch_write` skips the WAL until `end_bulk_load`.
+    pub fn begin_bulk_load(&self, disable_auto_compactions: bool) -> Result<(), String> {
+        info!(
+            "Beginning bulk load, disable_auto_compactions: {}",
+            disable_auto_compactions
+        );
+
+        let mut bulk_load = self
+            .bulk_load
+            .lock()
+            .map_err(|_| "Failed to lock bulk load state".to_string())?;
+        if bulk_load.is_some() {
+            return Err("Bulk load already in progress".to_string());
+        }
+
+        if disable_auto_compactions {
+            self.set_auto_compactions(false)?;
+        }
+        *bulk_load = Some(BulkLoad {
+            auto_compactions_disabled: disable_auto_compactions,
+        });
+        Ok(())
+    }
+
+    /// Ends a bulk load: flushes every column family so the WAL-less writes are durable,
+    /// re-enables auto compactions and, if `compact` is set, compacts everything.
+    pub fn end_bulk_load(&self, compact: bool) -> Result<(), String> {
+        info!("Ending bulk load, compact: {}", compact);
+
+        let mut bulk_load = self
+            .bulk_load
+            .lock()
+            .map_err(|_| "Failed to lock bulk load state".to_string())?;
+        let state = bulk_load.ok_or("No bulk load in progress".to_string())?;
+
+        let cf_names = self.list_column_families()?;
+        {
+            let db = self
+                .db
+                .read()
+                .map_err(|_| "Failed to read DB lock".to_string())?;
+            let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+            for cf_name in &cf_names {
+                match db.cf_handle(cf_name) {
+                    Some(cf) => db.flush_cf(&cf),
+                    None => db.flush(),
+                }
+                .map_err(|e| format!("Failed to flush {}: {}", cf_name, e))?;
             }
-        };
+        }
 
-        debug!("Compact range result: {:?}", result);
-        result
+        // The load is durable now; a failure below leaves only compaction settings to fix.
+        *bulk_load = None;
+        if state.auto_compactions_disabled {
+            self.set_auto_compactions(true)?;
+        }
+        if compact {
+            for cf_name in cf_names {
+                let cf_name = (cf_name != DEFAULT_COLUMN_FAMILY_NAME).then_some(cf_name);
+                self.compact_range(None, None, cf_name)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_auto_compactions(&self, enabled: bool) -> Result<(), String> {
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        let value = if enabled { "false" } else { "true" };
+        for cf_name in self.list_column_families()? {
+            match db.cf_handle(&cf_name) {
+                Some(cf) => db.set_options_cf(&cf, &[("disable_auto_compactions", value)]),
+                None => db.set_options(&[("disable_auto_compactions", value)]),
+            }
+            .map_err(|e| format!("Failed to configure compactions of {}: {}", cf_name, e))?;
+        }
+        Ok(())
     }
 
     pub fn write_batch_put(
@@ -873,19 +3242,20 @@ impl RocksDBManager {
             .map_err(|_| "Failed to lock write batch".to_string())?;
         let wb = batch
             .as_mut()
-            .ok_or("WriteBatch not initialized".to_string())?;
+            .ok_or(BATCH_NOT_INITIALIZED.to_string())?;
 
         match cf_name.clone() {
             Some(cf_name) => {
                 let cf = db
                     .cf_handle(&cf_name)
-                    .ok_or("Column family not found".to_string())?;
-                wb.put_cf(&cf, key.as_bytes(), value.as_bytes());
+                    .ok_or(CF_NOT_FOUND.to_string())?;
+                wb.batch.put_cf(&cf, key.as_bytes(), value.as_bytes());
             }
             None => {
-                wb.put(key.as_bytes(), value.as_bytes());
+                wb.batch.put(key.as_bytes(), value.as_bytes());
             }
         }
+        wb.keys.push((key.clone(), cf_name.clone()));
 
         debug!(
             "Write batch put with key: {}, value: {}, cf_name: {:?} completed successfully",
@@ -919,19 +3289,20 @@ impl RocksDBManager {
             .map_err(|_| "Failed to lock write batch".to_string())?;
         let wb = batch
             .as_mut()
-            .ok_or("WriteBatch not initialized".to_string())?;
+            .ok_or(BATCH_NOT_INITIALIZED.to_string())?;
 
         match cf_name.clone() {
             Some(cf_name) => {
                 let cf = db
                     .cf_handle(&cf_name)
-                    .ok_or("Column family not found".to_string())?;
-                wb.merge_cf(&cf, key.as_bytes(), value.as_bytes());
+                    .ok_or(CF_NOT_FOUND.to_string())?;
+                wb.batch.merge_cf(&cf, key.as_bytes(), value.as_bytes());
             }
             None => {
-                wb.merge(key.as_bytes(), value.as_bytes());
+                wb.batch.merge(key.as_bytes(), value.as_bytes());
             }
         }
+        wb.keys.push((key.clone(), cf_name.clone()));
 
         debug!(
             "Write batch merge with key: {}, value: {}, cf_name: {:?} completed successfully",
@@ -960,19 +3331,20 @@ impl RocksDBManager {
             .map_err(|_| "Failed to lock write batch".to_string())?;
         let wb = batch
             .as_mut()
-            .ok_or("WriteBatch not initialized".to_string())?;
+            .ok_or(BATCH_NOT_INITIALIZED.to_string())?;
 
         match cf_name.clone() {
             Some(cf_name) => {
                 let cf = db
                     .cf_handle(&cf_name)
-                    .ok_or("Column family not found".to_string())?;
-                wb.delete_cf(&cf, key.as_bytes());
+                    .ok_or(CF_NOT_FOUND.to_string())?;
+                wb.batch.delete_cf(&cf, key.as_bytes());
             }
             None => {
-                wb.delete(key.as_bytes());
+                wb.batch.delete(key.as_bytes());
             }
         }
+        wb.keys.push((key.clone(), cf_name.clone()));
 
         debug!(
             "Write batch delete with key: {}, cf_name: {:?} completed successfully",
@@ -982,7 +3354,8 @@ impl RocksDBManager {
         Ok(())
     }
 
-    pub fn write_batch_write(&self) -> Result<(), String> {
+    /// Writes the batch and returns the `(key, cf_name)` pairs it wrote, in the order they were added.
+    pub fn write_batch_write(&self, flags: WriteFlags) -> Result<Vec<(String, Option<String>)>, String> {
         debug!("Write batch write");
 
         let db = self
@@ -996,15 +3369,27 @@ impl RocksDBManager {
             .lock()
             .map_err(|_| "Failed to lock write batch".to_string())?;
 
+        // During a bulk load batches skip the WAL; `end_bulk_load` flushes them to disk.
+        let bulk_load = self
+            .bulk_load
+            .lock()
+            .map_err(|_| "Failed to lock bulk load state".to_string())?
+            .is_some();
+        let mut write_opts = flags.write_options();
+        write_opts.disable_wal(bulk_load);
+
         let result = if let Some(wb) = batch.take() {
-            db.write(wb).map_err(|e| e.to_string())?;
-            *batch = Some(WriteBatchWithTransaction::default());
-            Ok(())
+            db.write_opt(wb.batch, &write_opts).map_err(|e| e.to_string())?;
+            *batch = Some(PendingBatch::default());
+            Ok(wb.keys)
         } else {
-            Err("WriteBatch not initialized".into())
+            Err(BATCH_NOT_INITIALIZED.into())
         };
 
-        debug!("Write batch write result: {:?}", result);
+        debug!(
+            "Write batch write result: {:?}",
+            result.as_ref().map(|keys| keys.len())
+        );
         result
     }
 
@@ -1018,13 +3403,25 @@ impl RocksDBManager {
 
         match batch.as_mut() {
             Some(wb) => {
-                wb.clear();
+                wb.batch.clear();
+                wb.keys.clear();
                 Ok(())
             }
-            None => Err("WriteBatch not initialized".to_string()),
+            None => Err(BATCH_NOT_INITIALIZED.to_string()),
         }
     }
 
+    /// Starts a new empty batch, discarding the current one if there is any.
+    pub fn write_batch_create(&self) -> Result<(), String> {
+        debug!("Write batch create");
+        let mut batch = self
+            .write_batch
+            .lock()
+            .map_err(|_| "Failed to lock write batch".to_string())?;
+        *batch = Some(PendingBatch::default());
+        Ok(())
+    }
+
     pub fn write_batch_destroy(&self) -> Result<(), String> {
         debug!("Write batch destroy");
         let mut batch = self
@@ -1035,17 +3432,96 @@ impl RocksDBManager {
         Ok(())
     }
 
-    pub fn create_iterator(&self) -> Result<usize, String> {
-        debug!("Creating iterator");
+    /// Creates an unpositioned iterator; with `base64`, keys and values are base64-encoded.
+    pub fn create_iterator(&self, base64: bool) -> Result<usize, String> {
+        debug!("Creating iterator with base64: {}", base64);
+        self.insert_iterator(IteratorState {
+            base64,
+            ..IteratorState::default()
+        })
+    }
+
+    fn insert_iterator(&self, state: IteratorState) -> Result<usize, String> {
         let mut iterators = self
             .iterators
             .lock()
             .map_err(|_| "Failed to lock iterators".to_string())?;
         let id = self.iterator_id_counter.fetch_add(1, Ordering::SeqCst);
-        iterators.insert(id, (vec![], rust_rocksdb::Direction::Forward));
+        iterators.insert(id, state);
         Ok(id)
     }
 
+    /// Opens an iterator limited to `[lower_bound, upper_bound)` and returns its id with the first `limit`
+    /// entries from `start` (default: the first key in `reverse` order), and whether the range is exhausted.
+    /// With `base64`, `start` and the bounds are base64-encoded, like the entries returned.
+    #[allow(clippy::too_many_arguments)]
+    pub fn scan_open(
+        &self,
+        start: Option<String>,
+        lower_bound: Option<String>,
+        upper_bound: Option<String>,
+        reverse: bool,
+        base64: bool,
+        limit: usize,
+        flags: ReadFlags,
+    ) -> Result<(usize, Vec<IteratorEntry>, bool), String> {
+        debug!(
+            "Scan open with start: {:?}, lower_bound: {:?}, upper_bound: {:?}, reverse: {}, base64: {}, limit: {}",
+            start, lower_bound, upper_bound, reverse, base64, limit
+        );
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        let mut state = IteratorState {
+            reverse,
+            base64,
+            ..IteratorState::default()
+        };
+        state.lower_bound = lower_bound.map(|key| state.decode_key(key)).transpose()?;
+        state.upper_bound = upper_bound.map(|key| state.decode_key(key)).transpose()?;
+        let start = start.map(|key| state.decode_key(key)).transpose()?;
+
+        let direction = state.direction();
+        let (entries, exhausted) = walk_iterator(db, &mut state, start.as_deref(), direction, limit, flags)?;
+        let entries = state.encode_entries(entries);
+
+        let id = self.insert_iterator(state)?;
+        Ok((id, entries, exhausted))
+    }
+
+    /// Returns up to `limit` entries after the iterator's position in its scan direction, and whether
+    /// the range is exhausted.
+    pub fn iterator_next_batch(
+        &self,
+        iterator_id: usize,
+        limit: usize,
+        flags: ReadFlags,
+    ) -> Result<(Vec<IteratorEntry>, bool), String> {
+        debug!("Iterator next batch with id: {}, limit: {}", iterator_id, limit);
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        let mut iterators = self
+            .iterators
+            .lock()
+            .map_err(|_| "Failed to lock iterators".to_string())?;
+        let state = iterators
+            .get_mut(&iterator_id)
+            .ok_or(ITERATOR_NOT_FOUND.to_string())?;
+
+        let direction = state.direction();
+        let (entries, exhausted) = walk_iterator(db, state, None, direction, limit, flags)?;
+        Ok((state.encode_entries(entries), exhausted))
+    }
+
     pub fn destroy_iterator(&self, iterator_id: usize) -> Result<(), String> {
         debug!("Destroying iterator with id: {}", iterator_id);
 
@@ -1056,7 +3532,7 @@ impl RocksDBManager {
 
         iterators
             .remove(&iterator_id)
-            .map_or_else(|| Err("Iterator ID not found".to_string()), |_| Ok(()))
+            .map_or_else(|| Err(ITERATOR_NOT_FOUND.to_string()), |_| Ok(()))
     }
 
     pub fn iterator_seek(
@@ -1064,6 +3540,7 @@ impl RocksDBManager {
         iterator_id: usize,
         key: String,
         direction: rust_rocksdb::Direction,
+        flags: ReadFlags,
     ) -> Result<String, String> {
         let direction_str = match direction {
             rust_rocksdb::Direction::Forward => "Forward",
@@ -1074,78 +3551,29 @@ impl RocksDBManager {
             "Iterator seek with id: {}, key: {}, direction: {:?}",
             iterator_id, key, direction_str
         );
-
-        let db = self
-            .db
-            .read()
-            .map_err(|_| "Failed to read DB lock".to_string())?;
-        let db = db.as_ref().ok_or("Database is not open".to_string())?;
-
-        let mut iterators = self
-            .iterators
-            .lock()
-            .map_err(|_| "Failed to lock iterators".to_string())?;
-        let iterator = iterators
-            .get_mut(&iterator_id)
-            .ok_or("Iterator ID not found".to_string())?;
-
-        let mut iter = db.iterator(rust_rocksdb::IteratorMode::From(key.as_bytes(), direction));
-
-        if let Some(Ok((k, v))) = iter.next() {
-            iterator.0 = k.to_vec();
-            iterator.1 = direction;
-
-            let result = format!(
-                "{}:{}",
-                String::from_utf8(k.to_vec()).unwrap_or_else(|_| "invalid".to_string()),
-                String::from_utf8(v.to_vec()).unwrap_or_else(|_| "invalid".to_string())
-            );
-            debug!("Iterator seek result: {}", result);
-            Ok(result)
-        } else {
-            Ok("invalid:invalid".to_string())
-        }
+        self.iterator_move(iterator_id, Some(key), direction, flags)
     }
 
-    pub fn iterator_next(&self, iterator_id: usize) -> Result<String, String> {
+    /// Moves to the next key in key order, or to the first key if the iterator was never positioned.
+    pub fn iterator_next(&self, iterator_id: usize, flags: ReadFlags) -> Result<String, String> {
         debug!("Iterator next with id: {}", iterator_id);
-
-        let db = self
-            .db
-            .read()
-            .map_err(|_| "Failed to read DB lock".to_string())?;
-        let db = db.as_ref().ok_or("Database is not open".to_string())?;
-
-        let mut iterators = self
-            .iterators
-            .lock()
-            .map_err(|_| "Failed to lock iterators".to_string())?;
-        let iterator = iterators
-            .get_mut(&iterator_id)
-            .ok_or("Iterator ID not found".to_string())?;
-
-        let (ref mut pos, direction) = *iterator;
-        let mut iter = db.iterator(rust_rocksdb::IteratorMode::From(pos, direction));
-
-        iter.next(); // Move to current position
-        if let Some(Ok((k, v))) = iter.next() {
-            pos.clear();
-            pos.extend_from_slice(&k);
-            let result = format!(
-                "{}:{}",
-                String::from_utf8(k.to_vec()).unwrap_or_else(|_| "invalid".to_string()),
-                String::from_utf8(v.to_vec()).unwrap_or_else(|_| "invalid".to_string())
-            );
-            debug!("Iterator next result: {}", result);
-            Ok(result)
-        } else {
-            Ok("invalid:invalid".to_string())
-        }
+        self.iterator_move(iterator_id, None, rust_rocksdb::Direction::Forward, flags)
     }
 
-    pub fn iterator_prev(&self, iterator_id: usize) -> Result<String, String> {
+    /// Moves to the previous key in key order, or to the last key if the iterator was never positioned.
+    pub fn iterator_prev(&self, iterator_id: usize, flags: ReadFlags) -> Result<String, String> {
         debug!("Iterator prev with id: {}", iterator_id);
+        self.iterator_move(iterator_id, None, rust_rocksdb::Direction::Reverse, flags)
+    }
 
+    // Running past either end reports `invalid:invalid` and keeps the position on the last key returned.
+    fn iterator_move(
+        &self,
+        iterator_id: usize,
+        seek: Option<String>,
+        direction: rust_rocksdb::Direction,
+        flags: ReadFlags,
+    ) -> Result<String, String> {
         let db = self
             .db
             .read()
@@ -1156,40 +3584,31 @@ impl RocksDBManager {
             .iterators
             .lock()
             .map_err(|_| "Failed to lock iterators".to_string())?;
-        let iterator = iterators
+        let state = iterators
             .get_mut(&iterator_id)
-            .ok_or("Iterator ID not found".to_string())?;
-
-        let (ref mut pos, _direction) = *iterator;
-        let mut iter = db.iterator(rust_rocksdb::IteratorMode::From(
-            pos,
-            rust_rocksdb::Direction::Reverse,
-        ));
-
-        iter.next(); // Move to current position
-        if let Some(Ok((k, v))) = iter.next() {
-            pos.clear();
-            pos.extend_from_slice(&k);
-            let result = format!(
-                "{}:{}",
-                String::from_utf8(k.to_vec()).unwrap_or_else(|_| "invalid".to_string()),
-                String::from_utf8(v.to_vec()).unwrap_or_else(|_| "invalid".to_string())
-            );
-            debug!("Iterator prev result: {}", result);
-            Ok(result)
-        } else {
-            Ok("invalid:invalid".to_string())
+            .ok_or(ITERATOR_NOT_FOUND.to_string())?;
+
+        let seek = seek.map(|key| state.decode_key(key)).transpose()?;
+        let (entries, _) = walk_iterator(db, state, seek.as_deref(), direction, 1, flags)?;
+        match entries.first() {
+            Some((k, v)) => {
+                let result = state.format_entry(k, v);
+                debug!("Iterator move result: {}", result);
+                Ok(result)
+            }
+            None => Ok("invalid:invalid".to_string()),
         }
     }
 
+    fn open_backup_engine(&self) -> Result<BackupEngine, String> {
+        let backup_opts = BackupEngineOptions::new(&self.backup_path).map_err(|e| e.to_string())?;
+        BackupEngine::open(&backup_opts, &Env::new().map_err(|e| e.to_string())?).map_err(|e| e.to_string())
+    }
+
     pub fn backup(&self) -> Result<(), String> {
         info!("Creating backup");
 
-        let backup_path = format!("{}/backup", self.db_path);
-        let backup_opts = BackupEngineOptions::new(&backup_path).map_err(|e| e.to_string())?;
-        let mut backup_engine =
-            BackupEngine::open(&backup_opts, &Env::new().map_err(|e| e.to_string())?)
-                .map_err(|e| e.to_string())?;
+        let mut backup_engine = self.open_backup_engine()?;
 
         let db = self
             .db
@@ -1208,17 +3627,13 @@ impl RocksDBManager {
     pub fn restore_latest_backup(&self) -> Result<(), String> {
         info!("Restoring latest backup");
 
-        let backup_path = format!("{}/backup", self.db_path);
-        let backup_opts = BackupEngineOptions::new(&backup_path).map_err(|e| e.to_string())?;
-        let mut backup_engine =
-            BackupEngine::open(&backup_opts, &Env::new().map_err(|e| e.to_string())?)
-                .map_err(|e| e.to_string())?;
+        let mut backup_engine = self.open_backup_engine()?;
 
         let restore_opts = RestoreOptions::default();
         backup_engine
             .restore_from_latest_backup(
                 Path::new(&self.db_path),
-                Path::new(&self.db_path),
+                Path::new(self.wal_dir.as_deref().unwrap_or(&self.db_path)),
                 &restore_opts,
             )
             .map_err(|e| e.to_string())?;
@@ -1232,17 +3647,13 @@ impl RocksDBManager {
     pub fn restore_backup(&self, backup_id: u32) -> Result<(), String> {
         info!("Restoring backup with id: {}", backup_id);
 
-        let backup_path = format!("{}/backup", self.db_path);
-        let backup_opts = BackupEngineOptions::new(&backup_path).map_err(|e| e.to_string())?;
-        let mut backup_engine =
-            BackupEngine::open(&backup_opts, &Env::new().map_err(|e| e.to_string())?)
-                .map_err(|e| e.to_string())?;
+        let mut backup_engine = self.open_backup_engine()?;
 
         let restore_opts = RestoreOptions::default();
         backup_engine
             .restore_from_backup(
                 Path::new(&self.db_path),
-                Path::new(&self.db_path),
+                Path::new(self.wal_dir.as_deref().unwrap_or(&self.db_path)),
                 &restore_opts,
                 backup_id,
             )
@@ -1259,15 +3670,69 @@ impl RocksDBManager {
 
     pub fn get_backup_info(&self) -> Result<Vec<BackupInfo>, String> {
         info!("Getting backup info");
-        let backup_path = format!("{}/backup", self.db_path);
-        let backup_opts = BackupEngineOptions::new(&backup_path).map_err(|e| e.to_string())?;
-        let backup_engine =
-            BackupEngine::open(&backup_opts, &Env::new().map_err(|e| e.to_string())?)
-                .map_err(|e| e.to_string())?;
+        let backup_engine = self.open_backup_engine()?;
 
         let info = backup_engine.get_backup_info();
         let backup_info: Vec<BackupInfo> = info.into_iter().map(BackupInfo::from).collect();
         debug!("Get backup info result: {:?}", backup_info);
         Ok(backup_info)
     }
+
+    /// Writes a consistent copy of the database to `path`, which must not exist yet.
+    ///
+    /// SST files are hard-linked when `path` is on the same filesystem, so the copy costs little disk.
+    pub fn create_checkpoint(&self, path: &Path) -> Result<(), String> {
+        info!("Creating checkpoint in {}", path.display());
+
+        let db = self
+            .db
+            .read()
+            .map_err(|_| "Failed to read DB lock".to_string())?;
+        let db = db.as_ref().ok_or("Database is not open".to_string())?;
+
+        Checkpoint::new(db)
+            .and_then(|checkpoint| checkpoint.create_checkpoint(path))
+            .map_err(|e| format!("Failed to create checkpoint: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn json_merge_keeps_integers_beyond_f64_precision() {
+        let dir = TempDir::new();
+        let manager = RocksDBManager::new(&dir.join("db"), None, DbConfig::default()).unwrap();
+        manager
+            .put("doc".to_string(), r#"{"id":9007199254740993}"#.to_string(), None, None, WriteFlags::default())
+            .unwrap();
+        manager
+            .merge(
+                "doc".to_string(),
+                r#"[{"op":"add","path":"/parent","value":9007199254740993}]"#.to_string(),
+                None,
+                None,
+                WriteFlags::default(),
+            )
+            .unwrap();
+
+        let merged = manager.get("doc".to_string(), None, None, None, ReadFlags::default()).unwrap();
+        assert_eq!(merged.as_deref(), Some(r#"{"id":9007199254740993,"parent":9007199254740993}"#));
+    }
+
+    #[test]
+    fn numeric_add_keeps_u64_sums_exact() {
+        let sum = MergeOp::NumericAdd.apply(Some("18446744073709551614"), "1").unwrap();
+        assert_eq!(sum, "18446744073709551615");
+        assert_eq!(MergeOp::NumericAdd.apply(Some("-1"), "18446744073709551615").unwrap(), "18446744073709551614");
+    }
+
+    #[test]
+    fn numeric_add_refuses_to_round_integers() {
+        assert!(MergeOp::NumericAdd.apply(Some("18446744073709551615"), "1").is_err());
+        assert!(MergeOp::NumericAdd.apply(Some("18446744073709551616"), "0.5").is_err());
+        assert_eq!(MergeOp::NumericAdd.apply(Some("1.5"), "1").unwrap(), "2.5");
+    }
 }
@@ -0,0 +1,138 @@
+use crate::db_manager::{CF_NOT_FOUND, ITERATOR_NOT_FOUND, SNAPSHOT_NOT_FOUND};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Machine-readable category of a failed request, sent as `error_code` in the response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// The requested key does not exist.
+    NotFound,
+    /// A required parameter is missing or malformed.
+    InvalidRequest,
+    /// The request token did not match the server token.
+    Unauthorized,
+    /// The action is not supported by this server.
+    UnknownAction,
+    /// A per-connection resource limit was reached.
+    ResourceLimit,
+    /// The request did not complete within its timeout.
+    Timeout,
+    /// RocksDB or the server failed while executing the request.
+    DbError,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::InvalidRequest => "INVALID_REQUEST",
+            ErrorCode::Unauthorized => "UNAUTHORIZED",
+            ErrorCode::UnknownAction => "UNKNOWN_ACTION",
+            ErrorCode::ResourceLimit => "RESOURCE_LIMIT",
+            ErrorCode::Timeout => "TIMEOUT",
+            ErrorCode::DbError => "DB_ERROR",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Error returned by request handlers: a code plus the message shown to the client.
+#[derive(Debug, Clone)]
+pub struct RequestError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl RequestError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        RequestError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn invalid_request(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidRequest, message)
+    }
+
+    /// Category of the RocksDB status a `DB_ERROR` carries, named like `rust_rocksdb::ErrorKind`,
+    /// e.g. `Busy` for a transaction conflict. `None` when the error did not come from RocksDB.
+    pub fn rocksdb_kind(&self) -> Option<&'static str> {
+        if self.code != ErrorCode::DbError {
+            return None;
+        }
+        // Manager errors are the RocksDB message, possibly after context such as "Failed to put data: ".
+        without_context(&self.message).find_map(|candidate| {
+            ROCKSDB_STATUSES
+                .iter()
+                .find(|(status, _)| {
+                    candidate
+                        .strip_prefix(status)
+                        .is_some_and(|rest| rest.starts_with(':'))
+                })
+                .map(|(_, kind)| *kind)
+        })
+    }
+}
+
+/// Prefixes RocksDB puts on status messages, and the `rust_rocksdb::ErrorKind` each stands for.
+const ROCKSDB_STATUSES: &[(&str, &str)] = &[
+    ("NotFound", "NotFound"),
+    ("Corruption", "Corruption"),
+    ("Not implemented", "NotSupported"),
+    ("Invalid argument", "InvalidArgument"),
+    ("IO error", "IOError"),
+    ("Merge in progress", "MergeInProgress"),
+    ("Result incomplete", "Incomplete"),
+    ("Shutdown in progress", "ShutdownInProgress"),
+    ("Operation timed out", "TimedOut"),
+    ("Operation aborted", "Aborted"),
+    ("Resource busy", "Busy"),
+    ("Operation expired", "Expired"),
+    ("Operation failed. Try again.", "TryAgain"),
+    ("Compaction too large", "CompactionTooLarge"),
+    ("Column family dropped", "ColumnFamilyDropped"),
+];
+
+/// Manager errors meaning that a named resource does not exist rather than that the database failed.
+const NOT_FOUND_MESSAGES: &[&str] = &[CF_NOT_FOUND, ITERATOR_NOT_FOUND, SNAPSHOT_NOT_FOUND];
+
+/// `message` followed by what remains after each `": "`, i.e. with leading context stripped step by step.
+fn without_context(message: &str) -> impl Iterator<Item = &str> {
+    std::iter::once(message).chain(message.match_indices(": ").map(move |(at, _)| &message[at + 2..]))
+}
+
+// Errors from `RocksDBManager` are plain strings; they count as database failures unless they report a
+// missing column family, iterator or snapshot.
+impl From<String> for RequestError {
+    fn from(message: String) -> Self {
+        let code = if without_context(&message).any(|candidate| NOT_FOUND_MESSAGES.contains(&candidate)) {
+            ErrorCode::NotFound
+        } else {
+            ErrorCode::DbError
+        };
+        Self::new(code, message)
+    }
+}
+
+impl From<&str> for RequestError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
@@ -4,10 +4,15 @@ use async_std::path::PathBuf;
 use async_std::sync::{Arc, Mutex};
 use async_std::task;
 use log::LevelFilter;
+use serde::Deserialize;
+use socket2::{Domain, Protocol, Socket, Type};
 use std::fs;
+use std::net::{TcpListener, ToSocketAddrs};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LogLevel {
     Debug,
     Info,
@@ -29,9 +34,9 @@ impl FromStr for LogLevel {
     }
 }
 
-impl Into<LevelFilter> for LogLevel {
-    fn into(self) -> LevelFilter {
-        match self {
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
             LogLevel::Debug => LevelFilter::Debug,
             LogLevel::Info => LevelFilter::Info,
             LogLevel::Warn => LevelFilter::Warn,
@@ -87,3 +92,179 @@ impl Drop for LockFileGuard {
 pub async fn create_lock_guard(lock_file_path: PathBuf) -> Option<LockFileGuard> {
     LockFileGuard::new(lock_file_path).await.ok()
 }
+
+// Binds the listening socket. IPv6 addresses accept IPv4 clients too (dual-stack) unless
+// `ipv6_only` is set; the OS default differs between platforms, so it is always set explicitly.
+pub fn bind_listener(address: &str, ipv6_only: bool) -> Result<TcpListener, String> {
+    let addr = address
+        .to_socket_addrs()
+        .map_err(|e| format!("Invalid bind address {}: {}", address, e))?
+        .next()
+        .ok_or_else(|| format!("Invalid bind address {}", address))?;
+
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))
+        .map_err(|e| format!("Failed to create socket for {}: {}", addr, e))?;
+    if addr.is_ipv6() {
+        socket
+            .set_only_v6(ipv6_only)
+            .map_err(|e| format!("Failed to configure IPv6 socket for {}: {}", addr, e))?;
+    }
+    #[cfg(unix)]
+    socket
+        .set_reuse_address(true)
+        .map_err(|e| format!("Failed to configure socket for {}: {}", addr, e))?;
+
+    socket
+        .bind(&addr.into())
+        .map_err(|e| format!("Failed to bind {}: {}", addr, e))?;
+    socket
+        .listen(1024)
+        .map_err(|e| format!("Failed to listen on {}: {}", addr, e))?;
+
+    Ok(socket.into())
+}
+
+/// Settings read from the `--config` file at startup and again on SIGHUP. Everything else is
+/// restart-only; fields left out of the file keep their current value.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReloadableConfig {
+    pub log_level: Option<LogLevel>,
+    pub cache_ttl: Option<u64>,
+    pub max_connections: Option<usize>,
+}
+
+impl ReloadableConfig {
+    pub fn load(path: &std::path::Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&text).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+    }
+}
+
+/// Caps the number of concurrent client connections; `0` means no limit.
+#[derive(Debug)]
+pub struct ConnectionLimiter {
+    max: AtomicUsize,
+    active: Arc<AtomicUsize>,
+}
+
+/// An accepted connection's place under the limit, released on drop.
+pub struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionLimiter {
+    pub fn new(max: usize) -> Self {
+        ConnectionLimiter {
+            max: AtomicUsize::new(max),
+            active: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn max(&self) -> usize {
+        self.max.load(Ordering::Relaxed)
+    }
+
+    /// Lowering the limit never closes open connections; new ones are refused until enough have closed.
+    pub fn set_max(&self, max: usize) {
+        self.max.store(max, Ordering::Relaxed);
+    }
+
+    pub fn acquire(&self) -> Option<ConnectionSlot> {
+        let max = self.max();
+        let active = self.active.fetch_add(1, Ordering::AcqRel);
+        if max > 0 && active >= max {
+            self.active.fetch_sub(1, Ordering::AcqRel);
+            return None;
+        }
+        Some(ConnectionSlot(self.active.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Request line of an HTTP request sent to the health check or metrics endpoint.
+#[derive(Debug)]
+pub struct HttpRequestLine {
+    pub method: String,
+    /// Request target without query string or fragment.
+    pub path: String,
+    query: String,
+    version: String,
+}
+
+impl HttpRequestLine {
+    /// Parses `METHOD target HTTP/x.y`; anything else, such as a JSON request, yields `None`.
+    pub fn parse(line: &[u8]) -> Option<Self> {
+        let line = std::str::from_utf8(line).ok()?;
+        let mut parts = line.split_whitespace();
+        let (method, target, version) = (parts.next()?, parts.next()?, parts.next()?);
+
+        let is_method = !method.is_empty() && method.bytes().all(|b| b.is_ascii_uppercase());
+        if parts.next().is_some() || !is_method || !version.starts_with("HTTP/") {
+            return None;
+        }
+
+        let target = target.split('#').next().unwrap_or(target);
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        Some(HttpRequestLine {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query.to_string(),
+            version: version.to_string(),
+        })
+    }
+
+    /// Value of a query string parameter, taken verbatim without percent-decoding.
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query
+            .split('&')
+            .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    }
+
+    /// HTTP version to answer with: HTTP/1.0 clients get HTTP/1.0, everyone else HTTP/1.1.
+    pub fn response_version(&self) -> &'static str {
+        if self.version == "HTTP/1.0" {
+            "HTTP/1.0"
+        } else {
+            "HTTP/1.1"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HttpRequestLine;
+
+    #[test]
+    fn parses_path_and_query() {
+        let line = HttpRequestLine::parse(b"GET /metrics?foo=bar HTTP/1.1").unwrap();
+        assert_eq!(line.method, "GET");
+        assert_eq!(line.path, "/metrics");
+        assert_eq!(line.query_param("foo"), Some("bar"));
+        assert_eq!(line.query_param("missing"), None);
+        assert_eq!(line.response_version(), "HTTP/1.1");
+    }
+
+    #[test]
+    fn answers_http_1_0_with_http_1_0() {
+        let line = HttpRequestLine::parse(b"GET /health HTTP/1.0").unwrap();
+        assert_eq!(line.path, "/health");
+        assert_eq!(line.response_version(), "HTTP/1.0");
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert!(HttpRequestLine::parse(b"GET /metrics").is_none());
+        assert!(HttpRequestLine::parse(b"get /metrics HTTP/1.1").is_none());
+        assert!(HttpRequestLine::parse(b"GET /metrics HTTP/1.1 extra").is_none());
+        assert!(HttpRequestLine::parse(b"GET /metrics FTP/1.0").is_none());
+        assert!(HttpRequestLine::parse(br#"{"action":"ping"}"#).is_none());
+        assert!(HttpRequestLine::parse(b"GET /\xff HTTP/1.1").is_none());
+    }
+}
@@ -0,0 +1,169 @@
+use crate::error::{ErrorCode, RequestError};
+use async_std::channel::{bounded, Receiver, Sender};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Most idempotency keys remembered per database; new keys are refused beyond it.
+const MAX_KEYS: usize = 100_000;
+
+/// The parts of a successful response replayed for a repeated idempotency key.
+#[derive(Debug, Clone)]
+pub(crate) struct Outcome {
+    pub result: Option<Value>,
+    pub hint: Option<String>,
+}
+
+enum State {
+    /// The first attempt is still executing; the receiver closes when it ends.
+    Running(Receiver<()>),
+    Done(Outcome, Instant),
+}
+
+struct Entry {
+    action: String,
+    state: State,
+}
+
+#[derive(Default)]
+struct Keys {
+    entries: HashMap<String, Entry>,
+    /// Finished keys by expiry; results are remembered for a fixed time, so this is also insertion order.
+    expiries: VecDeque<(Instant, String)>,
+}
+
+impl Keys {
+    fn purge(&mut self, now: Instant) {
+        while let Some((expires_at, _)) = self.expiries.front() {
+            if *expires_at > now {
+                break;
+            }
+            let (expires_at, key) = self.expiries.pop_front().unwrap();
+            // The key may have expired and been reused since this expiry was queued.
+            if matches!(self.entries.get(&key), Some(Entry { state: State::Done(_, at), .. }) if *at == expires_at) {
+                self.entries.remove(&key);
+            }
+        }
+    }
+}
+
+/// Results of requests sent with `idempotency_key`, remembered so that a retry returns the first result
+/// instead of executing again.
+pub(crate) struct IdempotencyKeys {
+    ttl: Duration,
+    keys: Mutex<Keys>,
+}
+
+/// What to do with a request carrying an idempotency key.
+pub(crate) enum Claim<'a> {
+    /// Execute it, then record the outcome with `ClaimGuard::finish`.
+    Execute(ClaimGuard<'a>),
+    /// An earlier attempt already succeeded with this outcome.
+    Replay(Outcome),
+}
+
+/// Marks a key as running until the request finishes; dropping it unfinished forgets the key.
+pub(crate) struct ClaimGuard<'a> {
+    keys: &'a IdempotencyKeys,
+    key: String,
+    finished: bool,
+    // Dropped after the outcome is recorded, which wakes attempts waiting for this one.
+    _running: Sender<()>,
+}
+
+impl IdempotencyKeys {
+    /// Remembers results for `ttl`; a zero `ttl` only holds up retries while the first attempt runs.
+    pub(crate) fn new(ttl: Duration) -> Self {
+        IdempotencyKeys {
+            ttl,
+            keys: Mutex::new(Keys::default()),
+        }
+    }
+
+    pub(crate) fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Claims `key` for `action`, waiting while an earlier attempt with the same key is still running.
+    pub(crate) async fn claim(&self, key: &str, action: &str) -> Result<Claim<'_>, RequestError> {
+        loop {
+            let running = {
+                let mut keys = self
+                    .keys
+                    .lock()
+                    .map_err(|_| RequestError::from("Failed to acquire idempotency keys lock"))?;
+                keys.purge(Instant::now());
+
+                match keys.entries.get(key) {
+                    Some(entry) if entry.action != action => {
+                        return Err(RequestError::invalid_request(format!(
+                            "Idempotency key was already used for {}",
+                            entry.action
+                        )));
+                    }
+                    Some(Entry { state: State::Done(outcome, _), .. }) => return Ok(Claim::Replay(outcome.clone())),
+                    Some(Entry { state: State::Running(running), .. }) => running.clone(),
+                    None => {
+                        if keys.entries.len() >= MAX_KEYS {
+                            return Err(RequestError::new(
+                                ErrorCode::ResourceLimit,
+                                format!("Too many idempotency keys, at most {} are remembered", MAX_KEYS),
+                            ));
+                        }
+                        let (sender, receiver) = bounded(1);
+                        keys.entries.insert(
+                            key.to_string(),
+                            Entry {
+                                action: action.to_string(),
+                                state: State::Running(receiver),
+                            },
+                        );
+                        return Ok(Claim::Execute(ClaimGuard {
+                            keys: self,
+                            key: key.to_string(),
+                            finished: false,
+                            _running: sender,
+                        }));
+                    }
+                }
+            };
+
+            // Nothing is ever sent: the channel closes once the running attempt ends.
+            let _ = running.recv().await;
+        }
+    }
+}
+
+impl ClaimGuard<'_> {
+    /// Records a successful outcome for replay; `None` forgets the key, so a retry executes again.
+    pub(crate) fn finish(mut self, outcome: Option<Outcome>) {
+        self.finished = true;
+        let Ok(mut keys) = self.keys.keys.lock() else {
+            return;
+        };
+
+        match outcome {
+            Some(outcome) if !self.keys.ttl.is_zero() => {
+                let expires_at = Instant::now() + self.keys.ttl;
+                if let Some(entry) = keys.entries.get_mut(&self.key) {
+                    entry.state = State::Done(outcome, expires_at);
+                }
+                keys.expiries.push_back((expires_at, self.key.clone()));
+            }
+            _ => {
+                keys.entries.remove(&self.key);
+            }
+        }
+    }
+}
+
+impl Drop for ClaimGuard<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            if let Ok(mut keys) = self.keys.keys.lock() {
+                keys.entries.remove(&self.key);
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use async_std::sync::{Mutex as AsyncMutex, MutexGuard as AsyncMutexGuard};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, MutexGuard};
+
+const STRIPES: usize = 64;
+
+/// Striped locks that serialize read-modify-write operations touching the same keys.
+///
+/// Keys hash onto a fixed set of mutexes, so unrelated keys rarely contend and memory use
+/// does not grow with the key space.
+pub struct KeyLocks {
+    stripes: Vec<Mutex<()>>,
+}
+
+impl KeyLocks {
+    pub fn new() -> Self {
+        KeyLocks {
+            stripes: (0..STRIPES).map(|_| Mutex::new(())).collect(),
+        }
+    }
+
+    /// Locks every key in `keys`. Stripes are taken in ascending order so that callers
+    /// locking overlapping key sets cannot deadlock.
+    pub fn lock(&self, cf_name: Option<&str>, keys: &[&str]) -> Result<Vec<MutexGuard<'_, ()>>, String> {
+        let entries: Vec<(Option<&str>, &str)> = keys.iter().map(|key| (cf_name, *key)).collect();
+        self.lock_entries(&entries)
+    }
+
+    /// Locks keys that may be in different column families, in the same order as `lock`.
+    pub fn lock_entries(&self, entries: &[(Option<&str>, &str)]) -> Result<Vec<MutexGuard<'_, ()>>, String> {
+        stripes(entries)
+            .into_iter()
+            .map(|stripe| {
+                self.stripes[stripe]
+                    .lock()
+                    .map_err(|_| "Failed to acquire key lock".to_string())
+            })
+            .collect()
+    }
+}
+
+impl Default for KeyLocks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The async counterpart of `KeyLocks`, for guards held across `.await`.
+pub struct AsyncKeyLocks {
+    stripes: Vec<AsyncMutex<()>>,
+}
+
+impl AsyncKeyLocks {
+    pub fn new() -> Self {
+        AsyncKeyLocks {
+            stripes: (0..STRIPES).map(|_| AsyncMutex::new(())).collect(),
+        }
+    }
+
+    /// Locks keys that may be in different column families, in the same order as `KeyLocks`.
+    pub async fn lock_entries(&self, entries: &[(Option<&str>, &str)]) -> Vec<AsyncMutexGuard<'_, ()>> {
+        let mut guards = Vec::new();
+        for stripe in stripes(entries) {
+            guards.push(self.stripes[stripe].lock().await);
+        }
+        guards
+    }
+}
+
+impl Default for AsyncKeyLocks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn stripe(cf_name: Option<&str>, key: &str) -> usize {
+    let mut hasher = DefaultHasher::new();
+    cf_name.hash(&mut hasher);
+    key.hash(&mut hasher);
+    (hasher.finish() as usize) % STRIPES
+}
+
+/// The distinct stripes of `entries` in ascending order.
+fn stripes(entries: &[(Option<&str>, &str)]) -> Vec<usize> {
+    let mut stripes: Vec<usize> = entries
+        .iter()
+        .map(|(cf_name, key)| stripe(*cf_name, key))
+        .collect();
+    stripes.sort_unstable();
+    stripes.dedup();
+    stripes
+}
@@ -1,10 +1,19 @@
+mod backup_stream;
 mod cache;
+pub mod databases;
 pub mod db_manager;
+pub mod error;
 mod helpers;
+mod idempotency;
+mod key_locks;
 pub mod server;
+mod waiters;
 mod metrics;
+#[cfg(test)]
+mod test_support;
 
-use async_std::channel::{bounded, Receiver};
+use async_std::channel::{unbounded, Receiver, Sender};
+use base64::prelude::{Engine, BASE64_STANDARD};
 use async_std::io::{prelude::*, BufReader, BufWriter};
 use async_std::net::{TcpListener, TcpStream};
 use async_std::sync::Arc;
@@ -12,14 +21,20 @@ use async_std::task;
 use futures::stream::StreamExt;
 use futures::FutureExt;
 use log::{error, info, warn};
+#[cfg(unix)]
+use nix::sys::signal::{SigSet, Signal};
 use std::env;
-use std::path::PathBuf;
-use std::time::{Instant};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use structopt::StructOpt;
 
-use crate::helpers::{create_lock_guard, LogLevel};
-use crate::metrics::{METRICS, Metrics};
-use crate::server::{Request, RocksDBServer};
+use crate::databases::{Databases, Session};
+use crate::db_manager::{CompactionConfig, CompactionStyle, DbConfig, DbPath};
+use crate::helpers::{
+    bind_listener, create_lock_guard, ConnectionLimiter, HttpRequestLine, LogLevel, ReloadableConfig,
+};
+use crate::metrics::{METRICS, Metrics, MetricsFormat};
+use crate::server::{action_label, ConnectionLimits, Request, ServerConfig};
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "RocksDB Server", about = "A simple RocksDB server.")]
@@ -37,10 +52,17 @@ struct Opt {
         long,
         env = "ROCKSDB_ADDRESS",
         default_value = "127.0.0.1:12345",
-        help = "Bind address"
+        help = "Bind address, e.g. 127.0.0.1:12345 or [::]:12345"
     )]
     address: String,
 
+    #[structopt(
+        long,
+        env = "ROCKSDB_IPV6_ONLY",
+        help = "Accept only IPv6 clients on an IPv6 bind address (dual-stack otherwise)"
+    )]
+    ipv6_only: bool,
+
     #[structopt(
         long,
         env = "ROCKSDB_TTL",
@@ -56,6 +78,20 @@ struct Opt {
     )]
     token: Option<String>,
 
+    #[structopt(
+        long,
+        env = "ROCKSDB_ADMIN_ADDRESS",
+        help = "Second bind address, the only one accepting admin actions such as truncate and restore"
+    )]
+    admin_address: Option<String>,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_ADMIN_TOKEN",
+        help = "Authentication token for the admin address (--token if unset)"
+    )]
+    admin_token: Option<String>,
+
     #[structopt(
         long,
         env = "ROCKSDB_LOCK_FILE",
@@ -85,16 +121,212 @@ struct Opt {
     )]
     metrics: bool,
 
+    #[structopt(
+        long,
+        env = "ROCKSDB_METRICS_TOKEN",
+        help = "Require this token on the metrics endpoint, as a bearer token or the basic auth password"
+    )]
+    metrics_token: Option<String>,
+
     #[structopt(
         long,
         env = "ROCKSDB_HEALTH_CHECK",
         help = "Enable health check endpoint"
     )]
     health_check: bool,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_WARM_CACHE",
+        help = "Read the database into the block cache after startup; /health reports warming until done"
+    )]
+    warm_cache: bool,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_WARM_CACHE_PREFIXES",
+        use_delimiter = true,
+        help = "Comma-separated key prefixes --warm-cache reads instead of every key"
+    )]
+    warm_cache_prefixes: Vec<String>,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_PARALLELISM",
+        help = "Number of background threads used by RocksDB (defaults to the CPUs available to the process, honoring cgroup CPU limits)"
+    )]
+    parallelism: Option<i32>,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_ATOMIC_FLUSH",
+        help = "Flush all column families atomically for cross-CF crash consistency"
+    )]
+    atomic_flush: bool,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_PIPELINED_WRITE",
+        help = "Pipeline WAL and memtable writes for higher throughput under concurrent writes"
+    )]
+    pipelined_write: bool,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_RATE_LIMITER_BYTES_PER_SEC",
+        help = "Limit flush and compaction I/O to this many bytes per second (unlimited by default)"
+    )]
+    rate_limiter_bytes_per_sec: Option<i64>,
+
+    #[structopt(
+        long,
+        possible_values = &CompactionStyle::variants(),
+        case_insensitive = true,
+        env = "ROCKSDB_COMPACTION_STYLE",
+        default_value = "level",
+        help = "Compaction style of every column family"
+    )]
+    compaction_style: CompactionStyle,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_NUM_LEVELS",
+        help = "Number of LSM levels (RocksDB's default of 7 if unset)"
+    )]
+    num_levels: Option<i32>,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_FIFO_MAX_SIZE",
+        help = "Total SST size in bytes after which FIFO compaction drops the oldest files (requires --compaction-style fifo)"
+    )]
+    fifo_max_size: Option<u64>,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_BACKUP_PATH",
+        help = "Directory for backups, preferably on another disk (backup inside the database path if unset)"
+    )]
+    backup_path: Option<String>,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_WAL_DIR",
+        help = "Directory for the write-ahead log, e.g. on a faster disk (the database path if unset)"
+    )]
+    wal_dir: Option<String>,
+
+    #[structopt(
+        long = "db-path",
+        env = "ROCKSDB_DB_PATHS",
+        use_delimiter = true,
+        number_of_values = 1,
+        help = "Directory for SST files as PATH:TARGET_BYTES; repeat to fill several in order (the database path if unset)"
+    )]
+    db_paths: Vec<DbPath>,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_MAX_ITERATORS_PER_CONNECTION",
+        default_value = "1024",
+        help = "Maximum number of open iterators per connection (0 for no limit)"
+    )]
+    max_iterators_per_connection: usize,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_MAX_SNAPSHOTS_PER_CONNECTION",
+        default_value = "64",
+        help = "Maximum number of unreleased snapshots created by one connection (0 for no limit)"
+    )]
+    max_snapshots_per_connection: usize,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_IDEMPOTENCY_TTL",
+        default_value = "300",
+        help = "Seconds the result of a request with an idempotency_key is remembered for retries"
+    )]
+    idempotency_ttl: u64,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_MAX_KEY_SIZE",
+        default_value = "65536",
+        help = "Maximum key size in bytes accepted by writes (0 for no limit)"
+    )]
+    max_key_size: usize,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_MAX_CONNECTIONS",
+        default_value = "0",
+        help = "Maximum number of concurrent client connections (0 for no limit)"
+    )]
+    max_connections: usize,
+
+    #[structopt(
+        long,
+        env = "ROCKSDB_CONFIG",
+        parse(from_os_str),
+        help = "JSON file with log_level, cache_ttl and max_connections, re-read on SIGHUP"
+    )]
+    config: Option<PathBuf>,
+}
+
+/// What a signal asks the server to do.
+enum ControlSignal {
+    Shutdown,
+    Reload,
 }
 
-#[async_std::main]
-async fn main() {
+fn main() {
+    // SIGTERM and SIGHUP are blocked before the runtime starts its threads, so every thread
+    // inherits the mask and only the signal thread below receives them.
+    #[cfg(unix)]
+    let signals = block_signals();
+
+    let (signal_sender, signal_receiver) = unbounded();
+    let ctrlc_sender = signal_sender.clone();
+    ctrlc::set_handler(move || {
+        let _ = ctrlc_sender.try_send(ControlSignal::Shutdown);
+    })
+    .expect("Error setting Ctrl-C handler");
+    #[cfg(unix)]
+    spawn_signal_thread(signals, signal_sender);
+
+    task::block_on(run(signal_receiver));
+}
+
+#[cfg(unix)]
+fn block_signals() -> SigSet {
+    let mut signals = SigSet::empty();
+    signals.add(Signal::SIGTERM);
+    signals.add(Signal::SIGHUP);
+    signals
+        .thread_block()
+        .expect("Error blocking SIGTERM and SIGHUP");
+    signals
+}
+
+#[cfg(unix)]
+fn spawn_signal_thread(signals: SigSet, sender: Sender<ControlSignal>) {
+    std::thread::spawn(move || loop {
+        let signal = match signals.wait() {
+            Ok(Signal::SIGHUP) => ControlSignal::Reload,
+            Ok(_) => ControlSignal::Shutdown,
+            Err(e) => {
+                error!("Failed to wait for signals: {}", e);
+                return;
+            }
+        };
+        if sender.try_send(signal).is_err() {
+            return;
+        }
+    });
+}
+
+async fn run(signal_receiver: Receiver<ControlSignal>) {
     let opt = Opt::from_args();
 
     let dbpath = if opt.dbpath.starts_with(".") {
@@ -105,52 +337,138 @@ async fn main() {
     let dbpath = dbpath.to_str().unwrap().to_string();
 
     let address = opt.address;
-    let ttl = opt.ttl;
-    let token = opt.token;
-    let cache = opt.cache;
-    let cache_ttl = opt.cache_ttl;
-
-    let lock_guard = if let Some(lock_file_path) = opt.lock_file {
-        Some(create_lock_guard(lock_file_path.into()).await.unwrap())
-    } else {
-        None
-    };
-
-    let log_level: log::LevelFilter = opt.log_level.into();
 
+    // The logger lets everything through; `log::set_max_level` filters, so a reload can change it.
     env_logger::Builder::new()
-        .filter(None, log_level)
+        .filter(None, log::LevelFilter::Debug)
         .target(env_logger::Target::Stdout)
         .init();
 
+    let file_config = match opt.config.as_deref().map(ReloadableConfig::load).transpose() {
+        Ok(file_config) => file_config.unwrap_or_default(),
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    log::set_max_level(file_config.log_level.unwrap_or(opt.log_level).into());
+
+    let limiter = Arc::new(ConnectionLimiter::new(
+        file_config.max_connections.unwrap_or(opt.max_connections),
+    ));
+
+    let config = ServerConfig {
+        ttl_secs: opt.ttl,
+        auth_token: opt.token,
+        cache_ttl_secs: Some(file_config.cache_ttl.unwrap_or(opt.cache_ttl)),
+        cache_enabled: opt.cache,
+        db_config: DbConfig {
+            parallelism: opt.parallelism,
+            atomic_flush: opt.atomic_flush,
+            pipelined_write: opt.pipelined_write,
+            rate_limiter_bytes_per_sec: opt.rate_limiter_bytes_per_sec,
+            compaction: CompactionConfig {
+                style: opt.compaction_style,
+                num_levels: opt.num_levels,
+                fifo_max_size: opt.fifo_max_size,
+            },
+            wal_dir: opt.wal_dir,
+            db_paths: opt.db_paths,
+            backup_path: opt.backup_path,
+        },
+        limits: ConnectionLimits {
+            max_iterators: opt.max_iterators_per_connection,
+            max_snapshots: opt.max_snapshots_per_connection,
+        },
+        idempotency_ttl_secs: opt.idempotency_ttl,
+        max_key_size: opt.max_key_size,
+        admin_port: opt.admin_address.is_some(),
+        admin_token: opt.admin_token,
+    };
 
+    let lock_guard = if let Some(lock_file_path) = opt.lock_file {
+        Some(create_lock_guard(lock_file_path.into()).await.unwrap())
+    } else {
+        None
+    };
 
-    let addr = format!("{}",address);
-    let listener = TcpListener::bind(&addr).await.unwrap();
+    let addr = address;
+    let listener = match bind_listener(&addr, opt.ipv6_only) {
+        Ok(listener) => TcpListener::from(listener),
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let admin_listener = match opt.admin_address.as_deref().map(|addr| bind_listener(addr, opt.ipv6_only)) {
+        Some(Ok(listener)) => Some(TcpListener::from(listener)),
+        Some(Err(e)) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+        None => None,
+    };
+    if config.admin_token.is_some() && opt.admin_address.is_none() {
+        warn!("--admin-token has no effect without --admin-address");
+    }
 
     if opt.metrics {
         METRICS.set_enabled(true);
-        METRICS.observe_request_duration(0.0);
 
         warn!("> Metrics listening on http://{}/metrics", addr);
     }
+    if opt.metrics_token.is_some() && !opt.metrics {
+        warn!("--metrics-token has no effect without --metrics");
+    }
+    if config.db_config.backup_path.is_none() {
+        warn!("Backups go to {}/backup inside the database directory; set --backup-path to keep them elsewhere", dbpath);
+    }
     if opt.health_check {
         warn!("> Health check endpoint listening on http://{}/health", addr); // Добавлен вывод для health_check
     }
 
 
-    let server = Arc::new(RocksDBServer::new(dbpath, ttl, token, Some(cache_ttl), cache).unwrap());
+    let server = match Databases::open(dbpath, config) {
+        Ok(server) => Arc::new(server),
+        Err(e) => {
+            error!("Failed to open database: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if opt.warm_cache {
+        server.start_warming(opt.warm_cache_prefixes);
+    } else if !opt.warm_cache_prefixes.is_empty() {
+        warn!("--warm-cache-prefixes has no effect without --warm-cache");
+    }
 
     warn!("> Server listening on {}", addr);
 
-    let (signal_sender, signal_receiver) = bounded(1);
-    ctrlc::set_handler(move || {
-        let _ = signal_sender.try_send(());
-    })
-    .expect("Error setting Ctrl-C handler");
+    let metrics_token: Option<Arc<str>> = opt.metrics_token.map(Arc::from);
+    if let Some(admin_listener) = admin_listener {
+        warn!("> Admin listening on {}", opt.admin_address.as_deref().unwrap_or_default());
+        // Admin connections do not count against --max-connections, so a flood of clients cannot lock operators out.
+        task::spawn(handle_incoming_connections(
+            admin_listener,
+            true,
+            server.clone(),
+            Arc::new(ConnectionLimiter::new(0)),
+            opt.metrics,
+            metrics_token.clone(),
+            opt.health_check,
+        ));
+    }
 
-    let server_task = task::spawn(handle_incoming_connections(listener, server, opt.metrics, opt.health_check));
-    let signal_task = task::spawn(handle_signals(signal_receiver));
+    let server_task = task::spawn(handle_incoming_connections(
+        listener,
+        false,
+        server.clone(),
+        limiter.clone(),
+        opt.metrics,
+        metrics_token,
+        opt.health_check,
+    ));
+    let signal_task = task::spawn(handle_signals(signal_receiver, opt.config, server, limiter));
 
     futures::select! {
         _ = server_task.fuse() => (),
@@ -162,19 +480,50 @@ async fn main() {
     info!("Server has shut down gracefully");
 }
 
-async fn handle_incoming_connections(listener: TcpListener, server: Arc<RocksDBServer>, metrics: bool, health_check: bool) {
+async fn handle_incoming_connections(
+    listener: TcpListener,
+    admin: bool,
+    server: Arc<Databases>,
+    limiter: Arc<ConnectionLimiter>,
+    metrics: bool,
+    metrics_token: Option<Arc<str>>,
+    health_check: bool,
+) {
     listener
         .incoming()
         // .for_each_concurrent(Some(1000), |stream| { // Limit concurrency to 1000
         .for_each_concurrent(/* limit */ None, |stream| {
             // Limit concurrency to 1000
             let server = server.clone();
+            let limiter = limiter.clone();
+            let metrics_token = metrics_token.clone();
             async move {
                 match stream {
-                    Ok(stream) => {
-                        task::spawn(handle_connection(stream, server, metrics, health_check));
-                    }
+                    Ok(stream) => match limiter.acquire() {
+                        Some(slot) => {
+                            let connection = METRICS.track_connection();
+                            task::spawn(async move {
+                                let _slot = slot;
+                                let _connection = connection;
+                                if let Err(e) =
+                                    handle_connection(stream, admin, server, metrics, metrics_token, health_check).await
+                                {
+                                    METRICS.inc_connection_error("io");
+                                    warn!("Connection closed with an error: {}", e);
+                                }
+                            });
+                        }
+                        None => {
+                            METRICS.inc_connections();
+                            METRICS.inc_connection_error("refused");
+                            warn!(
+                                "Refusing connection: {} connections already open",
+                                limiter.max()
+                            )
+                        }
+                    },
                     Err(e) => {
+                        METRICS.inc_connection_error("accept");
                         error!("Failed to accept connection: {}", e);
                     }
                 }
@@ -183,79 +532,263 @@ async fn handle_incoming_connections(listener: TcpListener, server: Arc<RocksDBS
         .await;
 }
 
-async fn handle_signals(signal_receiver: Receiver<()>) {
-    let _ = signal_receiver.recv().await;
-    info!("Ctrl+C received, shutting down");
+async fn handle_signals(
+    signal_receiver: Receiver<ControlSignal>,
+    config_path: Option<PathBuf>,
+    server: Arc<Databases>,
+    limiter: Arc<ConnectionLimiter>,
+) {
+    while let Ok(signal) = signal_receiver.recv().await {
+        match signal {
+            ControlSignal::Reload => reload_config(config_path.as_deref(), &server, &limiter),
+            ControlSignal::Shutdown => break,
+        }
+    }
+    info!("Shutdown signal received, shutting down");
+}
+
+/// Re-reads `--config` on SIGHUP. A file that fails to load leaves every setting unchanged.
+fn reload_config(path: Option<&Path>, server: &Databases, limiter: &ConnectionLimiter) {
+    let Some(path) = path else {
+        warn!("SIGHUP received without --config, nothing to reload");
+        return;
+    };
+    let config = match ReloadableConfig::load(path) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Keeping the current settings: {}", e);
+            return;
+        }
+    };
+
+    if let Some(level) = config.log_level {
+        log::set_max_level(level.into());
+    }
+    if let Some(cache_ttl) = config.cache_ttl {
+        server.set_cache_ttl(Duration::from_secs(cache_ttl));
+    }
+    if let Some(max_connections) = config.max_connections {
+        limiter.set_max(max_connections);
+    }
+    warn!("Reloaded settings from {}: {:?}", path.display(), config);
 }
 
 async fn handle_connection(
     socket: TcpStream,
-    server: Arc<RocksDBServer>,
+    admin: bool,
+    server: Arc<Databases>,
     metrics: bool,
+    metrics_token: Option<Arc<str>>,
     health_check: bool,
 ) -> async_std::io::Result<()> {
-    let mut buffer = Vec::new();
-    let mut reader = BufReader::new(&socket);
-    let mut writer = BufWriter::new(&socket);
+    let mut session = Session::new(admin);
+    let result = serve_connection(
+        &socket,
+        &server,
+        metrics,
+        metrics_token.as_deref(),
+        health_check,
+        &mut session,
+    )
+    .await;
+    server.close_session(session);
+    result
+}
 
-    while reader.read_until(b'\n', &mut buffer).await? != 0 {
-        let request_str = String::from_utf8_lossy(&buffer);
-        info!("Received request: {}", request_str);
+/// The request headers the HTTP endpoints look at.
+#[derive(Debug, Default)]
+struct HttpHeaders {
+    accept: Option<String>,
+    authorization: Option<String>,
+}
 
-        if buffer.starts_with(b"GET /favicon.ico") {
-            info!("Ignoring /favicon.ico request");
-            return Ok(());
+/// Consumes the HTTP headers after the request line and keeps the ones `HttpHeaders` holds.
+async fn read_http_headers(reader: &mut BufReader<&TcpStream>) -> async_std::io::Result<HttpHeaders> {
+    let mut headers = HttpHeaders::default();
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            break;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
         }
+        if let Some((header_name, header_value)) = header.split_once(':') {
+            let value = Some(header_value.trim().to_string());
+            match header_name.trim().to_ascii_lowercase().as_str() {
+                "accept" => headers.accept = value,
+                "authorization" => headers.authorization = value,
+                _ => {}
+            }
+        }
+    }
 
-        if health_check && buffer.starts_with(b"GET /health ") {
-            let http_response = "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 2\r\n\r\nOK";
+    Ok(headers)
+}
 
-            match writer.write_all(http_response.as_bytes()).await {
-                Ok(_) => info!("Successfully wrote health check response"),
-                Err(e) => error!("Failed to write health check response: {}", e),
-            }
-            if let Err(e) = writer.flush().await {
-                error!("Failed to flush health check response: {}", e);
-            }
-            return Ok(());
+/// Whether an `Authorization` header carries `token`, as `Bearer <token>` or as the password of
+/// `Basic` credentials with any user name.
+fn authorizes(authorization: Option<&str>, token: &str) -> bool {
+    let Some((scheme, credentials)) = authorization.and_then(|header| header.split_once(' ')) else {
+        return false;
+    };
+    let credentials = credentials.trim();
+
+    if scheme.eq_ignore_ascii_case("bearer") {
+        credentials == token
+    } else if scheme.eq_ignore_ascii_case("basic") {
+        BASE64_STANDARD
+            .decode(credentials)
+            .ok()
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+            .is_some_and(|decoded| decoded.split_once(':').is_some_and(|(_, password)| password == token))
+    } else {
+        false
+    }
+}
+
+/// Body of `GET /health`; `status` is `unavailable` while the database is closed for a restore
+/// and `warming` while `--warm-cache` is loading the block cache.
+fn health_status(server: &Databases) -> String {
+    let db_open = server.is_open();
+    let status = if !db_open {
+        "unavailable"
+    } else if server.is_warming() {
+        "warming"
+    } else {
+        "ok"
+    };
+    let uptime_seconds = METRICS
+        .process_start_time
+        .elapsed()
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+
+    serde_json::json!({
+        "status": status,
+        "uptime_seconds": uptime_seconds,
+        "version": env!("CARGO_PKG_VERSION"),
+        "db_open": db_open,
+    })
+    .to_string()
+}
+
+/// Answers the HTTP endpoints by path alone, so query strings and HTTP/1.0 clients are handled too.
+async fn serve_http(
+    writer: &mut BufWriter<&TcpStream>,
+    request_line: &HttpRequestLine,
+    headers: &HttpHeaders,
+    server: &Databases,
+    metrics: bool,
+    metrics_token: Option<&str>,
+    health_check: bool,
+) {
+    let mut extra_headers = "";
+    let (status, content_type, body, endpoint) = match (request_line.method.as_str(), request_line.path.as_str()) {
+        ("GET", "/favicon.ico") => {
+            info!("Ignoring /favicon.ico request");
+            return;
+        }
+        // A load balancer holds traffic back until the block cache is warm.
+        ("GET", "/health") if health_check && server.is_warming() => match request_line.query_param("format") {
+            Some("plain") => ("503 Service Unavailable", "text/plain", "WARMING".to_string(), "health check"),
+            _ => ("503 Service Unavailable", "application/json", health_status(server), "health check"),
+        },
+        ("GET", "/health") if health_check => match request_line.query_param("format") {
+            Some("plain") => ("200 OK", "text/plain", "OK".to_string(), "health check"),
+            _ => ("200 OK", "application/json", health_status(server), "health check"),
+        },
+        ("GET", "/metrics") if metrics && metrics_token.is_some_and(|token| !authorizes(headers.authorization.as_deref(), token)) => {
+            warn!("Rejecting unauthorized metrics request");
+            extra_headers = "WWW-Authenticate: Basic realm=\"metrics\"\r\n";
+            ("401 Unauthorized", "text/plain", "Unauthorized".to_string(), "metrics")
         }
+        ("GET", "/metrics") if metrics => {
+            let format = MetricsFormat::from_accept(headers.accept.as_deref());
 
-        if metrics && buffer.starts_with(b"GET /metrics ") {
             METRICS.update_system_metrics();
+            server.update_write_health_metrics();
 
-            let response = Metrics::gather_metrics();
-            let http_response = format!(
-                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}",
-                response.len(),
-                response
-            );
+            ("200 OK", format.content_type(), Metrics::gather_metrics(format), "metrics")
+        }
+        _ => ("404 Not Found", "text/plain", "Not Found".to_string(), "not found"),
+    };
 
-            match writer.write_all(http_response.as_bytes()).await {
-                Ok(_) => info!("Successfully wrote metrics response"),
-                Err(e) => error!("Failed to write metrics response: {}", e),
-            }
-            if let Err(e) = writer.flush().await {
-                error!("Failed to flush metrics response: {}", e);
-            }
+    let http_response = format!(
+        "{} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}\r\n{}",
+        request_line.response_version(),
+        status,
+        content_type,
+        body.len(),
+        extra_headers,
+        body
+    );
+
+    match writer.write_all(http_response.as_bytes()).await {
+        Ok(_) => info!("Successfully wrote {} response", endpoint),
+        Err(e) => error!("Failed to write {} response: {}", endpoint, e),
+    }
+    if let Err(e) = writer.flush().await {
+        error!("Failed to flush {} response: {}", endpoint, e);
+    }
+}
+
+async fn serve_connection(
+    socket: &TcpStream,
+    server: &Databases,
+    metrics: bool,
+    metrics_token: Option<&str>,
+    health_check: bool,
+    session: &mut Session,
+) -> async_std::io::Result<()> {
+    let mut buffer = Vec::new();
+    let mut reader = BufReader::new(socket);
+    let mut writer = BufWriter::new(socket);
+
+    while reader.read_until(b'\n', &mut buffer).await? != 0 {
+        let request_str = String::from_utf8_lossy(&buffer);
+        info!("Received request: {}", request_str);
+
+        if let Some(request_line) = HttpRequestLine::parse(&buffer) {
+            let headers = read_http_headers(&mut reader).await?;
+            serve_http(&mut writer, &request_line, &headers, server, metrics, metrics_token, health_check).await;
             return Ok(());
         }
 
-
         let start = Instant::now();
-        METRICS.inc_active_requests();
-        METRICS.inc_requests();
+        let _active = METRICS.track_request();
+
+        let request = serde_json::from_slice::<Request>(&buffer);
+        let action = match &request {
+            Ok(request) => action_label(&request.action),
+            Err(_) => "unknown",
+        };
+        METRICS.inc_requests(action);
 
-        match serde_json::from_slice::<Request>(&buffer) {
+        match request {
             Ok(request) => {
-                let response = server.handle_request(request.clone()).await;
+                let mut response = if request.action == "import_backup_stream" {
+                    server.handle_import_backup_stream(request.clone(), session, &mut reader).await
+                } else {
+                    server.handle_request(request.clone(), session).await
+                };
+                let binary = response.binary.take().unwrap_or_default();
+                let stream = response.stream.take();
                 let response = match serde_json::to_vec(&response) {
-                    Ok(data) => {
-                        let response_size = data.len() as u64;  // Размер ответа в байтах
+                    Ok(mut data) => {
+                        let stream_size = stream.as_ref().map_or(0, |stream| stream.len());
+                        let response_size = (data.len() + binary.len()) as u64 + stream_size;  // Размер ответа в байтах
                         METRICS.inc_response_speed_bytes(response_size);  // Наблюдаем за размером ответа
+                        // A binary payload follows the response line; `binary_length` gives its size.
+                        data.push(b'\n');
+                        data.extend_from_slice(&binary);
                         data
                     },
                     Err(e) => {
-                        METRICS.inc_request_failure();
+                        METRICS.inc_request_failure(action);
                         error!(
                             "Failed to serialize response: {} request {:?}",
                             e,
@@ -266,30 +799,31 @@ async fn handle_connection(
                 };
 
                 if writer.write_all(&response).await.is_err() {
-                    METRICS.inc_request_failure();
+                    METRICS.inc_request_failure(action);
                     error!("Failed to write to socket");
                     break;
                 }
-                if writer.write_all(b"\n").await.is_err() {
-                    METRICS.inc_request_failure();
-                    error!("Failed to write to socket");
-                    break;
+                if let Some(stream) = stream {
+                    if let Err(e) = stream.write_to(&mut writer).await {
+                        METRICS.inc_request_failure(action);
+                        error!("Failed to stream backup: {}", e);
+                        break;
+                    }
                 }
                 if writer.flush().await.is_err() {
-                    METRICS.inc_request_failure();
+                    METRICS.inc_request_failure(action);
                     error!("Failed to flush socket");
                     break;
                 }
 
-                METRICS.inc_request_success();
+                METRICS.inc_request_success(action);
             }
             Err(e) => {
                 error!("Failed to parse request: {} - {:?}", e, &buffer);
             }
         }
 
-        METRICS.observe_request_duration(start.elapsed().as_secs_f64());
-        METRICS.dec_active_requests();
+        METRICS.observe_request_duration(action, start.elapsed().as_secs_f64());
         buffer.clear();
     }
 
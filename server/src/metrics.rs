@@ -1,46 +1,125 @@
+use std::collections::HashSet;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::SystemTime;
-use prometheus::{Encoder, TextEncoder, register_histogram, Histogram, register_int_counter, IntCounter, register_int_gauge, IntGauge, Gauge, register_gauge};
+use prometheus::proto::MetricType;
+use prometheus::{Encoder, TextEncoder, register_histogram_vec, HistogramVec, register_int_counter, IntCounter, register_int_counter_vec, IntCounterVec, register_int_gauge, IntGauge, register_int_gauge_vec, IntGaugeVec, Gauge, register_gauge};
 use once_cell::sync::Lazy;
 use log::{info, error};
 use sysinfo::{Pid, System};
 
+/// Exposition format of the `/metrics` endpoint, chosen from the scraper's `Accept` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricsFormat {
+    /// Prometheus text format 0.0.4.
+    Text,
+    /// OpenMetrics 1.0.0 text format.
+    OpenMetrics,
+}
+
+impl MetricsFormat {
+    pub fn from_accept(accept: Option<&str>) -> Self {
+        let wants_openmetrics = accept.is_some_and(|accept| {
+            accept
+                .split(',')
+                .any(|media_range| media_range.trim().starts_with("application/openmetrics-text"))
+        });
+
+        if wants_openmetrics {
+            MetricsFormat::OpenMetrics
+        } else {
+            MetricsFormat::Text
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            MetricsFormat::Text => "text/plain; version=0.0.4",
+            MetricsFormat::OpenMetrics => "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        }
+    }
+}
+
+// OpenMetrics names a counter family without `_total` and requires the suffix on its samples;
+// counters that lack the suffix can only be exposed as `unknown`.
+fn to_openmetrics(text: &str, counters: &HashSet<String>) -> String {
+    let mut output = String::with_capacity(text.len() + 6);
+
+    for line in text.lines() {
+        let mut parts = line.splitn(4, ' ');
+        let (Some("#"), Some(kind @ ("HELP" | "TYPE")), Some(name)) = (parts.next(), parts.next(), parts.next()) else {
+            output.push_str(line);
+            output.push('\n');
+            continue;
+        };
+        let rest = parts.next().unwrap_or("");
+
+        if !counters.contains(name) {
+            output.push_str(line);
+        } else if let Some(family) = name.strip_suffix("_total") {
+            output.push_str(&format!("# {} {} {}", kind, family, rest));
+        } else if kind == "TYPE" {
+            output.push_str(&format!("# TYPE {} unknown", name));
+        } else {
+            output.push_str(line);
+        }
+        output.push('\n');
+    }
+
+    output.push_str("# EOF\n");
+    output
+}
+
 pub struct Metrics {
     pub enabled: AtomicBool,
-    pub requests: IntCounter,
-    pub request_duration: Histogram,
+    pub requests: IntCounterVec,
+    pub request_duration: HistogramVec,
     pub response_speed_bytes: IntCounter,
     pub cache_hits: IntCounter,
     pub cache_set: IntCounter,
     pub cache_misses: IntCounter,
-    pub active_connections: IntGauge,
+    pub active_requests: IntGauge,
+    pub connections: IntCounter,
+    pub connections_active: IntGauge,
+    pub connection_errors: IntCounterVec,
     pub memory_usage: Gauge,
     pub cpu_usage: Gauge,
     pub uptime: Gauge,
     pub process_start_time: SystemTime,
-    pub request_success: IntCounter,
-    pub request_failure: IntCounter,
+    pub request_success: IntCounterVec,
+    pub request_failure: IntCounterVec,
+    pub request_errors: IntCounterVec,
+    pub write_stopped: IntGaugeVec,
+    pub delayed_write_rate: IntGaugeVec,
 }
 
 impl Metrics {
     pub fn new(enabled: bool) -> Self {
-        let metrics = Self {
+        Self {
             enabled: AtomicBool::new(enabled),
-            requests: register_int_counter!(
+            requests: register_int_counter_vec!(
                 "requests",
-                "The number of requests"
+                "The number of requests",
+                &["action"]
             ).unwrap(),
-            request_success: register_int_counter!(
+            request_success: register_int_counter_vec!(
                 "request_success_total",
-                "Total number of successful requests"
+                "Total number of successful requests",
+                &["action"]
             ).unwrap(),
-            request_failure: register_int_counter!(
+            request_failure: register_int_counter_vec!(
                 "request_failure_total",
-                "Total number of failed requests"
+                "Total number of failed requests",
+                &["action"]
             ).unwrap(),
-            request_duration: register_histogram!(
+            request_errors: register_int_counter_vec!(
+                "request_errors_total",
+                "Total number of requests answered with an error, by error code",
+                &["code"]
+            ).unwrap(),
+            request_duration: register_histogram_vec!(
                 "request_duration_seconds",
-                "The duration of the request in seconds"
+                "The duration of the request in seconds",
+                &["action"]
             ).unwrap(),
             response_speed_bytes: register_int_counter!(
                 "response_speed_bytes",
@@ -58,9 +137,22 @@ impl Metrics {
                 "cache_misses_total",
                 "The total number of cache misses"
             ).unwrap(),
-            active_connections: register_int_gauge!(
-                "active_connections",
-                "The number of active connections"
+            active_requests: register_int_gauge!(
+                "active_requests",
+                "The number of requests being executed"
+            ).unwrap(),
+            connections: register_int_counter!(
+                "connections_total",
+                "Total number of accepted connections"
+            ).unwrap(),
+            connections_active: register_int_gauge!(
+                "connections_active",
+                "The number of open client connections"
+            ).unwrap(),
+            connection_errors: register_int_counter_vec!(
+                "connection_errors_total",
+                "Total number of connections that failed, by reason",
+                &["reason"]
             ).unwrap(),
             memory_usage: register_gauge!(
                 "memory_usage_bytes",
@@ -74,17 +166,25 @@ impl Metrics {
                 "process_uptime_seconds",
                 "Uptime of the process in seconds"
             ).unwrap(),
+            write_stopped: register_int_gauge_vec!(
+                "write_stopped",
+                "Whether RocksDB has stopped writes until compaction catches up (1) or not (0)",
+                &["database"]
+            ).unwrap(),
+            delayed_write_rate: register_int_gauge_vec!(
+                "delayed_write_rate_bytes",
+                "Rate in bytes per second that RocksDB throttles writes to, 0 when writes are not delayed",
+                &["database"]
+            ).unwrap(),
             process_start_time: SystemTime::now(),
-        };
-
-        metrics
+        }
     }
 
     pub fn set_enabled(&self, enabled: bool) {
         self.enabled.store(enabled, Ordering::Relaxed);
     }
 
-    pub fn gather_metrics() -> String {
+    pub fn gather_metrics(format: MetricsFormat) -> String {
         let encoder = TextEncoder::new();
         let metric_families = prometheus::gather();
 
@@ -100,10 +200,22 @@ impl Metrics {
         if let Err(e) = encoder.encode(&metric_families, &mut buffer) {
             error!("Failed to encode metrics: {}", e);
         }
-        String::from_utf8(buffer).unwrap_or_else(|e| {
+        let text = String::from_utf8(buffer).unwrap_or_else(|e| {
             error!("Failed to convert metrics to string: {}", e);
             String::new()
-        })
+        });
+
+        match format {
+            MetricsFormat::Text => text,
+            MetricsFormat::OpenMetrics => {
+                let counters = metric_families
+                    .iter()
+                    .filter(|family| family.get_field_type() == MetricType::COUNTER)
+                    .map(|family| family.get_name().to_string())
+                    .collect();
+                to_openmetrics(&text, &counters)
+            }
+        }
     }
 
     pub fn update_system_metrics(&self) {
@@ -121,41 +233,62 @@ impl Metrics {
     }
 
     // Метод для инкрементации счетчика запросов
-    pub fn inc_requests(&self) {
+    pub fn inc_requests(&self, action: &str) {
         if self.enabled.load(Ordering::Relaxed) {
-            self.requests.inc();
+            self.requests.with_label_values(&[action]).inc();
         }
     }
 
-    pub fn inc_request_success(&self) {
+    pub fn inc_request_success(&self, action: &str) {
         if self.enabled.load(Ordering::Relaxed) {
-            self.request_success.inc();
+            self.request_success.with_label_values(&[action]).inc();
         }
     }
 
-    pub fn inc_request_failure(&self) {
+    pub fn inc_request_failure(&self, action: &str) {
         if self.enabled.load(Ordering::Relaxed) {
-            self.request_failure.inc();
+            self.request_failure.with_label_values(&[action]).inc();
+        }
+    }
+
+    pub fn inc_request_error(&self, code: &str) {
+        if self.enabled.load(Ordering::Relaxed) {
+            self.request_errors.with_label_values(&[code]).inc();
         }
     }
 
     // Метод для обновления продолжительности запроса
-    pub fn observe_request_duration(&self, duration: f64) {
+    pub fn observe_request_duration(&self, action: &str, duration: f64) {
         if self.enabled.load(Ordering::Relaxed) {
-            self.request_duration.observe(duration);
+            self.request_duration.with_label_values(&[action]).observe(duration);
         }
     }
 
-    // Аналогичные методы для остальных метрик
-    pub fn inc_active_requests(&self) {
+    // Gauges are updated even while metrics are disabled, so enabling them in between cannot unbalance them.
+    /// Counts a request as active until the returned guard is dropped.
+    pub fn track_request(&'static self) -> ActiveGuard {
+        self.active_requests.inc();
+        ActiveGuard(&self.active_requests)
+    }
+
+    /// Counts an accepted connection; `connections_active` counts it until the returned guard is dropped.
+    pub fn track_connection(&'static self) -> ActiveGuard {
+        self.inc_connections();
+        self.connections_active.inc();
+        ActiveGuard(&self.connections_active)
+    }
+
+    pub fn inc_connections(&self) {
         if self.enabled.load(Ordering::Relaxed) {
-            self.active_connections.inc();
+            self.connections.inc();
         }
     }
 
-    pub fn dec_active_requests(&self) {
+    /// `reason` is `accept` when accepting failed, `refused` over `--max-connections` and `io` for a
+    /// connection that ended with an I/O error.
+    pub fn inc_connection_error(&self, reason: &str) {
         if self.enabled.load(Ordering::Relaxed) {
-            self.active_connections.dec();
+            self.connection_errors.with_label_values(&[reason]).inc();
         }
     }
 
@@ -177,6 +310,19 @@ impl Metrics {
         }
     }
 
+    pub fn set_write_health(&self, database: &str, delayed_write_rate: u64, write_stopped: bool) {
+        if self.enabled.load(Ordering::Relaxed) {
+            self.delayed_write_rate.with_label_values(&[database]).set(delayed_write_rate as i64);
+            self.write_stopped.with_label_values(&[database]).set(write_stopped as i64);
+        }
+    }
+
+    // Dropped databases must not keep reporting their last state.
+    pub fn remove_write_health(&self, database: &str) {
+        let _ = self.delayed_write_rate.remove_label_values(&[database]);
+        let _ = self.write_stopped.remove_label_values(&[database]);
+    }
+
     pub fn inc_response_speed_bytes(&self, bytes: u64) {  // Вернулся к байтам
         if self.enabled.load(Ordering::Relaxed) {
             self.response_speed_bytes.inc_by(bytes);
@@ -184,5 +330,14 @@ impl Metrics {
     }
 }
 
+/// Decrements a gauge of `Metrics` when dropped.
+pub struct ActiveGuard(&'static IntGauge);
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
 pub static METRICS: Lazy<Metrics> = Lazy::new(|| Metrics::new(false));
 
@@ -1,99 +1,697 @@
+use crate::backup_stream::{self, FileStream, StreamDir};
 use crate::cache::cache::CacheLayer;
-use crate::db_manager::RocksDBManager;
-use async_std::task::{sleep, spawn};
-use log::{debug, error};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use crate::db_manager::{
+    DbConfig, IteratorEntry, MergeOp, ReadFlags, RocksDBManager, ValuePredicate, WriteFlags, WriteHealth,
+    CF_NOT_FOUND, MUTABLE_OPTIONS, SNAPSHOT_NOT_FOUND,
+};
+use crate::error::{ErrorCode, RequestError};
+use crate::idempotency::{Claim, IdempotencyKeys, Outcome};
+use crate::metrics::METRICS;
+use crate::waiters::KeyWaiters;
+use async_std::io::Read;
+use async_std::task::{sleep, spawn, spawn_blocking};
+use rust_rocksdb::{ReadTier, DEFAULT_COLUMN_FAMILY_NAME};
+use log::{debug, error, warn};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Request {
     pub action: String,
     pub key: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_value")]
     pub value: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_value")]
     pub default_value: Option<String>,
     pub cf_name: Option<String>,
     pub options: Option<HashMap<String, String>>,
     pub token: Option<String>,
     pub txn: Option<bool>,
+    pub db: Option<String>,
+    /// Retries with the same key within `--idempotency-ttl` get the first result instead of executing again.
+    pub idempotency_key: Option<String>,
+}
+
+/// Values are stored as strings; any other JSON value is stored as its compact JSON text.
+fn deserialize_value<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => None,
+        Some(Value::String(value)) => Some(value),
+        Some(value) => Some(value.to_string()),
+    })
 }
 
 impl Request {
-    fn parse_option<T: std::str::FromStr>(&self, key: &str) -> Option<T> {
+    pub(crate) fn parse_option<T: std::str::FromStr>(&self, key: &str) -> Option<T> {
         self.options
             .as_ref()
             .and_then(|opts| opts.get(key))
             .and_then(|value| value.parse::<T>().ok())
     }
+
+    fn read_flags(&self) -> ReadFlags {
+        ReadFlags {
+            verify_checksums: self.parse_option::<bool>("verify_checksums").unwrap_or(true),
+            ..ReadFlags::default()
+        }
+    }
+
+    fn write_flags(&self) -> WriteFlags {
+        WriteFlags {
+            low_priority: self.parse_option::<bool>("low_priority").unwrap_or(false),
+        }
+    }
+
+    /// Whether `options.decode` asks for values parsed as JSON rather than returned as strings.
+    fn decode_json(&self) -> Result<bool, RequestError> {
+        match self.parse_option::<String>("decode").as_deref() {
+            None | Some("string") => Ok(false),
+            Some("json") => Ok(true),
+            Some(other) => Err(RequestError::invalid_request(format!(
+                "Unknown decode: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Entries returned per `scan_open` or `iterator_next_batch` call without `options.limit`.
+const DEFAULT_BATCH_LIMIT: usize = 100;
+
+fn batch_limit(req: &Request) -> Result<usize, RequestError> {
+    match req.options.as_ref().and_then(|opts| opts.get("limit")) {
+        Some(limit) => match limit.parse::<usize>() {
+            Ok(limit) if limit > 0 => Ok(limit),
+            _ => Err(RequestError::invalid_request("limit must be a positive integer")),
+        },
+        None => Ok(DEFAULT_BATCH_LIMIT),
+    }
+}
+
+/// Digits a numeric range bound is padded to without `options.key_width`, enough for any u64.
+const DEFAULT_NUMERIC_KEY_WIDTH: usize = 20;
+
+/// Reads the range bound option `name`. With `options.key_type=numeric` the bound is a non-negative integer,
+/// zero-padded to `options.key_width` digits and appended to `prefix`, so it sorts like keys written that way.
+fn range_bound(req: &Request, name: &str, prefix: Option<&str>) -> Result<Option<String>, RequestError> {
+    let bound = req.parse_option::<String>(name);
+    match req.parse_option::<String>("key_type").as_deref() {
+        None | Some("string") => Ok(bound),
+        Some("numeric") => {
+            let width = match req.options.as_ref().and_then(|opts| opts.get("key_width")) {
+                Some(width) => match width.parse::<usize>() {
+                    Ok(width) if width > 0 => width,
+                    _ => return Err(RequestError::invalid_request("key_width must be a positive integer")),
+                },
+                None => DEFAULT_NUMERIC_KEY_WIDTH,
+            };
+            let Some(bound) = bound else {
+                return Ok(None);
+            };
+            let number = bound.parse::<u64>().map_err(|_| {
+                RequestError::invalid_request(format!("{} must be a non-negative integer with key_type=numeric", name))
+            })?;
+            let digits = number.to_string();
+            if digits.len() > width {
+                return Err(RequestError::invalid_request(format!(
+                    "{} has more than key_width={} digits",
+                    name, width
+                )));
+            }
+            Ok(Some(format!("{}{:0>width$}", prefix.unwrap_or_default(), digits, width = width)))
+        }
+        Some(other) => Err(RequestError::invalid_request(format!("Unknown key_type: {}", other))),
+    }
+}
+
+/// Whether `options.encoding` asks for an iterator with base64-encoded keys and values.
+fn iterator_base64(req: &Request) -> Result<bool, RequestError> {
+    match req.parse_option::<String>("encoding").as_deref() {
+        None | Some("utf8") => Ok(false),
+        Some("base64") => Ok(true),
+        Some(other) => Err(RequestError::invalid_request(format!(
+            "Unknown encoding: {}",
+            other
+        ))),
+    }
+}
+
+fn iterator_entries_json(entries: Vec<IteratorEntry>) -> Value {
+    entries
+        .into_iter()
+        .map(|(key, value)| serde_json::json!({ "key": key, "value": value }))
+        .collect()
+}
+
+/// Parses a stored value for `options.decode=json`.
+fn parse_json_value(value: &str) -> Result<Value, RequestError> {
+    serde_json::from_str::<Value>(value)
+        .map_err(|e| RequestError::invalid_request(format!("Value is not valid JSON: {}", e)))
+}
+
+/// `options.fields`: the top-level fields of JSON object values a range read returns.
+struct Projection {
+    fields: Vec<String>,
+    /// Whether a value that is not a JSON object fails the request rather than being returned whole.
+    strict: bool,
+}
+
+impl Projection {
+    fn from_request(req: &Request) -> Result<Option<Self>, RequestError> {
+        let Some(fields) = req.parse_option::<String>("fields") else {
+            return Ok(None);
+        };
+        let fields: Vec<String> = fields
+            .split(',')
+            .map(|field| field.trim().to_string())
+            .filter(|field| !field.is_empty())
+            .collect();
+        if fields.is_empty() {
+            return Err(RequestError::invalid_request("fields must name at least one field"));
+        }
+
+        Ok(Some(Projection {
+            fields,
+            strict: req.parse_option::<bool>("strict").unwrap_or(true),
+        }))
+    }
+
+    /// Keeps only the requested fields; missing ones are left out. The result is JSON text
+    /// unless `decode_json` asks for the JSON value.
+    fn apply(&self, key: &str, value: String, decode_json: bool) -> Result<Value, RequestError> {
+        let object = match serde_json::from_str::<Value>(&value) {
+            Ok(Value::Object(object)) => object,
+            _ if self.strict => {
+                return Err(RequestError::invalid_request(format!(
+                    "Value of {} is not a JSON object",
+                    key
+                )));
+            }
+            _ if decode_json => return parse_json_value(&value),
+            _ => return Ok(Value::String(value)),
+        };
+
+        let projected: serde_json::Map<String, Value> = object
+            .into_iter()
+            .filter(|(field, _)| self.fields.contains(field))
+            .collect();
+        Ok(if decode_json {
+            Value::Object(projected)
+        } else {
+            Value::String(Value::Object(projected).to_string())
+        })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Response {
     pub success: bool,
-    pub result: Option<String>,
+    /// A string, except for `get` with `options.decode=json`, which returns the parsed value.
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    /// The RocksDB status category behind a `DB_ERROR`, e.g. `Busy` or `Corruption`, when RocksDB reported one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<String>,
+    /// Soft advice for the client, currently only `SLOW_DOWN` while RocksDB stalls writes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+    /// Number of raw bytes that follow the response line, set for `all` with `options.format=binary`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary_length: Option<usize>,
+    #[serde(skip)]
+    pub binary: Option<Vec<u8>>,
+    /// Files streamed after the response line instead of `binary`, set for `export_backup_stream`.
+    #[serde(skip)]
+    pub stream: Option<FileStream>,
+    /// `true` when the result was remembered under the request's `idempotency_key` rather than executed again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replayed: Option<bool>,
+}
+
+impl Response {
+    pub(crate) fn success(result: Option<String>) -> Self {
+        Response {
+            success: true,
+            result: result.map(Value::String),
+            error_code: None,
+            error_kind: None,
+            hint: None,
+            binary_length: None,
+            binary: None,
+            stream: None,
+            replayed: None,
+        }
+    }
+
+    /// A successful response whose payload is written as raw bytes after the response line.
+    fn success_binary(data: Vec<u8>) -> Self {
+        Response {
+            binary_length: Some(data.len()),
+            binary: Some(data),
+            ..Response::success(None)
+        }
+    }
+
+    /// A successful response followed by the frames of `stream`, which can be larger than memory.
+    fn success_stream(stream: FileStream) -> Self {
+        Response {
+            binary_length: Some(stream.len() as usize),
+            stream: Some(stream),
+            ..Response::success(None)
+        }
+    }
+
+    /// Answers a retried request with the outcome of its first attempt.
+    fn replay(outcome: Outcome) -> Self {
+        Response {
+            result: outcome.result,
+            hint: outcome.hint,
+            replayed: Some(true),
+            ..Response::success(None)
+        }
+    }
+
+    /// What a retry with the same idempotency key gets; only successes without raw bytes are remembered.
+    fn outcome(&self) -> Option<Outcome> {
+        (self.success && self.binary.is_none() && self.stream.is_none()).then(|| Outcome {
+            result: self.result.clone(),
+            hint: self.hint.clone(),
+        })
+    }
+
+    /// Like `success`, but embeds a result that is already JSON text as JSON.
+    fn success_json(result: Option<String>) -> Self {
+        Response {
+            result: result.map(|text| serde_json::from_str(&text).unwrap_or(Value::String(text))),
+            ..Response::success(None)
+        }
+    }
+
+    pub(crate) fn error(error: RequestError) -> Self {
+        METRICS.inc_request_error(error.code.as_str());
+        Response {
+            success: false,
+            error_kind: error.rocksdb_kind().map(str::to_string),
+            result: Some(Value::String(error.message)),
+            error_code: Some(error.code),
+            hint: None,
+            binary_length: None,
+            binary: None,
+            stream: None,
+            replayed: None,
+        }
+    }
+}
+
+/// Actions dispatched by `handle_request`. Metrics are labeled by action, so anything
+/// outside this list is reported as `unknown` to keep label cardinality bounded.
+pub const ACTIONS: &[&str] = &[
+    "put",
+    "get",
+    "get_many",
+    "delete",
+    "delete_if",
+    "scan_delete",
+    "truncate",
+    "merge",
+    "rename",
+    "copy",
+    "get_or_put",
+    "getset",
+    "incr",
+    "decr",
+    "wait_for_key",
+    "get_property",
+    "get_properties",
+    "keys",
+    "all",
+    "get_range_map",
+    "get_entity",
+    "tail",
+    "list_column_families",
+    "use_cf",
+    "create_column_family",
+    "drop_column_family",
+    "compact_range",
+    "delete_files_in_range",
+    "sync_wal",
+    "set_db_option",
+    "write_batch_put",
+    "write_batch_merge",
+    "write_batch_delete",
+    "write_batch_write",
+    "write_batch_clear",
+    "write_batch_create",
+    "write_batch_destroy",
+    "begin_bulk_load",
+    "end_bulk_load",
+    "create_iterator",
+    "scan_open",
+    "destroy_iterator",
+    "iterator_seek",
+    "iterator_seek_for_prev",
+    "iterator_next",
+    "iterator_prev",
+    "iterator_next_batch",
+    "export",
+    "import",
+    "backup",
+    "restore_latest",
+    "restore",
+    "get_backup_info",
+    "swap_db",
+    "export_backup_stream",
+    "import_backup_stream",
+    "create_snapshot",
+    "release_snapshot",
+    "list_snapshots",
+    "begin_transaction",
+    "commit_transaction",
+    "rollback_transaction",
+    "info",
+    "ping",
+    "write_health",
+    "selftest",
+    "storage_stats",
+    "get_options",
+    "cache_stats",
+    "cache_clear",
+    "list_databases",
+    "create_database",
+    "drop_database",
+];
+
+const MAX_CF_NAME_LEN: usize = 255;
+
+/// Rejects column family names RocksDB would fail on with an obscure error, or that are unreadable in logs.
+fn validate_cf_name(name: &str) -> Result<(), RequestError> {
+    if name.is_empty() {
+        return Err(RequestError::invalid_request("Column family name must not be empty"));
+    }
+    if name.len() > MAX_CF_NAME_LEN {
+        return Err(RequestError::invalid_request(format!(
+            "Column family name must be at most {} bytes",
+            MAX_CF_NAME_LEN
+        )));
+    }
+    if name.chars().any(char::is_control) {
+        return Err(RequestError::invalid_request(
+            "Column family name must not contain control characters",
+        ));
+    }
+    Ok(())
+}
+
+/// Longest `options.timeout_ms` accepted by `wait_for_key`, one hour.
+const MAX_WAIT_TIMEOUT_MS: u64 = 3_600_000;
+
+/// Key prefix reserved for the sentinel keys of `selftest`.
+const SELFTEST_PREFIX: &str = "__selftest__:";
+
+static NEXT_SELFTEST: AtomicU64 = AtomicU64::new(0);
+
+/// Actions that can return the `SLOW_DOWN` hint when called with `options.stall_hint`.
+const WRITE_ACTIONS: &[&str] = &[
+    "put",
+    "delete",
+    "delete_if",
+    "scan_delete",
+    "truncate",
+    "merge",
+    "rename",
+    "copy",
+    "get_or_put",
+    "getset",
+    "incr",
+    "decr",
+    "write_batch_write",
+    "import",
+];
+
+/// Actions that store `key`, which must fit `--max-key-size`; `rename` and `copy` store `options.new_key` instead.
+const KEY_WRITE_ACTIONS: &[&str] = &[
+    "put",
+    "merge",
+    "get_or_put",
+    "getset",
+    "incr",
+    "decr",
+    "write_batch_put",
+    "write_batch_merge",
+];
+
+/// Returns the metrics label for a requested action.
+pub fn action_label(action: &str) -> &'static str {
+    ACTIONS
+        .iter()
+        .find(|known| **known == action)
+        .copied()
+        .unwrap_or("unknown")
+}
+
+/// Caps on the resources a single connection may hold.
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionLimits {
+    /// Maximum number of open iterators; `0` disables the limit.
+    pub max_iterators: usize,
+    /// Maximum number of unreleased snapshots created with `create_snapshot`; `0` disables the limit.
+    pub max_snapshots: usize,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        ConnectionLimits {
+            max_iterators: 1024,
+            max_snapshots: 64,
+        }
+    }
+}
+
+/// Settings shared by every database the server opens.
+#[derive(Debug, Clone, Default)]
+pub struct ServerConfig {
+    pub ttl_secs: Option<u64>,
+    pub auth_token: Option<String>,
+    pub cache_ttl_secs: Option<u64>,
+    pub cache_enabled: bool,
+    pub db_config: DbConfig,
+    pub limits: ConnectionLimits,
+    pub idempotency_ttl_secs: u64,
+    /// Longest key in bytes a write may store; `0` disables the limit.
+    pub max_key_size: usize,
+    /// Whether `--admin-address` is set, which makes it the only address accepting admin actions.
+    pub admin_port: bool,
+    /// Token required on the admin address; `None` requires `auth_token` there too.
+    pub admin_token: Option<String>,
+}
+
+/// Resources one connection opened in one database, released when it disconnects.
+#[derive(Debug, Default)]
+pub struct ConnectionState {
+    iterators: HashSet<usize>,
+    /// Name and id of the snapshots this connection created; they outlive the connection.
+    snapshots: Vec<(String, u64)>,
+    /// Column family selected with `use_cf`, applied to requests without `cf_name`.
+    default_cf: Option<String>,
+    /// Transaction begun on this connection and not yet committed or rolled back.
+    transaction: Option<u64>,
+    /// Transaction whose `keep_open` commit or rollback on this connection left the transaction DB open.
+    kept_transaction_db: Option<u64>,
 }
 
 #[derive(Clone)]
 pub struct RocksDBServer {
+    name: String,
     db_manager: Arc<RocksDBManager>,
-    auth_token: Option<String>,
     cache_layer: Arc<CacheLayer>,
+    key_waiters: Arc<KeyWaiters>,
+    idempotency_keys: Arc<IdempotencyKeys>,
+    limits: ConnectionLimits,
+    max_key_size: usize,
 }
 
 impl RocksDBServer {
-    pub fn new(
-        db_path: String,
-        ttl_secs: Option<u64>,
-        auth_token: Option<String>,
-        cache_ttl_secs: Option<u64>,
-        cache_enabled: bool,
-    ) -> Result<Self, String> {
-        let db_manager = Arc::new(RocksDBManager::new(&db_path, ttl_secs)?);
+    /// Opens the database at `db_path`; `name` identifies it in metrics and `info`.
+    pub fn new(name: &str, db_path: String, config: &ServerConfig) -> Result<Self, String> {
+        backup_stream::remove_leftovers(&db_path);
+        let db_manager = Arc::new(RocksDBManager::new(
+            &db_path,
+            config.ttl_secs,
+            config.db_config.clone(),
+        )?);
 
         let cache_layer = CacheLayer::new(
-            Duration::from_secs(cache_ttl_secs.unwrap_or(1800)),
-            cache_enabled,
+            Duration::from_secs(config.cache_ttl_secs.unwrap_or(1800)),
+            config.cache_enabled,
             db_manager.clone(),
         );
 
         Ok(RocksDBServer {
+            name: name.to_string(),
             db_manager,
-            auth_token,
             cache_layer: Arc::new(cache_layer),
+            key_waiters: Arc::new(KeyWaiters::default()),
+            idempotency_keys: Arc::new(IdempotencyKeys::new(Duration::from_secs(config.idempotency_ttl_secs))),
+            limits: config.limits,
+            max_key_size: config.max_key_size,
         })
     }
 
-    pub(crate) async fn handle_request(&self, req: Request) -> Response {
-        if !self.is_authorized(&req) {
-            error!("Unauthorized request: {:?}", req);
-            return Response {
-                success: false,
-                result: Some("Unauthorized".to_string()),
-            };
+    /// Closes the database; later requests to this server fail with "Database is not open".
+    pub(crate) fn close(&self) -> Result<(), String> {
+        METRICS.remove_write_health(&self.name);
+        self.db_manager.close()
+    }
+
+    pub(crate) fn is_open(&self) -> bool {
+        self.db_manager.is_open()
+    }
+
+    /// Loads the entries under `prefixes`, or all of them, into the block cache. Blocks the calling thread.
+    pub(crate) fn warm_block_cache(&self, prefixes: &[String]) {
+        if let Err(e) = self.db_manager.warm_block_cache(prefixes) {
+            error!("Failed to warm the block cache of database {}: {}", self.name, e);
+        }
+    }
+
+    pub(crate) fn cache_ttl(&self) -> Duration {
+        self.cache_layer.ttl()
+    }
+
+    pub(crate) fn set_cache_ttl(&self, ttl: Duration) {
+        self.cache_layer.set_ttl(ttl);
+    }
+
+    /// Releases everything a closed connection left open.
+    pub(crate) fn close_connection(&self, connection: ConnectionState) {
+        for iterator_id in connection.iterators {
+            if let Err(e) = self.db_manager.destroy_iterator(iterator_id) {
+                debug!("Failed to destroy iterator {} on disconnect: {}", iterator_id, e);
+            }
         }
+        if let Some(id) = connection.transaction {
+            if let Err(e) = self.db_manager.rollback_transaction_if_active(id) {
+                error!("Failed to roll back transaction {} on disconnect: {}", id, e);
+            }
+        }
+        if let Some(id) = connection.kept_transaction_db {
+            if let Err(e) = self.db_manager.release_transaction_db(id) {
+                error!("Failed to release the transaction DB on disconnect: {}", e);
+            }
+        }
+    }
+
+    /// Executes a request against this database. The caller has already checked the token.
+    pub(crate) async fn handle_request(&self, req: Request, connection: &mut ConnectionState) -> Response {
+        let Some(key) = req.idempotency_key.clone() else {
+            return self.execute_request(req, connection).await;
+        };
+
+        match self.idempotency_keys.claim(&key, &req.action).await {
+            Ok(Claim::Replay(outcome)) => {
+                debug!("Replaying {} for idempotency key {}", req.action, key);
+                Response::replay(outcome)
+            }
+            Ok(Claim::Execute(claim)) => {
+                let response = self.execute_request(req, connection).await;
+                claim.finish(response.outcome());
+                response
+            }
+            Err(e) => Response::error(e),
+        }
+    }
 
+    async fn execute_request(&self, mut req: Request, connection: &mut ConnectionState) -> Response {
         debug!("Handling request action: {}", req.action);
+        // Creating or dropping a column family must name it; the selected one is never implied.
+        let uses_default_cf = !matches!(
+            req.action.as_str(),
+            "use_cf" | "create_column_family" | "drop_column_family"
+        );
+        if req.cf_name.is_none() && uses_default_cf {
+            req.cf_name = connection.default_cf.clone();
+        }
+        if let Err(e) = self.check_key_size(&req) {
+            return Response::error(e);
+        }
+        // A transaction DB kept open by `keep_open` gives way to the first request that needs the database.
+        let keeps_transaction_db = req.txn.unwrap_or(false)
+            || matches!(
+                req.action.as_str(),
+                "begin_transaction" | "commit_transaction" | "rollback_transaction" | "ping"
+            );
+        if !keeps_transaction_db {
+            if let Err(e) = self.db_manager.release_idle_transaction_db() {
+                error!("Failed to release the idle transaction DB: {}", e);
+            }
+        }
+
+        let stall_hint = WRITE_ACTIONS.contains(&req.action.as_str())
+            && req.parse_option::<bool>("stall_hint").unwrap_or(false);
+        let json_result =
+            req.action == "get" && req.parse_option::<String>("decode").as_deref() == Some("json");
+
+        if req.action == "all" && req.parse_option::<String>("format").as_deref() == Some("binary") {
+            return match self.handle_get_all_binary(req).await {
+                Ok(data) => Response::success_binary(data),
+                Err(e) => Response::error(e),
+            };
+        }
+        if req.action == "export_backup_stream" {
+            return match self.handle_export_backup_stream().await {
+                Ok(stream) => Response::success_stream(stream),
+                Err(e) => Response::error(e),
+            };
+        }
+
         let result = match req.action.as_str() {
             "put" => self.handle_put(req).await,
             "get" => self.handle_get(req).await,
+            "get_many" => self.handle_get_many(req).await,
             "delete" => self.handle_delete(req).await,
+            "delete_if" => self.handle_delete_if(req).await,
+            "scan_delete" => self.handle_scan_delete(req).await,
+            "truncate" => self.handle_truncate(req).await,
             "merge" => self.handle_merge(req).await,
+            "rename" => self.handle_rename(req).await,
+            "copy" => self.handle_copy(req).await,
+            "get_or_put" => self.handle_get_or_put(req).await,
+            "getset" => self.handle_getset(req).await,
+            "incr" => self.handle_incr(req).await,
+            "decr" => self.handle_decr(req).await,
+            "wait_for_key" => self.handle_wait_for_key(req).await,
             "get_property" => self.handle_get_property(req).await,
+            "get_properties" => self.handle_get_properties(req).await,
             "keys" => self.handle_get_keys(req).await,
             "all" => self.handle_get_all(req).await,
+            "get_range_map" => self.handle_get_range_map(req).await,
+            "get_entity" => self.handle_get_entity(req).await,
+            "tail" => self.handle_tail(req).await,
             "list_column_families" => self.handle_list_column_families().await,
+            "use_cf" => self.handle_use_cf(req, connection).await,
             "create_column_family" => self.handle_create_column_family(req).await,
             "drop_column_family" => self.handle_drop_column_family(req).await,
             "compact_range" => self.handle_compact_range(req).await,
+            "delete_files_in_range" => self.handle_delete_files_in_range(req).await,
+            "sync_wal" => self.handle_sync_wal().await,
+            "set_db_option" => self.handle_set_db_option(req).await,
             "write_batch_put" => self.handle_write_batch_put(req).await,
             "write_batch_merge" => self.handle_write_batch_merge(req).await,
             "write_batch_delete" => self.handle_write_batch_delete(req).await,
-            "write_batch_write" => self.handle_write_batch_write().await,
+            "write_batch_write" => self.handle_write_batch_write(req).await,
             "write_batch_clear" => self.handle_write_batch_clear().await,
+            "write_batch_create" => self.handle_write_batch_create().await,
             "write_batch_destroy" => self.handle_write_batch_destroy().await,
-            "create_iterator" => self.handle_create_iterator().await,
-            "destroy_iterator" => self.handle_destroy_iterator(req).await,
+            "begin_bulk_load" => self.handle_begin_bulk_load(req).await,
+            "end_bulk_load" => self.handle_end_bulk_load(req).await,
+            "create_iterator" => self.handle_create_iterator(req, connection).await,
+            "scan_open" => self.handle_scan_open(req, connection).await,
+            "destroy_iterator" => self.handle_destroy_iterator(req, connection).await,
             "iterator_seek" => {
                 self.handle_iterator_seek(req, rust_rocksdb::Direction::Forward)
                     .await
@@ -104,37 +702,84 @@ impl RocksDBServer {
             }
             "iterator_next" => self.handle_iterator_next(req).await,
             "iterator_prev" => self.handle_iterator_prev(req).await,
+            "iterator_next_batch" => self.handle_iterator_next_batch(req).await,
+            "export" => self.handle_export(req).await,
+            "import" => self.handle_import(req).await,
             "backup" => self.handle_backup().await,
             "restore_latest" => self.handle_restore_latest().await,
             "restore" => self.handle_restore_request(req).await,
             "get_backup_info" => self.handle_get_backup_info().await,
-            "begin_transaction" => self.handle_begin_transaction().await,
-            "commit_transaction" => self.handle_commit_transaction().await,
-            "rollback_transaction" => self.handle_rollback_transaction().await,
-            _ => Err("Unknown action".to_string()),
+            "swap_db" => self.handle_swap_db(req).await,
+            "create_snapshot" => self.handle_create_snapshot(req, connection).await,
+            "release_snapshot" => self.handle_release_snapshot(req).await,
+            "list_snapshots" => self.handle_list_snapshots().await,
+            "begin_transaction" => self.handle_begin_transaction(connection).await,
+            "commit_transaction" => self.handle_commit_transaction(req, connection).await,
+            "rollback_transaction" => self.handle_rollback_transaction(req, connection).await,
+            "info" => self.handle_info().await,
+            "ping" => self.handle_ping(),
+            "write_health" => self.handle_write_health().await,
+            "selftest" => self.handle_selftest(req).await,
+            "storage_stats" => self.handle_storage_stats(req).await,
+            "get_options" => self.handle_get_options(req).await,
+            "cache_stats" => self.handle_cache_stats().await,
+            "cache_clear" => self.handle_cache_clear().await,
+            _ => Err(RequestError::new(ErrorCode::UnknownAction, "Unknown action")),
         };
 
         debug!("result: {:?}", result);
 
         match result {
             Ok(response) => Response {
-                success: true,
-                result: response,
-            },
-            Err(e) => Response {
-                success: false,
-                result: Some(e),
+                hint: (stall_hint && self.write_health().is_some_and(|h| h.is_stalled()))
+                    .then(|| "SLOW_DOWN".to_string()),
+                ..if json_result {
+                    Response::success_json(response)
+                } else {
+                    Response::success(response)
+                }
             },
+            Err(e) => Response::error(e),
+        }
+    }
+
+    fn check_key_size(&self, req: &Request) -> Result<(), RequestError> {
+        let key = match req.action.as_str() {
+            "rename" | "copy" => req.parse_option::<String>("new_key"),
+            action if KEY_WRITE_ACTIONS.contains(&action) => req.key.clone(),
+            _ => None,
+        };
+        match key {
+            Some(key) if self.max_key_size > 0 && key.len() > self.max_key_size => {
+                Err(RequestError::invalid_request(format!(
+                    "Key is {} bytes, longer than the maximum of {} bytes",
+                    key.len(),
+                    self.max_key_size
+                )))
+            }
+            _ => Ok(()),
         }
     }
 
-    fn is_authorized(&self, req: &Request) -> bool {
-        match &self.auth_token {
-            Some(auth_token) => req.token.as_deref() == Some(auth_token),
-            None => true,
+    fn write_health(&self) -> Option<WriteHealth> {
+        match self.db_manager.write_health() {
+            Ok(health) => {
+                METRICS.set_write_health(&self.name, health.delayed_write_rate, health.write_stopped);
+                Some(health)
+            }
+            Err(e) => {
+                error!("Failed to read write stall state: {}", e);
+                None
+            }
         }
     }
 
+    /// Refreshes the write stall gauges before metrics are scraped.
+    pub(crate) fn update_write_health_metrics(&self) {
+        self.write_health();
+    }
+
+
     /**
      * Inserts a key-value pair into the database.
      *
@@ -148,43 +793,45 @@ impl RocksDBServer {
      * - `value`: String - The value to put
      * - `cf_name`: Option<String> - The column family name
      * - `txn`: Option<bool> - The transaction ID
+     * - `options.low_priority`: Option<bool> - Write at low priority, so RocksDB slows this write down first under write pressure (default false). Ignored in transactions and with `--cache`
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
      * - `result`: Option<String> - The result of the operation
      * - `error`: Option<String> - Any error that occurred
      */
-    pub(crate) async fn handle_put(&self, req: Request) -> Result<Option<String>, String> {
+    pub(crate) async fn handle_put(&self, req: Request) -> Result<Option<String>, RequestError> {
         let key = req
             .key
             .clone()
-            .ok_or_else(|| "Key must be provided".to_string())?;
+            .ok_or_else(|| RequestError::invalid_request("Key must be provided"))?;
         let value = req
             .value
             .clone()
-            .ok_or_else(|| "Value must be provided".to_string())?;
+            .ok_or_else(|| RequestError::invalid_request("Value must be provided"))?;
 
         // Добавление в кеш-слой
         self.cache_layer
-            .put(key.clone(), value.clone(), req.cf_name.clone())
+            .put(key.clone(), value.clone(), req.cf_name.clone(), req.write_flags())
             .await;
 
         // Если кеш-слой выключен, то добавляем в базу данных
         if !self.cache_layer.enabled {
-            match self.db_manager.put(key, value, req.cf_name, req.txn) {
-                Ok(_) => Ok(None),
-                Err(e) => Err(format!("Failed to put data: {}", e)),
-            }
-        } else {
-            Ok(None)
+            self.db_manager
+                .put(key.clone(), value, req.cf_name.clone(), req.txn, req.write_flags())
+                .map_err(|e| format!("Failed to put data: {}", e))?;
         }
+
+        self.key_waiters.notify(&key, req.cf_name).await;
+        Ok(None)
     }
 
     /**
      * Retrieves the value associated with a key from the database.
      *
      * This function handles the `get` action which fetches the value associated with a specified key from the RocksDB database.
-     * The function can optionally operate within a specified column family and return a default value if the key is not found.
+     * The function can optionally operate within a specified column family. A missing key returns `default_value`
+     * if one is given and `null` otherwise, unless `options.required` makes it a `NOT_FOUND` error.
      *
      * # Link: get
      *
@@ -193,39 +840,192 @@ impl RocksDBServer {
      * - `cf_name`: Option<String> - The column family name
      * - `default_value`: Option<String> - The default value
      * - `txn`: Option<bool> - The transaction ID
+     * - `options.verify_checksums`: Option<bool> - Verify block checksums while reading (default true). Disabling it is faster but corrupted blocks go undetected
+     * - `options.with_meta`: Option<bool> - Return a JSON object `{"value": .., "from_cache": ..}` instead of the bare value (default false)
+     * - `options.read_tier`: Option<String> - Storage tier to read from: `all` (default) or `memtable`. With `memtable`, keys already flushed to disk are reported as missing
+     * - `options.decode`: Option<String> - `string` (default) returns the stored value as a string, `json` parses it and returns the JSON value itself. A value that is not valid JSON is an error
+     * - `options.snapshot`: Option<String> - Read the key as it was when this snapshot was created with `create_snapshot`
+     * - `options.required`: Option<bool> - Fail with `NOT_FOUND` when the key is missing instead of returning `null` (default false). Cannot be combined with `default_value`
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
      * - `result`: Option<String> - The result of the operation
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_get(&self, req: Request) -> Result<Option<String>, String> {
+    async fn handle_get(&self, req: Request) -> Result<Option<String>, RequestError> {
         debug!("handle_get with key: {:?}", req.key);
 
         let key = req
             .key
             .clone()
-            .ok_or_else(|| "Key must be provided".to_string())?;
+            .ok_or_else(|| RequestError::invalid_request("Key must be provided"))?;
+
+        let mut flags = req.read_flags();
+        flags.read_tier = match req.parse_option::<String>("read_tier").as_deref() {
+            None | Some("all") => ReadTier::All,
+            Some("memtable") => ReadTier::Memtable,
+            Some(other) => {
+                return Err(RequestError::invalid_request(format!(
+                    "Unknown read_tier: {}",
+                    other
+                )))
+            }
+        };
+
+        let decode_json = req.decode_json()?;
 
-        if let Some(cached_value) = self.cache_layer.get(&key, req.cf_name.clone()).await {
-            return Ok(Some(cached_value));
+        let required = req.parse_option::<bool>("required").unwrap_or(false);
+        if required && req.default_value.is_some() {
+            return Err(RequestError::invalid_request(
+                "required cannot be combined with default_value",
+            ));
         }
 
-        match self.db_manager.get(
-            key.clone(),
-            req.cf_name.clone(),
-            req.default_value.clone(),
-            req.txn,
-        ) {
-            Ok(Some(value)) => {
+        let with_meta = req.parse_option::<bool>("with_meta").unwrap_or(false);
+        let respond = |value: String, from_cache: bool| -> Result<Option<String>, RequestError> {
+            if !decode_json && !with_meta {
+                return Ok(Some(value));
+            }
+
+            let value = if decode_json {
+                parse_json_value(&value)?
+            } else {
+                Value::String(value)
+            };
+            let result = if with_meta {
+                serde_json::json!({ "value": value, "from_cache": from_cache })
+            } else {
+                value
+            };
+            Ok(Some(result.to_string()))
+        };
+        let missing = || -> Result<Option<String>, RequestError> {
+            match req.default_value.clone() {
+                Some(default_value) => respond(default_value, false),
+                None if required => Err(RequestError::not_found("Key not found")),
+                None => Ok(None),
+            }
+        };
+
+        // The cache only knows the latest values, so snapshot reads bypass it.
+        if let Some(snapshot) = req.parse_option::<String>("snapshot") {
+            return match self
+                .db_manager
+                .get_at_snapshot(&snapshot, &key, req.cf_name.clone(), flags)?
+            {
+                Some(value) => respond(value, false),
+                None => missing(),
+            };
+        }
+
+        // The cache holds writes that may not have reached RocksDB yet, so a memtable read bypasses it.
+        if flags.read_tier == ReadTier::All {
+            if let Some(cached_value) = self.cache_layer.get(&key, req.cf_name.clone()).await {
+                return respond(cached_value, true);
+            }
+        }
+
+        // The default value is applied here rather than in the manager so it never ends up in the cache.
+        match self
+            .db_manager
+            .get(key.clone(), req.cf_name.clone(), None, req.txn, flags)?
+        {
+            Some(value) => {
                 self.cache_layer
-                    .put(key, value.clone(), req.cf_name.clone())
+                    .fill(key, value.clone(), req.cf_name.clone())
                     .await;
-                Ok(Some(value))
+                respond(value, false)
+            }
+            None => missing(),
+        }
+    }
+
+    /**
+     * Retrieves several keys at once, each with its own default.
+     *
+     * This function handles the `get_many` action which reads a list of keys in one batch and returns a JSON object
+     * `{key: value, ...}`. Each entry of the list is either a key or an object `{"key": .., "default": ..}`; a
+     * missing key maps to its default, or to `null` if it has none.
+     *
+     * # Link: get_many
+     *
+     * # Parameters
+     * - `value`: String - A JSON array of keys or `{"key": .., "default": ..}` objects
+     * - `cf_name`: Option<String> - The column family name
+     * - `options.decode`: Option<String> - `string` (default) returns values as strings, `json` parses them and returns the JSON values. Defaults are returned as given
+     * - `options.verify_checksums`: Option<bool> - Verify block checksums while reading (default true)
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The values as a JSON object
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_get_many(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_get_many with value: {:?}", req.value);
+
+        let value = req
+            .value
+            .as_deref()
+            .ok_or_else(|| RequestError::invalid_request("Value must be provided"))?;
+        let invalid = || {
+            RequestError::invalid_request(
+                "Value must be a JSON array of keys or {\"key\": .., \"default\": ..} objects",
+            )
+        };
+        let entries: Vec<(String, Value)> = match serde_json::from_str::<Value>(value) {
+            Ok(Value::Array(entries)) => entries
+                .into_iter()
+                .map(|entry| match entry {
+                    Value::String(key) => Ok((key, Value::Null)),
+                    Value::Object(mut entry) => match entry.remove("key") {
+                        Some(Value::String(key)) => {
+                            Ok((key, entry.remove("default").unwrap_or(Value::Null)))
+                        }
+                        _ => Err(invalid()),
+                    },
+                    _ => Err(invalid()),
+                })
+                .collect::<Result<_, _>>()?,
+            _ => return Err(invalid()),
+        };
+        let decode_json = req.decode_json()?;
+
+        // Serve what the cache has and read the rest from RocksDB in one batch.
+        let mut values = Vec::with_capacity(entries.len());
+        let mut missing = Vec::new();
+        for (key, _) in &entries {
+            let cached = self.cache_layer.get(key, req.cf_name.clone()).await;
+            if cached.is_none() {
+                missing.push(key.clone());
+            }
+            values.push(cached);
+        }
+        let mut fetched = self
+            .db_manager
+            .get_many(&missing, req.cf_name.clone(), req.read_flags())?
+            .into_iter();
+        for (value, key) in values.iter_mut().zip(entries.iter().map(|(key, _)| key)) {
+            if value.is_none() {
+                *value = fetched.next().flatten();
+                if let Some(value) = value {
+                    self.cache_layer
+                        .fill(key.clone(), value.clone(), req.cf_name.clone())
+                        .await;
+                }
             }
-            Ok(None) => Err("Key not found".to_string()),
-            Err(e) => Err(e),
         }
+
+        let mut map = serde_json::Map::new();
+        for ((key, default), value) in entries.into_iter().zip(values) {
+            let value = match value {
+                Some(value) if decode_json => parse_json_value(&value)?,
+                Some(value) => Value::String(value),
+                None => default,
+            };
+            map.insert(key, value);
+        }
+
+        Ok(Some(Value::Object(map).to_string()))
     }
 
     /**
@@ -246,636 +1046,2347 @@ impl RocksDBServer {
      * - `result`: Option<String> - The result of the operation
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_delete(&self, req: Request) -> Result<Option<String>, String> {
+    async fn handle_delete(&self, req: Request) -> Result<Option<String>, RequestError> {
         debug!("handle_delete with key: {:?}", req.key);
 
         let key = req
             .key
             .clone()
-            .ok_or_else(|| "Key must be provided".to_string())?;
+            .ok_or_else(|| RequestError::invalid_request("Key must be provided"))?;
         self.cache_layer
             .delete(key.clone(), req.cf_name.clone())
             .await;
 
         match self.db_manager.delete(key, req.cf_name, req.txn) {
             Ok(_) => Ok(None),
-            Err(e) => Err(e),
+            Err(e) => Err(e.into()),
         }
     }
 
     /**
-     * Merges a value with an existing key in the database.
+     * Deletes a key only if its current value matches.
      *
-     * This function handles the `merge` action which merges a specified value with an existing key in the RocksDB database.
-     * The function can optionally operate within a specified column family and transaction if provided.
+     * This function handles the `delete_if` action which compares the current value of a key with the expected value
+     * and deletes the key only when they are equal. The comparison and the delete are atomic with respect to other
+     * single-key writes, including ones still queued by the cache, but not to `write_batch_write` or `truncate`.
+     * With `txn` the value is read with `get_for_update` in the active transaction instead.
      *
-     * # Link: merge
+     * # Link: delete_if
      *
      * # Parameters
-     * - `key`: String - The key to merge
-     * - `value`: String - The value to merge
+     * - `key`: String - The key to delete
+     * - `value`: String - The expected current value
      * - `cf_name`: Option<String> - The column family name
-     * - `txn`: Option<bool> - The transaction ID
+     * - `txn`: Option<bool> - Run inside the active transaction
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
-     * - `result`: Option<String> - The result of the operation
+     * - `result`: Option<String> - `true` if the key was deleted, `false` if the value did not match or the key is missing
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_merge(&self, req: Request) -> Result<Option<String>, String> {
-        debug!(
-            "handle_merge with key: {:?}, value: {:?}",
-            req.key, req.value
-        );
+    async fn handle_delete_if(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_delete_if with key: {:?}", req.key);
 
         let key = req
             .key
             .clone()
-            .ok_or_else(|| "Key must be provided".to_string())?;
-        let value = req
+            .ok_or_else(|| RequestError::invalid_request("Key must be provided"))?;
+        let expected = req
             .value
             .clone()
-            .ok_or_else(|| "Value must be provided".to_string())?;
-
-        self.cache_layer
-            .clear(key.clone(), req.cf_name.clone())
-            .await;
+            .ok_or_else(|| RequestError::invalid_request("Value must be provided"))?;
 
-        match self.db_manager.merge(key, value, req.cf_name, req.txn) {
-            Ok(_) => Ok(None),
-            Err(e) => Err(e),
+        // Compare against the latest value, which a queued put may not have written to RocksDB yet.
+        let _settled = self.cache_layer.settle(&[(req.cf_name.as_deref(), &key)]).await;
+        let deleted = self
+            .db_manager
+            .delete_if(key.clone(), expected, req.cf_name.clone(), req.txn)?;
+        if deleted {
+            self.cache_layer.clear(key, req.cf_name).await;
         }
+
+        Ok(Some(deleted.to_string()))
     }
 
     /**
-     * Retrieves a property of the database.
+     * Returns the value of a key, inserting a value first if the key is missing.
      *
-     * This function handles the `get_property` action which fetches a specified property of the RocksDB database.
-     * The function can optionally operate within a specified column family if provided.
+     * This function handles the `get_or_put` action which reads a key and, only when it does not exist, stores the
+     * given value under it. The read and the insert are atomic with respect to other writes, so concurrent callers
+     * all get the same value back and only one of them inserts it.
      *
-     * # Link: get_property
+     * # Link: get_or_put
      *
      * # Parameters
-     * - `value`: String - The property to get
+     * - `key`: String - The key to get
+     * - `value`: String - The value to insert if the key is missing
      * - `cf_name`: Option<String> - The column family name
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
-     * - `result`: Option<String> - The result of the operation
+     * - `result`: Option<String> - A JSON object `{"value": .., "inserted": ..}` with the value now stored and whether this call inserted it
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_get_property(&self, req: Request) -> Result<Option<String>, String> {
-        debug!("handle_get_property with property: {:?}", req.value);
+    async fn handle_get_or_put(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_get_or_put with key: {:?}", req.key);
 
+        let key = req
+            .key
+            .clone()
+            .ok_or_else(|| RequestError::invalid_request("Key must be provided"))?;
         let value = req
             .value
             .clone()
-            .ok_or_else(|| "Value must be provided".to_string())?;
-
-        match self.db_manager.get_property(value, req.cf_name) {
-            Ok(_) => Ok(None),
-            Err(e) => Err(e),
+            .ok_or_else(|| RequestError::invalid_request("Value must be provided"))?;
+
+        // Holding the key until the cache is filled keeps a concurrent cached put or delete from landing in between,
+        // and the queued ones are applied first so a pending delete is not reported as an existing value.
+        let _settled = self.cache_layer.settle(&[(req.cf_name.as_deref(), &key)]).await;
+        let (value, inserted) = match self.cache_layer.get(&key, req.cf_name.clone()).await {
+            Some(cached_value) => (cached_value, false),
+            None => {
+                let (value, inserted) =
+                    self.db_manager
+                        .get_or_put(key.clone(), value, req.cf_name.clone())?;
+                self.cache_layer
+                    .fill(key.clone(), value.clone(), req.cf_name.clone())
+                    .await;
+                (value, inserted)
+            }
+        };
+        if inserted {
+            self.key_waiters.notify(&key, req.cf_name).await;
         }
+
+        Ok(Some(
+            serde_json::json!({ "value": value, "inserted": inserted }).to_string(),
+        ))
     }
 
     /**
-     * Retrieves a range of keys from the database.
+     * Stores a value and returns the one it replaced.
      *
-     * This function handles the `keys` action which retrieves a range of keys from the RocksDB database.
-     * The function can specify a starting index, limit on the number of keys, and a query string to filter keys.
+     * This function handles the `getset` action which writes the value under a key and returns the previous value,
+     * or null when the key did not exist. The read and the write are atomic with respect to other writes. With `txn`
+     * both happen in the active transaction, which reads the key with `get_for_update`.
      *
-     * # Link: keys
+     * # Link: getset
      *
      * # Parameters
-     * - `options.start`: String - The start index
-     * - `options.limit`: String - The limit of keys to retrieve
-     * - `options.query`: Option<String> - The query string to filter keys
+     * - `key`: String - The key to set
+     * - `value`: String - The new value
+     * - `cf_name`: Option<String> - The column family name
+     * - `txn`: Option<bool> - Run inside the active transaction
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
-     * - `result`: Option<String> - The result of the operation
+     * - `result`: Option<String> - The previous value, or null if the key did not exist
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_get_keys(&self, req: Request) -> Result<Option<String>, String> {
-        debug!("handle_get_keys with options: {:?}", req.options);
-        let start = req.parse_option::<usize>("start").unwrap_or(0);
-        let limit = req.parse_option::<usize>("limit").unwrap_or(20);
-        let query = req
-            .options
-            .as_ref()
-            .and_then(|opts| opts.get("query").cloned());
+    async fn handle_getset(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_getset with key: {:?}", req.key);
 
-        self.db_manager
-            .get_keys(start, limit, query)
-            .map(|keys| {
-                let result = serde_json::to_string(&keys).unwrap();
-                Ok(Some(result))
-            })
-            .unwrap_or_else(|e| Err(e))
+        let key = req
+            .key
+            .clone()
+            .ok_or_else(|| RequestError::invalid_request("Key must be provided"))?;
+        let value = req
+            .value
+            .clone()
+            .ok_or_else(|| RequestError::invalid_request("Value must be provided"))?;
+
+        // A queued put would otherwise be missed as the previous value and land over the new one.
+        let _settled = self.cache_layer.settle(&[(req.cf_name.as_deref(), &key)]).await;
+        let previous = self
+            .db_manager
+            .getset(key.clone(), value.clone(), req.cf_name.clone(), req.txn)?;
+        if req.txn.unwrap_or(false) {
+            // The new value is not visible outside the transaction until it commits.
+            self.cache_layer.clear(key.clone(), req.cf_name.clone()).await;
+        } else {
+            self.cache_layer.fill(key.clone(), value, req.cf_name.clone()).await;
+        }
+        self.key_waiters.notify(&key, req.cf_name).await;
+
+        Ok(previous)
     }
 
     /**
-     * Retrieves all keys from the database.
+     * Increments an integer value.
      *
-     * This function handles the `all` action which retrieves all keys from the RocksDB database.
-     * The function can specify a query string to filter keys.
+     * This function handles the `incr` action which adds `options.by` to the integer stored under a key and returns
+     * the new value. A missing key counts as `0`. The read and the write are atomic with respect to other writes.
      *
-     * # Link: all
+     * # Link: incr
      *
      * # Parameters
-     * - `options.query`: Option<String> - The query string to filter keys
-     *
-     * # Returns
-     * - `success`: bool - Whether the operation was successful
-     * - `result`: Option<String> - The result of the operation
-     * - `error`: Option<String> - Any error that occurred
-     */
-    async fn handle_get_all(&self, req: Request) -> Result<Option<String>, String> {
-        debug!("handle_get_all with options: {:?}", req.options);
-        let query = req
-            .options
-            .as_ref()
-            .and_then(|opts| opts.get("query").cloned());
-
-        self.db_manager
-            .get_all(query)
-            .map(|keys| {
-                let result = serde_json::to_string(&keys).unwrap();
-                Ok(Some(result))
-            })
-            .unwrap_or_else(|e| Err(e))
-    }
-
-    /**
-     * Lists all column families in the database.
-     *
-     * This function handles the `list_column_families` action which lists all column families in the RocksDB database.
-     * The function requires the path to the database.
-     *
-     * # Link: list_column_families
+     * - `key`: String - The key of the counter
+     * - `options.by`: Option<i64> - The amount to add, may be negative (default 1)
+     * - `cf_name`: Option<String> - The column family name
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
-     * - `result`: Option<String> - The result of the operation
+     * - `result`: Option<String> - The new value
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_list_column_families(&self) -> Result<Option<String>, String> {
-        debug!("handle_list_column_families with value");
-        match self.db_manager.list_column_families() {
-            Ok(cfs) => Ok(Some(serde_json::to_string(&cfs).unwrap())),
-            Err(e) => Err(e),
-        }
+    async fn handle_incr(&self, req: Request) -> Result<Option<String>, RequestError> {
+        self.handle_increment(req, false).await
     }
 
     /**
-     * Creates a new column family in the database.
+     * Decrements an integer value.
      *
-     * This function handles the `create_column_family` action which creates a new column family in the RocksDB database.
-     * The function requires the name of the column family to create.
+     * This function handles the `decr` action which subtracts `options.by` from the integer stored under a key and
+     * returns the new value, which may be negative. A missing key counts as `0`. The read and the write are atomic
+     * with respect to other writes.
      *
-     * # Link: create_column_family
+     * # Link: decr
      *
      * # Parameters
-     * - `cf_name`: String - The column family name to create
+     * - `key`: String - The key of the counter
+     * - `options.by`: Option<i64> - The amount to subtract, may be negative (default 1)
+     * - `cf_name`: Option<String> - The column family name
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
-     * - `result`: Option<String> - The result of the operation
+     * - `result`: Option<String> - The new value
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_create_column_family(&self, req: Request) -> Result<Option<String>, String> {
-        debug!(
-            "handle_create_column_family with cf_name: {:?}",
-            req.cf_name
-        );
+    async fn handle_decr(&self, req: Request) -> Result<Option<String>, RequestError> {
+        self.handle_increment(req, true).await
+    }
 
-        let cf_name = req
-            .cf_name
+    async fn handle_increment(&self, req: Request, decrement: bool) -> Result<Option<String>, RequestError> {
+        debug!("handle_increment with key: {:?}, decrement: {}", req.key, decrement);
+
+        let key = req
+            .key
             .clone()
-            .ok_or_else(|| "Missing column family name".to_string())?;
+            .ok_or_else(|| RequestError::invalid_request("Key must be provided"))?;
+        let by = match req.options.as_ref().and_then(|opts| opts.get("by")) {
+            Some(by) => by
+                .parse::<i64>()
+                .map_err(|_| RequestError::invalid_request("by must be an integer"))?,
+            None => 1,
+        };
+        let by = if decrement {
+            by.checked_neg()
+                .ok_or_else(|| RequestError::invalid_request("by is out of range"))?
+        } else {
+            by
+        };
 
-        match self.db_manager.create_column_family(cf_name) {
-            Ok(_) => Ok(None),
-            Err(e) => Err(e),
-        }
+        let _settled = self.cache_layer.settle(&[(req.cf_name.as_deref(), &key)]).await;
+        let value = self
+            .db_manager
+            .increment(key.clone(), by, req.cf_name.clone())?
+            .ok_or_else(|| {
+                RequestError::invalid_request("Value is not an integer or the result is out of range")
+            })?;
+        self.cache_layer
+            .fill(key.clone(), value.to_string(), req.cf_name.clone())
+            .await;
+        self.key_waiters.notify(&key, req.cf_name).await;
+
+        Ok(Some(value.to_string()))
     }
 
     /**
-     * Drops an existing column family from the database.
+     * Deletes every key of a column family.
      *
-     * This function handles the `drop_column_family` action which drops an existing column family from the RocksDB database.
-     * The function requires the name of the column family to drop.
+     * This function handles the `truncate` action which deletes all keys of the column family, or of every column
+     * family with `options.all_cfs`, in write batches without reading the values. Because it cannot be undone, it
+     * must be confirmed with `options.confirm`.
      *
-     * # Link: drop_column_family
+     * # Link: truncate
      *
      * # Parameters
-     * - `cf_name`: String - The column family name to drop
+     * - `options.confirm`: bool - Must be `true`
+     * - `options.all_cfs`: Option<bool> - Truncate every column family instead of one (default false)
+     * - `options.batch_size`: Option<usize> - Number of keys deleted per batch (default 1000)
+     * - `cf_name`: Option<String> - The column family name
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
-     * - `result`: Option<String> - The result of the operation
+     * - `result`: Option<String> - The number of deleted keys
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_drop_column_family(&self, req: Request) -> Result<Option<String>, String> {
-        debug!("handle_drop_column_family with cf_name: {:?}", req.cf_name);
+    async fn handle_truncate(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_truncate with cf_name: {:?}, options: {:?}", req.cf_name, req.options);
 
-        let cf_name = req
-            .cf_name
-            .clone()
-            .ok_or_else(|| "Missing column family name".to_string())?;
+        if !req.parse_option::<bool>("confirm").unwrap_or(false) {
+            return Err(RequestError::invalid_request(
+                "truncate deletes every key; set options.confirm to true",
+            ));
+        }
+        let all_cfs = req.parse_option::<bool>("all_cfs").unwrap_or(false);
+        let batch_size = req.parse_option::<usize>("batch_size").unwrap_or(1000);
+        if batch_size == 0 {
+            return Err(RequestError::invalid_request("Batch size must be greater than zero"));
+        }
 
-        match self.db_manager.drop_column_family(cf_name) {
-            Ok(_) => Ok(None),
-            Err(e) => Err(e),
+        // Walking a whole column family takes a while; keep it off the threads that serve other connections.
+        let db_manager = self.db_manager.clone();
+        let cf_name = req.cf_name.clone();
+        let deleted = spawn_blocking(move || {
+            if all_cfs {
+                db_manager.truncate_all(batch_size)
+            } else {
+                db_manager.truncate(cf_name, batch_size)
+            }
+        })
+        .await?;
+
+        if all_cfs {
+            self.cache_layer.clear_all().await;
+        } else {
+            self.cache_layer.clear_cf(req.cf_name).await;
         }
+
+        Ok(Some(deleted.to_string()))
     }
 
     /**
-     * Compacts a range of keys in the database.
+     * Deletes the keys in a range whose value matches a condition.
      *
-     * This function handles the `compact_range` action which compacts a specified range of keys in the RocksDB database.
-     * The function can optionally specify the start key, end key, and column family.
+     * This function handles the `scan_delete` action which scans keys from `start` (inclusive) to `end` (exclusive),
+     * tests each value against exactly one predicate and deletes the matches in write batches. Each match is checked
+     * again under its key lock before it is deleted, so a value rewritten during the scan is kept.
      *
-     * # Link: compact_range
+     * # Link: scan_delete
      *
      * # Parameters
-     * - `options.start`: Option<String> - The start key
-     * - `options.end`: Option<String> - The end key
+     * - `options.start`: Option<String> - The first key to scan (default: the first key)
+     * - `options.end`: Option<String> - The key to stop before (default: scan to the last key)
+     * - `options.key_type`: Option<String> - `string` (default) or `numeric`, which reads `start` and `end` as integers zero-padded to `options.key_width` digits
+     * - `options.key_width`: Option<usize> - The digits of numeric keys with `key_type=numeric` (default 20)
+     * - `options.value_prefix`: Option<String> - Delete values starting with this string
+     * - `options.value_contains`: Option<String> - Delete values containing this string
+     * - `options.json_pointer`: Option<String> - Delete JSON values whose field at this JSON Pointer (e.g. `/expired`) equals `options.json_value`
+     * - `options.json_value`: Option<String> - The JSON value to compare with, e.g. `true`
+     * - `options.batch_size`: Option<usize> - Number of keys deleted per batch (default 1000)
      * - `cf_name`: Option<String> - The column family name
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
-     * - `result`: Option<String> - The result of the operation
+     * - `result`: Option<String> - The number of deleted keys
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_compact_range(&self, req: Request) -> Result<Option<String>, String> {
-        debug!("handle_compact_range with options: {:?}", req.options);
-        let start = req
-            .parse_option::<String>("start")
-            .unwrap_or("".to_string());
-        let end = req.parse_option::<String>("end").unwrap_or("".to_string());
-        match self
-            .db_manager
-            .compact_range(Some(start), Some(end), req.cf_name)
-        {
-            Ok(_) => Ok(None),
-            Err(e) => Err(e),
+    async fn handle_scan_delete(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_scan_delete with options: {:?}", req.options);
+
+        let json_equals = match (
+            req.parse_option::<String>("json_pointer"),
+            req.parse_option::<String>("json_value"),
+        ) {
+            (Some(pointer), Some(value)) => {
+                let value = serde_json::from_str(&value).map_err(|e| {
+                    RequestError::invalid_request(format!("json_value must be valid JSON: {}", e))
+                })?;
+                Some(ValuePredicate::JsonEquals { pointer, value })
+            }
+            (None, None) => None,
+            _ => {
+                return Err(RequestError::invalid_request(
+                    "json_pointer and json_value must be provided together",
+                ))
+            }
+        };
+
+        let mut predicates: Vec<ValuePredicate> = [
+            req.parse_option::<String>("value_prefix").map(ValuePredicate::Prefix),
+            req.parse_option::<String>("value_contains").map(ValuePredicate::Contains),
+            json_equals,
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if predicates.len() != 1 {
+            return Err(RequestError::invalid_request(
+                "Exactly one of value_prefix, value_contains or json_pointer must be provided",
+            ));
+        }
+        let predicate = predicates.remove(0);
+
+        let batch_size = req.parse_option::<usize>("batch_size").unwrap_or(1000);
+        if batch_size == 0 {
+            return Err(RequestError::invalid_request("Batch size must be greater than zero"));
         }
+
+        let deleted = self.db_manager.scan_delete(
+            range_bound(&req, "start", None)?,
+            range_bound(&req, "end", None)?,
+            req.cf_name.clone(),
+            &predicate,
+            batch_size,
+        )?;
+        if deleted > 0 {
+            self.cache_layer.clear_cf(req.cf_name).await;
+        }
+
+        Ok(Some(deleted.to_string()))
     }
 
     /**
-     * Adds a key-value pair to the current write batch.
+     * Merges a value with an existing key in the database.
      *
-     * This function handles the `write_batch_put` action which adds a specified key-value pair to the current write batch.
-     * The function can optionally operate within a specified column family.
+     * This function handles the `merge` action which merges a specified value with an existing key in the RocksDB database.
+     * The function can optionally operate within a specified column family and transaction if provided.
      *
-     * # Link: write_batch_put
+     * # Link: merge
      *
      * # Parameters
-     * - `key`: String - The key to put
-     * - `value`: String - The value to put
+     * - `key`: String - The key to merge
+     * - `value`: String - The value to merge
      * - `cf_name`: Option<String> - The column family name
+     * - `txn`: Option<bool> - The transaction ID
+     * - `options.return_value`: Option<bool> - Return the merged value in the response (default false)
+     * - `options.merge_op`: Option<String> - Merge with `json-patch`, `merge-patch`, `numeric-add` or `string-append` as a read-modify-write instead of the column family's merge operator. Not supported with `txn`
+     * - `options.low_priority`: Option<bool> - Write at low priority, so RocksDB slows this write down first under write pressure (default false). Ignored in transactions
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
-     * - `result`: Option<String> - The result of the operation
+     * - `result`: Option<String> - The merged value when `return_value` is set
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_write_batch_put(&self, req: Request) -> Result<Option<String>, String> {
+    async fn handle_merge(&self, req: Request) -> Result<Option<String>, RequestError> {
         debug!(
-            "handle_write_batch_put with key: {:?}, value: {:?}",
+            "handle_merge with key: {:?}, value: {:?}",
             req.key, req.value
         );
 
         let key = req
             .key
             .clone()
-            .ok_or_else(|| "Key must be provided".to_string())?;
+            .ok_or_else(|| RequestError::invalid_request("Key must be provided"))?;
         let value = req
             .value
             .clone()
-            .ok_or_else(|| "Value must be provided".to_string())?;
+            .ok_or_else(|| RequestError::invalid_request("Value must be provided"))?;
 
-        match self.db_manager.write_batch_put(key, value, req.cf_name) {
-            Ok(_) => Ok(None),
-            Err(e) => Err(e),
+        let return_value = req.parse_option::<bool>("return_value").unwrap_or(false);
+
+        // Merge into the latest value, and keep a queued put from landing over the merged one.
+        let _settled = self.cache_layer.settle(&[(req.cf_name.as_deref(), &key)]).await;
+
+        if let Some(merge_op) = req.parse_option::<String>("merge_op") {
+            let merge_op = merge_op
+                .parse::<MergeOp>()
+                .map_err(RequestError::invalid_request)?;
+            if req.txn.unwrap_or(false) {
+                return Err(RequestError::invalid_request(
+                    "merge_op is not supported in transactions",
+                ));
+            }
+
+            let merged = self
+                .db_manager
+                .merge_with_op(key.clone(), &value, merge_op, req.cf_name.clone(), req.write_flags())?
+                .map_err(RequestError::invalid_request)?;
+            self.cache_layer
+                .clear(key.clone(), req.cf_name.clone())
+                .await;
+            self.key_waiters.notify(&key, req.cf_name).await;
+
+            return Ok(return_value.then_some(merged));
+        }
+
+        self.db_manager
+            .merge(key.clone(), value, req.cf_name.clone(), req.txn, req.write_flags())?;
+        // Cleared after the merge so a concurrent get cannot re-cache the pre-merge value.
+        self.cache_layer
+            .clear(key.clone(), req.cf_name.clone())
+            .await;
+        self.key_waiters.notify(&key, req.cf_name.clone()).await;
+
+        if return_value {
+            Ok(self
+                .db_manager
+                .get(key, req.cf_name, None, req.txn, ReadFlags::default())?)
+        } else {
+            Ok(None)
         }
     }
 
     /**
-     * Merges a value with an existing key in the current write batch.
+     * Renames a key in the database.
      *
-     * This function handles the `write_batch_merge` action which merges a specified value with an existing key in the current write batch.
-     * The function can optionally operate within a specified column family.
+     * This function handles the `rename` action which atomically moves the value of a key to a new key.
+     * The old key is deleted and the new key is written in one write batch, overwriting any existing value.
      *
-     * # Link: write_batch_merge
+     * # Link: rename
      *
      * # Parameters
-     * - `key`: String - The key to merge
-     * - `value`: String - The value to merge
+     * - `key`: String - The key to rename
+     * - `options.new_key`: String - The new name of the key
      * - `cf_name`: Option<String> - The column family name
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
-     * - `result`: Option<String> - The result of the operation
+     * - `result`: Option<String> - `true` if the key existed and was renamed, `false` otherwise
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_write_batch_merge(&self, req: Request) -> Result<Option<String>, String> {
-        debug!(
-            "handle_write_batch_merge with key: {:?}, value: {:?}",
-            req.key, req.value
-        );
+    async fn handle_rename(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_rename with key: {:?}", req.key);
 
         let key = req
             .key
             .clone()
-            .ok_or_else(|| "Key must be provided".to_string())?;
-        let value = req
-            .value
-            .clone()
-            .ok_or_else(|| "Value must be provided".to_string())?;
+            .ok_or_else(|| RequestError::invalid_request("Key must be provided"))?;
+        let new_key = req
+            .parse_option::<String>("new_key")
+            .ok_or_else(|| RequestError::invalid_request("New key must be provided"))?;
+
+        // A queued put to either key would otherwise land after the rename and undo it.
+        let _settled = self
+            .cache_layer
+            .settle(&[(req.cf_name.as_deref(), &key), (req.cf_name.as_deref(), &new_key)])
+            .await;
+        let renamed = self
+            .db_manager
+            .rename(key.clone(), new_key.clone(), req.cf_name.clone())?;
 
-        match self.db_manager.write_batch_merge(key, value, req.cf_name) {
-            Ok(_) => Ok(None),
-            Err(e) => Err(e),
+        self.cache_layer.clear(key, req.cf_name.clone()).await;
+        self.cache_layer.clear(new_key.clone(), req.cf_name.clone()).await;
+        if renamed {
+            self.key_waiters.notify(&new_key, req.cf_name).await;
         }
+
+        Ok(Some(renamed.to_string()))
     }
 
     /**
-     * Deletes a key from the current write batch.
+     * Copies a key in the database.
      *
-     * This function handles the `write_batch_delete` action which deletes a specified key from the current write batch.
-     * The function can optionally operate within a specified column family.
+     * This function handles the `copy` action which stores the value of a key under a new key as well, keeping the
+     * original. Both keys are locked from the read to the write, so a concurrent write cannot produce a torn copy.
+     * An existing value under the new key is overwritten.
      *
-     * # Link: write_batch_delete
+     * # Link: copy
      *
      * # Parameters
-     * - `key`: String - The key to delete
-     * - `cf_name`: Option<String> - The column family name
+     * - `key`: String - The key to copy
+     * - `options.new_key`: String - The key to copy it to
+     * - `cf_name`: Option<String> - The column family name of the source
+     * - `options.new_cf_name`: Option<String> - The column family name of the copy, the source's if omitted
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
-     * - `result`: Option<String> - The result of the operation
+     * - `result`: Option<String> - `true` if the key existed and was copied, `false` otherwise
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_write_batch_delete(&self, req: Request) -> Result<Option<String>, String> {
-        debug!("handle_write_batch_delete with key: {:?}", req.key);
+    async fn handle_copy(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_copy with key: {:?}", req.key);
 
         let key = req
             .key
             .clone()
-            .ok_or_else(|| "Key must be provided".to_string())?;
+            .ok_or_else(|| RequestError::invalid_request("Key must be provided"))?;
+        let new_key = req
+            .parse_option::<String>("new_key")
+            .ok_or_else(|| RequestError::invalid_request("New key must be provided"))?;
+        let new_cf_name = req
+            .parse_option::<String>("new_cf_name")
+            .or_else(|| req.cf_name.clone());
+
+        // Settling both keys reads the source's latest value and keeps a queued put to the copy from landing over it.
+        let _settled = self
+            .cache_layer
+            .settle(&[(req.cf_name.as_deref(), &key), (new_cf_name.as_deref(), &new_key)])
+            .await;
+        let copied = self
+            .db_manager
+            .copy(key, new_key.clone(), req.cf_name.clone(), new_cf_name.clone())?;
 
-        match self.db_manager.write_batch_delete(key, req.cf_name) {
-            Ok(_) => Ok(None),
-            Err(e) => Err(e),
+        if copied {
+            self.cache_layer.clear(new_key.clone(), new_cf_name.clone()).await;
+            self.key_waiters.notify(&new_key, new_cf_name).await;
         }
+
+        Ok(Some(copied.to_string()))
     }
 
     /**
-     * Writes the current write batch to the database.
+     * Waits until a key exists in the database.
      *
-     * This function handles the `write_batch_write` action which writes the current write batch to the RocksDB database.
+     * This function handles the `wait_for_key` action which returns the value of a key as soon as it exists.
+     * If the key is missing, the request blocks until a `put`, `merge`, `rename` or `copy` writes it, or until the timeout expires.
      *
-     * # Link: write_batch_write
+     * # Link: wait_for_key
      *
      * # Parameters
-     * - None
+     * - `key`: String - The key to wait for
+     * - `cf_name`: Option<String> - The column family name
+     * - `options.timeout_ms`: Option<u64> - How long to wait in milliseconds (default 30000, at most 3600000)
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
-     * - `result`: Option<String> - The result of the operation
+     * - `result`: Option<String> - The value of the key
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_write_batch_write(&self) -> Result<Option<String>, String> {
-        debug!("handle_write_batch_write");
-        match self.db_manager.write_batch_write() {
-            Ok(_) => Ok(None),
-            Err(e) => Err(e),
+    async fn handle_wait_for_key(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_wait_for_key with key: {:?}", req.key);
+
+        let key = req
+            .key
+            .clone()
+            .ok_or_else(|| RequestError::invalid_request("Key must be provided"))?;
+        let timeout_ms = req.parse_option::<u64>("timeout_ms").unwrap_or(30000);
+        if timeout_ms > MAX_WAIT_TIMEOUT_MS {
+            return Err(RequestError::invalid_request(format!(
+                "options.timeout_ms must be at most {}",
+                MAX_WAIT_TIMEOUT_MS
+            )));
+        }
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+
+        loop {
+            // Register before looking the key up so a write in between is not missed.
+            let waiter = self.key_waiters.register(&key, req.cf_name.clone()).await;
+            if let Some(value) = self.lookup(&key, req.cf_name.clone()).await? {
+                return Ok(Some(value));
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if async_std::future::timeout(remaining, waiter.recv()).await.is_err() {
+                return Err(RequestError::new(
+                    ErrorCode::Timeout,
+                    "Timed out waiting for key",
+                ));
+            }
         }
     }
 
+    async fn lookup(&self, key: &str, cf_name: Option<String>) -> Result<Option<String>, RequestError> {
+        if let Some(value) = self.cache_layer.get(key, cf_name.clone()).await {
+            return Ok(Some(value));
+        }
+        Ok(self
+            .db_manager
+            .get(key.to_string(), cf_name, None, None, ReadFlags::default())?)
+    }
+
     /**
-     * Clears the current write batch.
+     * Retrieves a property of the database.
      *
-     * This function handles the `write_batch_clear` action which clears the current write batch.
+     * This function handles the `get_property` action which fetches a specified property of the RocksDB database.
+     * The function can optionally operate within a specified column family if provided.
      *
-     * # Link: write_batch_clear
+     * # Link: get_property
      *
      * # Parameters
-     * - None
+     * - `value`: String - The property to get
+     * - `cf_name`: Option<String> - The column family name
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
      * - `result`: Option<String> - The result of the operation
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_write_batch_clear(&self) -> Result<Option<String>, String> {
-        debug!("handle_write_batch_clear");
-        match self.db_manager.write_batch_clear() {
-            Ok(_) => Ok(None),
-            Err(e) => Err(e),
+    async fn handle_get_property(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_get_property with property: {:?}", req.value);
+
+        let value = req
+            .value
+            .clone()
+            .ok_or_else(|| RequestError::invalid_request("Value must be provided"))?;
+
+        match self.db_manager.get_property(value, req.cf_name)? {
+            Some(property) => Ok(Some(property)),
+            None => Err(RequestError::not_found("Property not found")),
         }
     }
 
     /**
-     * Destroys the current write batch.
+     * Retrieves several properties of the database in one call.
      *
-     * This function handles the `write_batch_destroy` action which destroys the current write batch.
+     * This function handles the `get_properties` action which fetches a list of RocksDB properties and returns them
+     * as a JSON object of name to value. Unknown properties map to `null`.
      *
-     * # Link: write_batch_destroy
+     * # Link: get_properties
      *
      * # Parameters
-     * - None
+     * - `value`: String - JSON array of property names, e.g. `["rocksdb.estimate-num-keys"]`
+     * - `cf_name`: Option<String> - The column family name
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
      * - `result`: Option<String> - The result of the operation
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_write_batch_destroy(&self) -> Result<Option<String>, String> {
-        debug!("handle_write_batch_destroy");
-        match self.db_manager.write_batch_destroy() {
-            Ok(_) => Ok(None),
-            Err(_) => Err("WriteBatch not initialized".to_string()),
-        }
+    async fn handle_get_properties(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_get_properties with properties: {:?}", req.value);
+
+        let value = req
+            .value
+            .as_deref()
+            .ok_or_else(|| RequestError::invalid_request("Value must be provided"))?;
+        let properties: Vec<String> = serde_json::from_str(value).map_err(|e| {
+            RequestError::invalid_request(format!("Value must be a JSON array of property names: {}", e))
+        })?;
+
+        let values: serde_json::Map<String, Value> = self
+            .db_manager
+            .get_properties(properties, req.cf_name)?
+            .into_iter()
+            .map(|(property, value)| (property, value.into()))
+            .collect();
+        Ok(Some(Value::Object(values).to_string()))
     }
 
     /**
-     * Creates a new iterator for the database.
+     * Retrieves a range of keys from the database.
      *
-     * This function handles the `create_iterator` action which creates a new iterator for iterating over the keys in the RocksDB database.
+     * This function handles the `keys` action which retrieves a range of keys from the RocksDB database.
+     * The function can specify a starting index, limit on the number of keys, and a query string to filter keys.
      *
-     * # Link: create_iterator
+     * # Link: keys
      *
      * # Parameters
-     * - None
+     * - `options.start`: String - The start index
+     * - `options.limit`: String - The limit of keys to retrieve
+     * - `options.query`: Option<String> - The query string to filter keys
+     * - `options.verify_checksums`: Option<bool> - Verify block checksums while reading (default true)
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
      * - `result`: Option<String> - The result of the operation
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_create_iterator(&self) -> Result<Option<String>, String> {
-        debug!("handle_create_iterator");
+    async fn handle_get_keys(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_get_keys with options: {:?}", req.options);
+        let start = req.parse_option::<usize>("start").unwrap_or(0);
+        let limit = req.parse_option::<usize>("limit").unwrap_or(20);
+        let query = req
+            .options
+            .as_ref()
+            .and_then(|opts| opts.get("query").cloned());
+
         self.db_manager
-            .create_iterator()
-            .map(|id| Ok(Some(id.to_string())))
-            .unwrap_or_else(|e| Err(e))
+            .get_keys(start, limit, query, req.read_flags())
+            .map(|keys| {
+                let result = serde_json::to_string(&keys).unwrap();
+                Ok(Some(result))
+            })
+            .unwrap_or_else(|e| Err(e.into()))
     }
 
     /**
-     * Destroys an existing iterator.
+     * Retrieves all keys from the database.
      *
-     * This function handles the `destroy_iterator` action which destroys an existing iterator in the RocksDB database.
-     * The function requires the ID of the iterator to destroy.
+     * This function handles the `all` action which retrieves all keys from the RocksDB database.
+     * The function can specify a query string to filter keys.
      *
-     * # Link: destroy_iterator
+     * # Link: all
      *
      * # Parameters
-     * - `options.iterator_id`: String - The iterator ID
+     * - `options.query`: Option<String> - The query string to filter keys
+     * - `options.verify_checksums`: Option<bool> - Verify block checksums while reading (default true)
+     * - `options.format`: Option<String> - `json` (default) returns the keys as a JSON array. `binary` returns every matching key with its value as length-prefixed raw bytes after the response line, whose `binary_length` gives their size
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
      * - `result`: Option<String> - The result of the operation
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_destroy_iterator(&self, req: Request) -> Result<Option<String>, String> {
-        debug!(
-            "handle_destroy_iterator with iterator_id: {:?}",
-            req.parse_option::<usize>("iterator_id")
-        );
-        let iterator_id = req.parse_option::<usize>("iterator_id").unwrap_or(0);
-        self.db_manager
-            .destroy_iterator(iterator_id)
-            .map(|_| Ok(None))
-            .unwrap_or_else(|e| Err(e))
-    }
+    async fn handle_get_all(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_get_all with options: {:?}", req.options);
+        if let Some(format) = req.parse_option::<String>("format").filter(|format| format != "json") {
+            return Err(RequestError::invalid_request(format!("Unknown format: {}", format)));
+        }
+        let query = req
+            .options
+            .as_ref()
+            .and_then(|opts| opts.get("query").cloned());
+
+        self.db_manager
+            .get_all(query, req.read_flags())
+            .map(|keys| {
+                let result = serde_json::to_string(&keys).unwrap();
+                Ok(Some(result))
+            })
+            .unwrap_or_else(|e| Err(e.into()))
+    }
+
+    /**
+     * Retrieves a range of entries as a JSON object.
+     *
+     * This function handles the `get_range_map` action which returns the entries under a prefix or in a key range as
+     * a JSON object `{key: value, ...}`. The object's keys are in the database's key order. A key that is not valid
+     * UTF-8 fails the request.
+     *
+     * # Link: get_range_map
+     *
+     * # Parameters
+     * - `options.prefix`: Option<String> - Only return keys starting with this string
+     * - `options.start`: Option<String> - The first key to return (default: the prefix, or the first key)
+     * - `options.end`: Option<String> - The key to stop before (default: the end of the prefix, or the last key)
+     * - `options.key_type`: Option<String> - `string` (default) or `numeric`, which reads `start` and `end` as integers zero-padded to `options.key_width` digits after the prefix
+     * - `options.key_width`: Option<usize> - The digits of numeric keys with `key_type=numeric` (default 20)
+     * - `options.limit`: Option<usize> - The maximum number of entries to return (default: no limit)
+     * - `options.decode`: Option<String> - `string` (default) returns values as strings, `json` parses them and returns the JSON values. A value that is not valid JSON is an error
+     * - `options.fields`: Option<String> - Comma-separated top-level fields to return from JSON object values
+     * - `options.strict`: Option<bool> - With `fields`, fail on a value that is not a JSON object instead of returning it whole (default true)
+     * - `options.verify_checksums`: Option<bool> - Verify block checksums while reading (default true)
+     * - `cf_name`: Option<String> - The column family name
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The entries as a JSON object
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_get_range_map(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_get_range_map with options: {:?}", req.options);
+
+        let decode_json = req.decode_json()?;
+        let projection = Projection::from_request(&req)?;
+        let prefix = req.parse_option::<String>("prefix");
+        let entries = self.db_manager.get_range(
+            req.cf_name.clone(),
+            prefix.clone(),
+            range_bound(&req, "start", prefix.as_deref())?,
+            range_bound(&req, "end", prefix.as_deref())?,
+            req.parse_option::<usize>("limit"),
+            req.read_flags(),
+        )?;
+
+        // `Map` sorts keys as strings, which is the byte order RocksDB returned them in.
+        let mut map = serde_json::Map::new();
+        for (key, value) in entries {
+            let value = if let Some(projection) = &projection {
+                projection.apply(&key, value, decode_json)?
+            } else if decode_json {
+                parse_json_value(&value)?
+            } else {
+                Value::String(value)
+            };
+            map.insert(key, value);
+        }
+
+        Ok(Some(Value::Object(map).to_string()))
+    }
+
+    /**
+     * Assembles the keys of an entity into a JSON object.
+     *
+     * This function handles the `get_entity` action for entities stored as one key per attribute, such as
+     * `user:123:name` and `user:123:email`. It returns the entries under `prefix` followed by `separator` as a JSON
+     * object keyed by the rest of the key, e.g. `{"email": .., "name": ..}`. An entity without keys is `{}`.
+     *
+     * # Link: get_entity
+     *
+     * # Parameters
+     * - `options.prefix`: String - The key prefix of the entity, e.g. `user:123`
+     * - `options.separator`: Option<String> - The separator between the prefix and the attribute names (default `:`)
+     * - `options.decode`: Option<String> - `string` (default) returns values as strings, `json` parses them and returns the JSON values. A value that is not valid JSON is an error
+     * - `options.verify_checksums`: Option<bool> - Verify block checksums while reading (default true)
+     * - `cf_name`: Option<String> - The column family name
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The entity as a JSON object
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_get_entity(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_get_entity with options: {:?}", req.options);
+
+        let prefix = req
+            .parse_option::<String>("prefix")
+            .ok_or_else(|| RequestError::invalid_request("Prefix must be provided"))?;
+        let separator = req
+            .parse_option::<String>("separator")
+            .unwrap_or_else(|| ":".to_string());
+        let decode_json = req.decode_json()?;
+
+        let key_prefix = format!("{}{}", prefix, separator);
+        let entries = self.db_manager.get_range(
+            req.cf_name.clone(),
+            Some(key_prefix.clone()),
+            None,
+            None,
+            None,
+            req.read_flags(),
+        )?;
+
+        let mut entity = serde_json::Map::new();
+        for (key, value) in entries {
+            let value = if decode_json {
+                parse_json_value(&value)?
+            } else {
+                Value::String(value)
+            };
+            entity.insert(key[key_prefix.len()..].to_string(), value);
+        }
+
+        Ok(Some(Value::Object(entity).to_string()))
+    }
+
+    /**
+     * Retrieves the last entries under a prefix, newest first.
+     *
+     * This function handles the `tail` action which positions at the last key starting with the prefix and walks
+     * backward, returning up to `count` entries in descending key order as a JSON array of `{"key": .., "value": ..}`
+     * objects. For time-ordered keys such as `event:<timestamp>` this returns the latest entries.
+     *
+     * # Link: tail
+     *
+     * # Parameters
+     * - `options.prefix`: Option<String> - Only return keys starting with this string (default: all keys)
+     * - `options.count`: Option<usize> - The maximum number of entries to return (default 10)
+     * - `options.fields`: Option<String> - Comma-separated top-level fields to return from JSON object values
+     * - `options.strict`: Option<bool> - With `fields`, fail on a value that is not a JSON object instead of returning it whole (default true)
+     * - `options.verify_checksums`: Option<bool> - Verify block checksums while reading (default true)
+     * - `cf_name`: Option<String> - The column family name
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The entries as a JSON array
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_tail(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_tail with options: {:?}", req.options);
+
+        let count = match req.options.as_ref().and_then(|opts| opts.get("count")) {
+            Some(count) => count
+                .parse::<usize>()
+                .map_err(|_| RequestError::invalid_request("count must be a non-negative integer"))?,
+            None => 10,
+        };
+        let projection = Projection::from_request(&req)?;
+        let entries = self.db_manager.tail(
+            req.cf_name.clone(),
+            req.parse_option::<String>("prefix"),
+            count,
+            req.read_flags(),
+        )?;
+
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| {
+                let value = match &projection {
+                    Some(projection) => projection.apply(&key, value, false)?,
+                    None => Value::String(value),
+                };
+                Ok(serde_json::json!({ "key": key, "value": value }))
+            })
+            .collect::<Result<Vec<Value>, RequestError>>()?;
+        Ok(Some(serde_json::to_string(&entries).unwrap()))
+    }
+
+    async fn handle_get_all_binary(&self, req: Request) -> Result<Vec<u8>, RequestError> {
+        debug!("handle_get_all_binary with options: {:?}", req.options);
+        let query = req
+            .options
+            .as_ref()
+            .and_then(|opts| opts.get("query").cloned());
+
+        Ok(self.db_manager.get_all_binary(query, req.read_flags())?)
+    }
+
+    /**
+     * Lists all column families in the database.
+     *
+     * This function handles the `list_column_families` action which lists all column families in the RocksDB database.
+     * The names are sorted, with `default` first.
+     *
+     * # Link: list_column_families
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_list_column_families(&self) -> Result<Option<String>, RequestError> {
+        debug!("handle_list_column_families with value");
+        match self.db_manager.list_column_families() {
+            Ok(cfs) => Ok(Some(serde_json::to_string(&cfs).unwrap())),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /**
+     * Selects the column family used by requests on this connection that omit `cf_name`.
+     *
+     * This function handles the `use_cf` action which stores a default column family for the connection,
+     * similar to `USE database` in SQL. An explicit `cf_name` on a request still takes precedence.
+     *
+     * # Link: use_cf
+     *
+     * # Parameters
+     * - `cf_name`: Option<String> - The column family to select; omit it to go back to the default column family
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_use_cf(
+        &self,
+        req: Request,
+        connection: &mut ConnectionState,
+    ) -> Result<Option<String>, RequestError> {
+        debug!("handle_use_cf with cf_name: {:?}", req.cf_name);
+
+        if let Some(cf_name) = &req.cf_name {
+            if !self.db_manager.list_column_families()?.contains(cf_name) {
+                return Err(RequestError::not_found(CF_NOT_FOUND));
+            }
+        }
+
+        connection.default_cf = req.cf_name;
+        Ok(None)
+    }
+
+    /**
+     * Creates a new column family in the database.
+     *
+     * This function handles the `create_column_family` action which creates a new column family in the RocksDB database.
+     * The function requires the name of the column family to create. The result is `{"created": true}`, or
+     * `{"created": false}` if the column family already existed.
+     *
+     * # Link: create_column_family
+     *
+     * # Parameters
+     * - `cf_name`: String - The column family name to create, 1-255 bytes without control characters
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_create_column_family(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!(
+            "handle_create_column_family with cf_name: {:?}",
+            req.cf_name
+        );
+
+        let cf_name = req
+            .cf_name
+            .clone()
+            .ok_or_else(|| RequestError::invalid_request("Missing column family name"))?;
+        validate_cf_name(&cf_name)?;
+
+        let created = self.db_manager.create_column_family(cf_name)?;
+        Ok(Some(serde_json::json!({ "created": created }).to_string()))
+    }
+
+    /**
+     * Drops an existing column family from the database.
+     *
+     * This function handles the `drop_column_family` action which drops an existing column family from the RocksDB database.
+     * The function requires the name of the column family to drop. The result is `{"dropped": true}`, or
+     * `{"dropped": false}` if there was no such column family. The `default` column family cannot be dropped.
+     *
+     * # Link: drop_column_family
+     *
+     * # Parameters
+     * - `cf_name`: String - The column family name to drop
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_drop_column_family(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_drop_column_family with cf_name: {:?}", req.cf_name);
+
+        let cf_name = req
+            .cf_name
+            .clone()
+            .ok_or_else(|| RequestError::invalid_request("Missing column family name"))?;
+        if cf_name == DEFAULT_COLUMN_FAMILY_NAME {
+            return Err(RequestError::invalid_request("Cannot drop the default column family"));
+        }
+
+        let dropped = self.db_manager.drop_column_family(cf_name)?;
+        Ok(Some(serde_json::json!({ "dropped": dropped }).to_string()))
+    }
+
+    /**
+     * Compacts a range of keys in the database.
+     *
+     * This function handles the `compact_range` action which compacts a specified range of keys in the RocksDB database.
+     * The function can optionally specify the start key, end key, and column family. A missing or empty bound leaves
+     * that side of the range open, so without either the whole column family is compacted. The result reports the
+     * total SST size of the column family before and after, so the space the compaction freed is visible.
+     *
+     * # Link: compact_range
+     *
+     * # Parameters
+     * - `options.start`: Option<String> - The start key
+     * - `options.end`: Option<String> - The end key
+     * - `options.key_type`: Option<String> - `string` (default) or `numeric`, which reads `start` and `end` as integers zero-padded to `options.key_width` digits
+     * - `options.key_width`: Option<usize> - The digits of numeric keys with `key_type=numeric` (default 20)
+     * - `cf_name`: Option<String> - The column family name
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - A JSON object with `size_before`, `size_after` and `reclaimed_bytes`
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_compact_range(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_compact_range with options: {:?}", req.options);
+        let start = range_bound(&req, "start", None)?.filter(|start| !start.is_empty());
+        let end = range_bound(&req, "end", None)?.filter(|end| !end.is_empty());
+        // Compaction can take minutes; keep it off the threads that serve other connections.
+        let db_manager = self.db_manager.clone();
+        let (size_before, size_after) =
+            spawn_blocking(move || db_manager.compact_range(start, end, req.cf_name)).await?;
+
+        Ok(Some(
+            serde_json::json!({
+                "size_before": size_before,
+                "size_after": size_after,
+                "reclaimed_bytes": size_before.saturating_sub(size_after),
+            })
+            .to_string(),
+        ))
+    }
+
+    /**
+     * Drops the SST files that lie entirely within a key range.
+     *
+     * This function handles the `delete_files_in_range` action which deletes every SST file whose keys all fall
+     * between `start` and `end`, freeing the disk space right away instead of waiting for compaction. Keys in
+     * files that only partly overlap the range, or in level 0 files and memtables, are left in place.
+     *
+     * # Link: delete_files_in_range
+     *
+     * # Parameters
+     * - `options.start`: String - The first key of the range
+     * - `options.end`: String - The last key of the range, inclusive
+     * - `options.key_type`: Option<String> - `string` (default) or `numeric`, which reads `start` and `end` as integers zero-padded to `options.key_width` digits
+     * - `options.key_width`: Option<usize> - The digits of numeric keys with `key_type=numeric` (default 20)
+     * - `cf_name`: Option<String> - The column family name
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - A JSON object with `freed_bytes`, the drop in total SST size
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_delete_files_in_range(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_delete_files_in_range with options: {:?}", req.options);
+
+        let (Some(start), Some(end)) = (
+            range_bound(&req, "start", None)?,
+            range_bound(&req, "end", None)?,
+        ) else {
+            return Err(RequestError::invalid_request("Start and end must be provided"));
+        };
+
+        let freed_bytes = self
+            .db_manager
+            .delete_files_in_range(&start, &end, req.cf_name.clone())?;
+        // Which keys went away is unknown, so keep nothing from this column family.
+        self.cache_layer.clear_cf(req.cf_name).await;
+
+        Ok(Some(serde_json::json!({ "freed_bytes": freed_bytes }).to_string()))
+    }
+
+    /**
+     * Flushes and fsyncs the write-ahead log.
+     *
+     * This function handles the `sync_wal` action which makes every write acknowledged so far durable
+     * without flushing the memtables. It is much cheaper than a full flush. Writes still queued by the
+     * cache are applied first, so they are covered too.
+     *
+     * # Link: sync_wal
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_sync_wal(&self) -> Result<Option<String>, RequestError> {
+        debug!("handle_sync_wal");
+        self.cache_layer.flush().await;
+        self.db_manager.sync_wal()?;
+        Ok(None)
+    }
+
+    /**
+     * Changes a column family option on the running database.
+     *
+     * This function handles the `set_db_option` action which applies a mutable RocksDB option without a restart,
+     * e.g. `disable_auto_compactions` during a bulk load. Options that RocksDB cannot change at runtime are rejected.
+     * The change lasts until the database is reopened.
+     *
+     * # Link: set_db_option
+     *
+     * # Parameters
+     * - `options.name`: String - The option name, one of `disable_auto_compactions`, `write_buffer_size`, `max_write_buffer_number`, `level0_file_num_compaction_trigger`, `level0_slowdown_writes_trigger`, `level0_stop_writes_trigger`, `target_file_size_base`, `max_bytes_for_level_base`, `soft_pending_compaction_bytes_limit` or `hard_pending_compaction_bytes_limit`
+     * - `options.value`: String - The new value, e.g. `true` or `67108864`
+     * - `cf_name`: Option<String> - The column family name
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_set_db_option(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_set_db_option with options: {:?}", req.options);
+
+        let name = req
+            .parse_option::<String>("name")
+            .ok_or_else(|| RequestError::invalid_request("Option name must be provided"))?;
+        let value = req
+            .parse_option::<String>("value")
+            .ok_or_else(|| RequestError::invalid_request("Option value must be provided"))?;
+        if !MUTABLE_OPTIONS.contains(&name.as_str()) {
+            return Err(RequestError::invalid_request(format!(
+                "Option {} cannot be changed at runtime",
+                name
+            )));
+        }
+
+        self.db_manager
+            .set_db_option(&name, &value, req.cf_name)
+            .map_err(|e| format!("Failed to set {}: {}", name, e))?;
+        Ok(None)
+    }
+
+    /**
+     * Adds a key-value pair to the current write batch.
+     *
+     * This function handles the `write_batch_put` action which adds a specified key-value pair to the current write batch.
+     * The function can optionally operate within a specified column family.
+     *
+     * # Link: write_batch_put
+     *
+     * # Parameters
+     * - `key`: String - The key to put
+     * - `value`: String - The value to put
+     * - `cf_name`: Option<String> - The column family name
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_write_batch_put(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!(
+            "handle_write_batch_put with key: {:?}, value: {:?}",
+            req.key, req.value
+        );
+
+        let key = req
+            .key
+            .clone()
+            .ok_or_else(|| RequestError::invalid_request("Key must be provided"))?;
+        let value = req
+            .value
+            .clone()
+            .ok_or_else(|| RequestError::invalid_request("Value must be provided"))?;
+
+        match self.db_manager.write_batch_put(key, value, req.cf_name) {
+            Ok(_) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /**
+     * Merges a value with an existing key in the current write batch.
+     *
+     * This function handles the `write_batch_merge` action which merges a specified value with an existing key in the current write batch.
+     * The function can optionally operate within a specified column family.
+     *
+     * # Link: write_batch_merge
+     *
+     * # Parameters
+     * - `key`: String - The key to merge
+     * - `value`: String - The value to merge
+     * - `cf_name`: Option<String> - The column family name
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_write_batch_merge(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!(
+            "handle_write_batch_merge with key: {:?}, value: {:?}",
+            req.key, req.value
+        );
+
+        let key = req
+            .key
+            .clone()
+            .ok_or_else(|| RequestError::invalid_request("Key must be provided"))?;
+        let value = req
+            .value
+            .clone()
+            .ok_or_else(|| RequestError::invalid_request("Value must be provided"))?;
+
+        match self.db_manager.write_batch_merge(key, value, req.cf_name) {
+            Ok(_) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /**
+     * Deletes a key from the current write batch.
+     *
+     * This function handles the `write_batch_delete` action which deletes a specified key from the current write batch.
+     * The function can optionally operate within a specified column family.
+     *
+     * # Link: write_batch_delete
+     *
+     * # Parameters
+     * - `key`: String - The key to delete
+     * - `cf_name`: Option<String> - The column family name
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_write_batch_delete(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_write_batch_delete with key: {:?}", req.key);
+
+        let key = req
+            .key
+            .clone()
+            .ok_or_else(|| RequestError::invalid_request("Key must be provided"))?;
+
+        match self.db_manager.write_batch_delete(key, req.cf_name) {
+            Ok(_) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /**
+     * Writes the current write batch to the database.
+     *
+     * This function handles the `write_batch_write` action which writes the current write batch to the RocksDB database.
+     * Puts queued by the cache are written first, and the cached values of the keys in the batch are dropped after
+     * the write, so later reads see the batch.
+     *
+     * # Link: write_batch_write
+     *
+     * # Parameters
+     * - `options.low_priority`: Option<bool> - Write at low priority, so RocksDB slows this write down first under write pressure (default false)
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_write_batch_write(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_write_batch_write with options: {:?}", req.options);
+        let flags = req.write_flags();
+        self.cache_layer
+            .write_and_clear(|| self.db_manager.write_batch_write(flags))
+            .await?;
+        Ok(None)
+    }
+
+    /**
+     * Clears the current write batch.
+     *
+     * This function handles the `write_batch_clear` action which clears the current write batch.
+     *
+     * # Link: write_batch_clear
+     *
+     * # Parameters
+     * - None
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_write_batch_clear(&self) -> Result<Option<String>, RequestError> {
+        debug!("handle_write_batch_clear");
+        match self.db_manager.write_batch_clear() {
+            Ok(_) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /**
+     * Creates a new write batch.
+     *
+     * This function handles the `write_batch_create` action which starts a new empty write batch, discarding the
+     * current one if there is any. It is needed after `write_batch_destroy` before the batch can be used again.
+     *
+     * # Link: write_batch_create
+     *
+     * # Parameters
+     * - None
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_write_batch_create(&self) -> Result<Option<String>, RequestError> {
+        debug!("handle_write_batch_create");
+        self.db_manager.write_batch_create()?;
+        Ok(None)
+    }
+
+    /**
+     * Destroys the current write batch.
+     *
+     * This function handles the `write_batch_destroy` action which destroys the current write batch.
+     * Later write batch actions fail until `write_batch_create` starts a new one.
+     *
+     * # Link: write_batch_destroy
+     *
+     * # Parameters
+     * - None
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_write_batch_destroy(&self) -> Result<Option<String>, RequestError> {
+        debug!("handle_write_batch_destroy");
+        self.db_manager.write_batch_destroy()?;
+        Ok(None)
+    }
+
+    /**
+     * Starts a bulk load.
+     *
+     * This function handles the `begin_bulk_load` action which makes `write_batch_write` skip the write-ahead log
+     * until `end_bulk_load`. Batches written in between are lost if the server crashes, so the load has to be
+     * re-run from the start in that case.
+     *
+     * # Link: begin_bulk_load
+     *
+     * # Parameters
+     * - `options.disable_auto_compactions`: Option<bool> - Pause automatic compactions during the load (default false)
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_begin_bulk_load(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_begin_bulk_load");
+        let disable_auto_compactions = req
+            .parse_option::<bool>("disable_auto_compactions")
+            .unwrap_or(false);
+        self.db_manager.begin_bulk_load(disable_auto_compactions)?;
+        Ok(None)
+    }
+
+    /**
+     * Finishes a bulk load.
+     *
+     * This function handles the `end_bulk_load` action which flushes every column family so the loaded data is
+     * durable, resumes automatic compactions and compacts the whole database.
+     *
+     * # Link: end_bulk_load
+     *
+     * # Parameters
+     * - `options.compact`: Option<bool> - Compact all column families after the flush (default true)
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_end_bulk_load(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_end_bulk_load");
+        let compact = req.parse_option::<bool>("compact").unwrap_or(true);
+        let db_manager = self.db_manager.clone();
+        spawn_blocking(move || db_manager.end_bulk_load(compact)).await?;
+        Ok(None)
+    }
+
+    /**
+     * Creates a new iterator for the database.
+     *
+     * This function handles the `create_iterator` action which creates a new iterator for iterating over the keys in the RocksDB database.
+     * Iterators belong to the connection that created them and are destroyed when it closes.
+     * Creating more than `--max-iterators-per-connection` iterators fails with `RESOURCE_LIMIT`.
+     * With `options.encoding` set to `base64`, the iterator returns base64-encoded keys and values and expects
+     * base64-encoded keys in `iterator_seek`, so binary keys can be scanned without loss.
+     *
+     * # Link: create_iterator
+     *
+     * # Parameters
+     * - `options.encoding`: Option<String> - `utf8` (default) or `base64`
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_create_iterator(
+        &self,
+        req: Request,
+        connection: &mut ConnectionState,
+    ) -> Result<Option<String>, RequestError> {
+        debug!("handle_create_iterator with options: {:?}", req.options);
+        self.check_iterator_limit(connection)?;
+
+        let id = self.db_manager.create_iterator(iterator_base64(&req)?)?;
+        connection.iterators.insert(id);
+        Ok(Some(id.to_string()))
+    }
+
+    fn check_iterator_limit(&self, connection: &ConnectionState) -> Result<(), RequestError> {
+        let max_iterators = self.limits.max_iterators;
+        if max_iterators > 0 && connection.iterators.len() >= max_iterators {
+            return Err(RequestError::new(
+                ErrorCode::ResourceLimit,
+                format!("Iterator limit of {} per connection reached", max_iterators),
+            ));
+        }
+        Ok(())
+    }
+
+    /**
+     * Opens an iterator positioned at a start key and returns its first entries.
+     *
+     * This function handles the `scan_open` action which creates an iterator limited to `[lower_bound, upper_bound)`,
+     * seeks it to `key` and returns the iterator ID with the first `limit` entries, saving the separate
+     * `create_iterator`, `iterator_seek` and `iterator_next` round trips. `iterator_next_batch` returns the rest in the
+     * same direction; the bounds also apply to `iterator_seek`, `iterator_next` and `iterator_prev` on this iterator.
+     * The result is a JSON object `{"iterator_id": .., "entries": [{"key": .., "value": ..}], "done": ..}`, where `done`
+     * means no entries are left. The iterator counts towards `--max-iterators-per-connection` until it is destroyed.
+     * With `options.encoding` set to `base64`, `key`, the bounds and the returned entries are base64-encoded.
+     *
+     * # Link: scan_open
+     *
+     * # Parameters
+     * - `key`: Option<String> - The first key to return, or with `reverse` the last (default: the first key in the bounds)
+     * - `options.lower_bound`: Option<String> - The first key the iterator may return
+     * - `options.upper_bound`: Option<String> - The key the iterator stops before
+     * - `options.direction`: Option<String> - `forward` (default) or `reverse`
+     * - `options.limit`: Option<usize> - The maximum number of entries to return (default 100)
+     * - `options.encoding`: Option<String> - `utf8` (default) or `base64`
+     * - `options.verify_checksums`: Option<bool> - Verify block checksums while reading (default true)
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The iterator ID and the first entries
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_scan_open(
+        &self,
+        req: Request,
+        connection: &mut ConnectionState,
+    ) -> Result<Option<String>, RequestError> {
+        debug!("handle_scan_open with key: {:?}, options: {:?}", req.key, req.options);
+        self.check_iterator_limit(connection)?;
+
+        let reverse = match req.parse_option::<String>("direction").as_deref() {
+            None | Some("forward") => false,
+            Some("reverse") => true,
+            Some(other) => {
+                return Err(RequestError::invalid_request(format!(
+                    "Unknown direction: {}",
+                    other
+                )))
+            }
+        };
+        let limit = batch_limit(&req)?;
+
+        let (id, entries, done) = self.db_manager.scan_open(
+            req.key.clone(),
+            req.parse_option::<String>("lower_bound"),
+            req.parse_option::<String>("upper_bound"),
+            reverse,
+            iterator_base64(&req)?,
+            limit,
+            req.read_flags(),
+        )?;
+        connection.iterators.insert(id);
+
+        Ok(Some(
+            serde_json::json!({
+                "iterator_id": id,
+                "entries": iterator_entries_json(entries),
+                "done": done,
+            })
+            .to_string(),
+        ))
+    }
+
+    /**
+     * Destroys an existing iterator.
+     *
+     * This function handles the `destroy_iterator` action which destroys an existing iterator in the RocksDB database.
+     * The function requires the ID of the iterator to destroy.
+     *
+     * # Link: destroy_iterator
+     *
+     * # Parameters
+     * - `options.iterator_id`: String - The iterator ID
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_destroy_iterator(
+        &self,
+        req: Request,
+        connection: &mut ConnectionState,
+    ) -> Result<Option<String>, RequestError> {
+        debug!(
+            "handle_destroy_iterator with iterator_id: {:?}",
+            req.parse_option::<usize>("iterator_id")
+        );
+        let iterator_id = req.parse_option::<usize>("iterator_id").unwrap_or(0);
+        self.db_manager.destroy_iterator(iterator_id)?;
+        connection.iterators.remove(&iterator_id);
+        Ok(None)
+    }
+
+    /**
+     * Seeks to a specific key in the iterator.
+     *
+     * This function handles the `iterator_seek` action which seeks to a specified key in an existing iterator in the RocksDB database.
+     * The function requires the ID of the iterator, the key to seek, and the direction of the seek (Forward or Reverse).
+     *
+     * # Link: iterator_seek
+     *
+     * # Parameters
+     * - `options.iterator_id`: String - The iterator ID
+     * - `key`: String - The key to seek, base64-encoded for iterators created with `options.encoding` `base64`
+     * - `options.verify_checksums`: Option<bool> - Verify block checksums while reading (default true)
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_iterator_seek(
+        &self,
+        req: Request,
+        direction: rust_rocksdb::Direction,
+    ) -> Result<Option<String>, RequestError> {
+        debug!(
+            "handle_iterator_seek with iterator_id: {:?}, key: {:?}",
+            req.parse_option::<usize>("iterator_id"),
+            req.key
+        );
+        let key = req
+            .key
+            .clone()
+            .ok_or_else(|| RequestError::invalid_request("Key must be provided"))?;
+        let iterator_id = req.parse_option::<usize>("iterator_id").unwrap_or(0);
+
+        self.db_manager
+            .iterator_seek(iterator_id, key, direction, req.read_flags())
+            .map(|result| Ok(Some(result)))
+            .unwrap_or_else(|e| Err(e.into()))
+    }
+
+    /**
+     * Advances the iterator to the next key.
+     *
+     * This function handles the `iterator_next` action which advances an existing iterator to the next key in the RocksDB database.
+     * The function requires the ID of the iterator. It always moves forward in key order, also after `iterator_seek_for_prev`,
+     * so `iterator_next` followed by `iterator_prev` returns to the same key. An iterator that was never positioned starts at the first key.
+     *
+     * # Link: iterator_next
+     *
+     * # Parameters
+     * - `options.iterator_id`: String - The iterator ID
+     * - `options.verify_checksums`: Option<bool> - Verify block checksums while reading (default true)
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_iterator_next(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!(
+            "handle_iterator_next with iterator_id: {:?}",
+            req.parse_option::<usize>("iterator_id")
+        );
+        let iterator_id = req.parse_option::<usize>("iterator_id").unwrap_or(0);
+        self.db_manager
+            .iterator_next(iterator_id, req.read_flags())
+            .map(|result| Ok(Some(result)))
+            .unwrap_or_else(|e| Err(e.into()))
+    }
+
+    /**
+     * Moves the iterator to the previous key.
+     *
+     * This function handles the `iterator_prev` action which moves an existing iterator to the previous key in the RocksDB database.
+     * The function requires the ID of the iterator. It always moves backward in key order. An iterator that was never positioned
+     * starts at the last key.
+     *
+     * # Link: iterator_prev
+     *
+     * # Parameters
+     * - `options.iterator_id`: String - The iterator ID
+     * - `options.verify_checksums`: Option<bool> - Verify block checksums while reading (default true)
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_iterator_prev(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!(
+            "handle_iterator_prev with iterator_id: {:?}",
+            req.parse_option::<usize>("iterator_id")
+        );
+        let iterator_id = req.parse_option::<usize>("iterator_id").unwrap_or(0);
+        self.db_manager
+            .iterator_prev(iterator_id, req.read_flags())
+            .map(|result| Ok(Some(result)))
+            .unwrap_or_else(|e| Err(e.into()))
+    }
+
+    /**
+     * Returns the next entries of an iterator.
+     *
+     * This function handles the `iterator_next_batch` action which returns up to `limit` entries after the iterator's
+     * position and moves it to the last one. Iterators opened with `scan_open` walk in their direction and stay within
+     * their bounds; other iterators walk forward. The result is a JSON object `{"entries": [{"key": .., "value": ..}],
+     * "done": ..}`, where `done` means no entries are left.
+     *
+     * # Link: iterator_next_batch
+     *
+     * # Parameters
+     * - `options.iterator_id`: String - The iterator ID
+     * - `options.limit`: Option<usize> - The maximum number of entries to return (default 100)
+     * - `options.verify_checksums`: Option<bool> - Verify block checksums while reading (default true)
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The entries
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_iterator_next_batch(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!(
+            "handle_iterator_next_batch with iterator_id: {:?}, limit: {:?}",
+            req.parse_option::<usize>("iterator_id"),
+            req.parse_option::<usize>("limit")
+        );
+        let iterator_id = req.parse_option::<usize>("iterator_id").unwrap_or(0);
+        let limit = batch_limit(&req)?;
+
+        let (entries, done) = self
+            .db_manager
+            .iterator_next_batch(iterator_id, limit, req.read_flags())?;
+        Ok(Some(
+            serde_json::json!({
+                "entries": iterator_entries_json(entries),
+                "done": done,
+            })
+            .to_string(),
+        ))
+    }
+
+    /**
+     * Exports key-value pairs to a file on the server.
+     *
+     * This function handles the `export` action which writes every key-value pair of the database
+     * or a column family to a server-local NDJSON file, one `{"key": .., "value": ..}` object per line.
+     * Pairs that are not valid UTF-8 are written base64-encoded with `"base64": true`.
+     *
+     * # Link: export
+     *
+     * # Parameters
+     * - `options.path`: String - The file path on the server to write to
+     * - `options.prefix`: Option<String> - Only export keys starting with this prefix
+     * - `cf_name`: Option<String> - The column family name to export
+     * - `options.verify_checksums`: Option<bool> - Verify block checksums while reading (default true)
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The number of exported entries
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_export(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_export with options: {:?}", req.options);
+        let path = req
+            .parse_option::<String>("path")
+            .ok_or_else(|| RequestError::invalid_request("Path must be provided"))?;
+        let prefix = req.parse_option::<String>("prefix");
+
+        let db_manager = self.db_manager.clone();
+        let cf_name = req.cf_name.clone();
+        let flags = req.read_flags();
+        let count = spawn_blocking(move || db_manager.export(&path, cf_name, prefix, flags)).await?;
+        Ok(Some(count.to_string()))
+    }
+
+    /**
+     * Imports key-value pairs from a file on the server.
+     *
+     * This function handles the `import` action which reads a server-local NDJSON file in the `export` format
+     * and writes its pairs to the database in chunked write batches.
+     * Lines that fail to parse or carry a key longer than `--max-key-size` are skipped and reported instead of
+     * aborting the import.
+     *
+     * # Link: import
+     *
+     * # Parameters
+     * - `options.path`: String - The file path on the server to read from
+     * - `cf_name`: Option<String> - The column family name to import into
+     * - `options.overwrite`: Option<bool> - Overwrite keys that already exist (default true)
+     * - `options.batch_size`: Option<usize> - Number of entries written per batch (default 1000)
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - JSON report with `imported`, `skipped`, `failed` counts and the first parse `errors`
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_import(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_import with options: {:?}", req.options);
+        let path = req
+            .parse_option::<String>("path")
+            .ok_or_else(|| RequestError::invalid_request("Path must be provided"))?;
+        let overwrite = req.parse_option::<bool>("overwrite").unwrap_or(true);
+        let batch_size = req.parse_option::<usize>("batch_size").unwrap_or(1000);
+        if batch_size == 0 {
+            return Err(RequestError::invalid_request("Batch size must be greater than zero"));
+        }
+
+        // Queued puts are applied first, so they neither land over imported pairs nor hide from `overwrite: false`.
+        let db_manager = self.db_manager.clone();
+        let cf_name = req.cf_name.clone();
+        let max_key_size = self.max_key_size;
+        let report = self
+            .cache_layer
+            .write_and_clear_cf(
+                req.cf_name.clone(),
+                spawn_blocking(move || db_manager.import(&path, cf_name, overwrite, batch_size, max_key_size)),
+            )
+            .await?;
+
+        Ok(Some(serde_json::to_string(&report).unwrap()))
+    }
+
+    /**
+     * Creates a backup of the database.
+     *
+     * This function handles the `backup` action which creates a backup of the RocksDB database.
+     *
+     * # Link: backup
+     *
+     * # Parameters
+     * - None
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_backup(&self) -> Result<Option<String>, RequestError> {
+        debug!("handle_backup");
+        match self.db_manager.backup() {
+            Ok(_) => Ok(Some("Backup created successfully".to_string())),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /**
+     * Restores the database from the latest backup.
+     *
+     * This function handles the `restore_latest` action which restores the RocksDB database from the latest backup.
+     *
+     * # Link: restore_latest
+     *
+     * # Parameters
+     * - None
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_restore_latest(&self) -> Result<Option<String>, RequestError> {
+        debug!("handle_restore_latest");
+        match self.db_manager.restore_latest_backup() {
+            Ok(_) => Ok(Some("Database restored from latest backup".to_string())),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /**
+     * Restores the database from a specified backup.
+     *
+     * This function handles the `restore` action which restores the RocksDB database from a specified backup.
+     * The function requires the ID of the backup to restore.
+     *
+     * # Link: restore
+     *
+     * # Parameters
+     * - `options.backup_id`: String - The ID of the backup to restore
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_restore_request(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!(
+            "handle_restore_request with backup_id: {:?}",
+            req.parse_option::<u32>("backup_id")
+        );
+        let backup_id = req.parse_option::<u32>("backup_id").unwrap_or(0);
+        match self.db_manager.restore_backup(backup_id) {
+            Ok(_) => Ok(Some(format!("Database restored from backup {}", backup_id))),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /**
+     * Retrieves information about all backups.
+     *
+     * This function handles the `get_backup_info` action which retrieves information about all backups of the RocksDB database.
+     *
+     * # Link: get_backup_info
+     *
+     * # Parameters
+     * - None
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_get_backup_info(&self) -> Result<Option<String>, RequestError> {
+        debug!("handle_get_backup_info");
+        match self.db_manager.get_backup_info() {
+            Ok(info) => {
+                let result = serde_json::to_string(&info).unwrap();
+                Ok(Some(result))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /**
+     * Swaps a staging database into place.
+     *
+     * This function handles the `swap_db` action which replaces the live database with the one in a staging
+     * directory on the server, for example a backup restored there. The staging database is opened read-only
+     * first and rejected if it is broken or holds a key longer than `--max-key-size`; if the swapped database then
+     * fails to open, the current one is put back.
+     * Afterwards the staging directory holds the previous database, so swapping again undoes the swap.
+     *
+     * # Link: swap_db
+     *
+     * # Parameters
+     * - `options.path`: String - The staging directory on the server, on the same filesystem as the database
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_swap_db(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_swap_db with path: {:?}", req.parse_option::<String>("path"));
+
+        let path = req
+            .parse_option::<String>("path")
+            .ok_or_else(|| RequestError::invalid_request("Path must be provided"))?;
+
+        let db_manager = self.db_manager.clone();
+        let staging = path.clone();
+        let max_key_size = self.max_key_size;
+        spawn_blocking(move || db_manager.swap_db(&staging, max_key_size)).await?;
+        self.cache_layer.clear_all().await;
+
+        Ok(Some(format!("Database swapped with {}", path)))
+    }
+
+    /**
+     * Streams a backup over the connection.
+     *
+     * This function handles the `export_backup_stream` action which checkpoints the database and sends its files
+     * after the response line, so a backup can go straight to remote storage. `binary_length` gives the size of
+     * the stream: one frame per file of name length (u32), name, file length (u64) and contents, big-endian. The
+     * checkpoint hard-links the SST files, so it needs little disk and is deleted once sent.
+     *
+     * # Link: export_backup_stream
+     *
+     * # Parameters
+     * - None
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `binary_length`: Option<usize> - The number of stream bytes that follow the response line
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_export_backup_stream(&self) -> Result<FileStream, RequestError> {
+        debug!("handle_export_backup_stream");
+
+        let db_manager = self.db_manager.clone();
+        let stream = spawn_blocking(move || {
+            let dir = StreamDir::new(&db_manager.db_path, "export");
+            db_manager.create_checkpoint(dir.path())?;
+            FileStream::from_dir(dir)
+        })
+        .await?;
+
+        Ok(stream)
+    }
+
+    /**
+     * Restores a streamed backup.
+     *
+     * This function handles the `import_backup_stream` action which reads `options.length` bytes in the
+     * `export_backup_stream` format right after the request line, unpacks them next to the database and swaps
+     * them in like `swap_db`, which rejects a database with a key longer than `--max-key-size`. The previous
+     * database is deleted afterwards. A rejected stream is still read to the end, so the connection stays usable.
+     *
+     * # Link: import_backup_stream
+     *
+     * # Parameters
+     * - `options.length`: u64 - The number of stream bytes that follow the request line
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    pub(crate) async fn handle_import_backup_stream<R: Read + Unpin>(
+        &self,
+        reader: &mut R,
+        length: u64,
+    ) -> Result<Option<String>, RequestError> {
+        debug!("handle_import_backup_stream with length: {}", length);
+
+        let staging = StreamDir::new(&self.db_manager.db_path, "import");
+        if let Err(e) = std::fs::create_dir(staging.path()) {
+            backup_stream::skip(reader, length).await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to create {}: {}", staging.path().display(), e).into());
+        }
+        backup_stream::unpack(reader, length, staging.path())
+            .await
+            .map_err(RequestError::invalid_request)?;
+
+        let db_manager = self.db_manager.clone();
+        let max_key_size = self.max_key_size;
+        spawn_blocking(move || {
+            let result = db_manager.swap_db(&staging.path().to_string_lossy(), max_key_size);
+            // The staging directory now holds the previous database.
+            drop(staging);
+            result
+        })
+        .await?;
+        self.cache_layer.clear_all().await;
+
+        Ok(Some("Backup imported".to_string()))
+    }
+
+    /**
+     * Creates a named snapshot.
+     *
+     * This function handles the `create_snapshot` action which pins the current state of the database under a
+     * name. `get` with `options.snapshot` then reads keys as they were at that moment. The snapshot keeps every
+     * value it can see from being compacted away, so it should be released once it is no longer needed.
+     * A connection may hold `--max-snapshots-per-connection` unreleased snapshots; creating more fails with
+     * `RESOURCE_LIMIT`.
+     *
+     * # Link: create_snapshot
+     *
+     * # Parameters
+     * - `options.name`: String - The snapshot name
+     * - `options.ttl`: Option<u64> - Release the snapshot automatically after this many seconds
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The snapshot as a JSON object
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_create_snapshot(
+        &self,
+        req: Request,
+        connection: &mut ConnectionState,
+    ) -> Result<Option<String>, RequestError> {
+        debug!("handle_create_snapshot with name: {:?}", req.parse_option::<String>("name"));
+
+        let name = req
+            .parse_option::<String>("name")
+            .ok_or_else(|| RequestError::invalid_request("Snapshot name must be provided"))?;
+        let ttl = req.parse_option::<u64>("ttl").map(Duration::from_secs);
+
+        let max_snapshots = self.limits.max_snapshots;
+        if max_snapshots > 0 {
+            // Snapshots released by name or by their ttl no longer count.
+            connection
+                .snapshots
+                .retain(|(name, id)| self.db_manager.is_snapshot_current(name, *id));
+            if connection.snapshots.len() >= max_snapshots {
+                return Err(RequestError::new(
+                    ErrorCode::ResourceLimit,
+                    format!("Snapshot limit of {} per connection reached", max_snapshots),
+                ));
+            }
+        }
+
+        let (id, info) = self.db_manager.create_snapshot(&name, ttl)?;
+        connection.snapshots.push((name.clone(), id));
+
+        if let Some(ttl) = ttl {
+            // A weak reference lets a dropped database go away without waiting for the timer.
+            let db_manager = Arc::downgrade(&self.db_manager);
+            spawn(async move {
+                sleep(ttl).await;
+                let Some(db_manager) = db_manager.upgrade() else {
+                    return;
+                };
+                match db_manager.release_snapshot_if_current(&name, id) {
+                    Ok(true) => debug!("Released snapshot {} after its ttl", name),
+                    Ok(false) => {}
+                    Err(e) => error!("Failed to release snapshot {} after its ttl: {}", name, e),
+                }
+            });
+        }
+
+        Ok(Some(serde_json::to_string(&info).unwrap()))
+    }
+
+    /**
+     * Releases a named snapshot.
+     *
+     * This function handles the `release_snapshot` action which drops a snapshot created with `create_snapshot`,
+     * letting compaction reclaim the data only it was keeping.
+     *
+     * # Link: release_snapshot
+     *
+     * # Parameters
+     * - `options.name`: String - The snapshot name
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The result of the operation
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_release_snapshot(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_release_snapshot with name: {:?}", req.parse_option::<String>("name"));
+
+        let name = req
+            .parse_option::<String>("name")
+            .ok_or_else(|| RequestError::invalid_request("Snapshot name must be provided"))?;
+
+        if self.db_manager.release_snapshot(&name)? {
+            Ok(None)
+        } else {
+            Err(RequestError::not_found(SNAPSHOT_NOT_FOUND))
+        }
+    }
 
     /**
-     * Seeks to a specific key in the iterator.
+     * Lists the named snapshots.
      *
-     * This function handles the `iterator_seek` action which seeks to a specified key in an existing iterator in the RocksDB database.
-     * The function requires the ID of the iterator, the key to seek, and the direction of the seek (Forward or Reverse).
+     * This function handles the `list_snapshots` action which returns every snapshot that has not been released,
+     * sorted by name, with the sequence number it was taken at and when it expires.
      *
-     * # Link: iterator_seek
+     * # Link: list_snapshots
      *
      * # Parameters
-     * - `options.iterator_id`: String - The iterator ID
-     * - `key`: String - The key to seek
+     * - None
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - The snapshots as a JSON array
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_list_snapshots(&self) -> Result<Option<String>, RequestError> {
+        debug!("handle_list_snapshots");
+        let snapshots = self.db_manager.list_snapshots()?;
+        Ok(Some(serde_json::to_string(&snapshots).unwrap()))
+    }
+
+    /**
+     * Begins a new transaction.
+     *
+     * This function handles the `begin_transaction` action which begins a new transaction in the RocksDB database.
+     * The transaction belongs to the connection that began it: it is rolled back if that connection closes, or if it
+     * is neither committed nor rolled back within 10 seconds. A connection holds one transaction at a time; beginning
+     * another before the first ends fails with `RESOURCE_LIMIT`.
+     *
+     * # Link: begin_transaction
+     *
+     * # Parameters
+     * - None
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
      * - `result`: Option<String> - The result of the operation
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_iterator_seek(
+    async fn handle_begin_transaction(
         &self,
-        req: Request,
-        direction: rust_rocksdb::Direction,
-    ) -> Result<Option<String>, String> {
-        debug!(
-            "handle_iterator_seek with iterator_id: {:?}, key: {:?}",
-            req.parse_option::<usize>("iterator_id"),
-            req.key
-        );
-        let key = req
-            .key
-            .clone()
-            .ok_or_else(|| "Key must be provided".to_string())?;
-        let iterator_id = req.parse_option::<usize>("iterator_id").unwrap_or(0);
+        connection: &mut ConnectionState,
+    ) -> Result<Option<String>, RequestError> {
+        debug!("handle_begin_transaction");
 
-        self.db_manager
-            .iterator_seek(iterator_id, key, direction)
-            .map(|result| Ok(Some(result)))
-            .unwrap_or_else(|e| Err(e))
+        // Waiting for the database's transaction to end would wait for this connection itself.
+        if connection
+            .transaction
+            .is_some_and(|id| self.db_manager.is_transaction_active(id))
+        {
+            return Err(RequestError::new(
+                ErrorCode::ResourceLimit,
+                "Transaction limit of 1 per connection reached",
+            ));
+        }
+
+        // Queued writes cannot reach RocksDB while the transaction DB replaces it.
+        self.cache_layer.flush().await;
+        match self.db_manager.begin_transaction() {
+            Ok(id) => {
+                connection.transaction = Some(id);
+                connection.kept_transaction_db = None;
+
+                // Roll back after 10 seconds unless the client finished the transaction meanwhile
+                let db_manager = self.db_manager.clone();
+                spawn(async move {
+                    sleep(Duration::from_secs(10)).await;
+                    match db_manager.rollback_transaction_if_active(id) {
+                        Ok(true) => warn!("Rolled back transaction {} after timeout", id),
+                        Ok(false) => {}
+                        Err(e) => error!("Failed to roll back transaction after timeout: {}", e),
+                    }
+                });
+
+                Ok(Some("Transaction started".to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
     }
 
     /**
-     * Advances the iterator to the next key.
+     * Commits an existing transaction.
      *
-     * This function handles the `iterator_next` action which advances an existing iterator to the next key in the RocksDB database.
-     * The function requires the ID of the iterator.
+     * This function handles the `commit_transaction` action which commits an existing transaction in the RocksDB database.
+     * Only the connection that began the transaction can commit it; on any other connection the request fails
+     * with `INVALID_REQUEST`.
      *
-     * # Link: iterator_next
+     * # Link: commit_transaction
      *
      * # Parameters
-     * - `options.iterator_id`: String - The iterator ID
+     * - `options.keep_open`: Option<bool> - Keep the transaction database open for the next `begin_transaction` instead of reopening the database (default false)
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
      * - `result`: Option<String> - The result of the operation
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_iterator_next(&self, req: Request) -> Result<Option<String>, String> {
-        debug!(
-            "handle_iterator_next with iterator_id: {:?}",
-            req.parse_option::<usize>("iterator_id")
-        );
-        let iterator_id = req.parse_option::<usize>("iterator_id").unwrap_or(0);
-        self.db_manager
-            .iterator_next(iterator_id)
-            .map(|result| Ok(Some(result)))
-            .unwrap_or_else(|e| Err(e))
+    async fn handle_commit_transaction(
+        &self,
+        req: Request,
+        connection: &mut ConnectionState,
+    ) -> Result<Option<String>, RequestError> {
+        debug!("handle_commit_transaction");
+
+        let keep_open = req.parse_option::<bool>("keep_open").unwrap_or(false);
+        let id = self.own_transaction(connection)?;
+        connection.transaction = None;
+        match self.db_manager.commit_transaction(keep_open) {
+            Ok(info) => {
+                if keep_open {
+                    self.release_kept_transaction_db(id, connection);
+                }
+                let result = serde_json::to_string(&info).unwrap();
+                Ok(Some(result))
+            }
+            Err(e) => Err(e.into()),
+        }
     }
 
     /**
-     * Moves the iterator to the previous key.
+     * Rolls back an existing transaction.
      *
-     * This function handles the `iterator_prev` action which moves an existing iterator to the previous key in the RocksDB database.
-     * The function requires the ID of the iterator.
+     * This function handles the `rollback_transaction` action which rolls back an existing transaction in the RocksDB database.
+     * Only the connection that began the transaction can roll back it; on any other connection the request fails
+     * with `INVALID_REQUEST`.
      *
-     * # Link: iterator_prev
+     * # Link: rollback_transaction
      *
      * # Parameters
-     * - `options.iterator_id`: String - The iterator ID
+     * - `options.keep_open`: Option<bool> - Keep the transaction database open for the next `begin_transaction` instead of reopening the database (default false)
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
      * - `result`: Option<String> - The result of the operation
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_iterator_prev(&self, req: Request) -> Result<Option<String>, String> {
-        debug!(
-            "handle_iterator_prev with iterator_id: {:?}",
-            req.parse_option::<usize>("iterator_id")
-        );
-        let iterator_id = req.parse_option::<usize>("iterator_id").unwrap_or(0);
-        self.db_manager
-            .iterator_prev(iterator_id)
-            .map(|result| Ok(Some(result)))
-            .unwrap_or_else(|e| Err(e))
+    async fn handle_rollback_transaction(
+        &self,
+        req: Request,
+        connection: &mut ConnectionState,
+    ) -> Result<Option<String>, RequestError> {
+        debug!("handle_rollback_transaction");
+
+        let keep_open = req.parse_option::<bool>("keep_open").unwrap_or(false);
+        let id = self.own_transaction(connection)?;
+        connection.transaction = None;
+        match self.db_manager.rollback_transaction(keep_open) {
+            Ok(info) => {
+                if keep_open {
+                    self.release_kept_transaction_db(id, connection);
+                }
+                let result = serde_json::to_string(&info).unwrap();
+                Ok(Some(result))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // The transaction this connection began, if it is still the active one; another connection's transaction
+    // cannot be committed or rolled back from here.
+    fn own_transaction(&self, connection: &mut ConnectionState) -> Result<u64, RequestError> {
+        match connection.transaction {
+            Some(id) if self.db_manager.is_transaction_active(id) => Ok(id),
+            _ => {
+                connection.transaction = None;
+                Err(RequestError::invalid_request("No active transaction was begun on this connection"))
+            }
+        }
+    }
+
+    // Reopens the database 10 seconds after a `keep_open` commit or rollback unless a transaction began meanwhile,
+    // so an idle transaction DB does not keep non-transactional requests out.
+    fn release_kept_transaction_db(&self, id: u64, connection: &mut ConnectionState) {
+        connection.kept_transaction_db = Some(id);
+
+        let db_manager = self.db_manager.clone();
+        spawn(async move {
+            sleep(Duration::from_secs(10)).await;
+            match db_manager.release_transaction_db(id) {
+                Ok(true) => debug!("Released the idle transaction DB of transaction {}", id),
+                Ok(false) => {}
+                Err(e) => error!("Failed to release the idle transaction DB: {}", e),
+            }
+        });
     }
 
     /**
-     * Creates a backup of the database.
+     * Answers a liveness probe.
      *
-     * This function handles the `backup` action which creates a backup of the RocksDB database.
+     * This function handles the `ping` action which returns `pong` without touching the database, so clients can
+     * check that a connection is still alive.
      *
-     * # Link: backup
+     * # Link: ping
      *
      * # Parameters
      * - None
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
-     * - `result`: Option<String> - The result of the operation
+     * - `result`: Option<String> - `pong`
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_backup(&self) -> Result<Option<String>, String> {
-        debug!("handle_backup");
-        match self.db_manager.backup() {
-            Ok(_) => Ok(Some("Backup created successfully".to_string())),
-            Err(e) => Err(e),
-        }
+    fn handle_ping(&self) -> Result<Option<String>, RequestError> {
+        Ok(Some("pong".to_string()))
     }
 
     /**
-     * Restores the database from the latest backup.
+     * Retrieves information about the running server.
      *
-     * This function handles the `restore_latest` action which restores the RocksDB database from the latest backup.
+     * This function handles the `info` action which returns the server version and the effective database settings as a JSON object.
      *
-     * # Link: restore_latest
+     * # Link: info
      *
      * # Parameters
      * - None
@@ -885,48 +3396,59 @@ impl RocksDBServer {
      * - `result`: Option<String> - The result of the operation
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_restore_latest(&self) -> Result<Option<String>, String> {
-        debug!("handle_restore_latest");
-        match self.db_manager.restore_latest_backup() {
-            Ok(_) => Ok(Some("Database restored from latest backup".to_string())),
-            Err(e) => Err(e),
-        }
+    async fn handle_info(&self) -> Result<Option<String>, RequestError> {
+        debug!("handle_info");
+
+        let info = serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "database": self.name,
+            "db_path": self.db_manager.db_path,
+            "parallelism": self.db_manager.parallelism,
+            "atomic_flush": self.db_manager.atomic_flush,
+            "pipelined_write": self.db_manager.pipelined_write,
+            "rate_limiter_bytes_per_sec": self.db_manager.rate_limiter_bytes_per_sec,
+            "compaction_style": self.db_manager.compaction.style,
+            "num_levels": self.db_manager.compaction.num_levels,
+            "fifo_max_size": self.db_manager.compaction.fifo_max_size,
+            "wal_dir": self.db_manager.wal_dir,
+            "db_paths": self.db_manager.db_paths,
+            "backup_path": self.db_manager.backup_path,
+            "idempotency_ttl": self.idempotency_keys.ttl().as_secs(),
+        });
+        Ok(Some(info.to_string()))
     }
 
     /**
-     * Restores the database from a specified backup.
+     * Retrieves the options a column family is running with.
      *
-     * This function handles the `restore` action which restores the RocksDB database from a specified backup.
-     * The function requires the ID of the backup to restore.
+     * This function handles the `get_options` action which returns the effective RocksDB options as a JSON object
+     * with `db_options`, `cf_options` and `table_options`, read from the OPTIONS file RocksDB keeps in the database
+     * directory. Changes made with `set_db_option` are included.
      *
-     * # Link: restore
+     * # Link: get_options
      *
      * # Parameters
-     * - `options.backup_id`: String - The ID of the backup to restore
+     * - `cf_name`: Option<String> - The column family name
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
      * - `result`: Option<String> - The result of the operation
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_restore_request(&self, req: Request) -> Result<Option<String>, String> {
-        debug!(
-            "handle_restore_request with backup_id: {:?}",
-            req.parse_option::<u32>("backup_id")
-        );
-        let backup_id = req.parse_option::<u32>("backup_id").unwrap_or(0);
-        match self.db_manager.restore_backup(backup_id) {
-            Ok(_) => Ok(Some(format!("Database restored from backup {}", backup_id))),
-            Err(e) => Err(e),
-        }
+    async fn handle_get_options(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_get_options with cf_name: {:?}", req.cf_name);
+
+        let options = self.db_manager.get_options(req.cf_name)?;
+        Ok(Some(serde_json::to_string(&options).unwrap()))
     }
 
     /**
-     * Retrieves information about all backups.
+     * Reports the state of the cache layer.
      *
-     * This function handles the `get_backup_info` action which retrieves information about all backups of the RocksDB database.
+     * This function handles the `cache_stats` action which returns whether the cache is enabled, the number of cached
+     * entries, the hits and misses of this database's cache since startup and the configured TTL as a JSON object.
      *
-     * # Link: get_backup_info
+     * # Link: cache_stats
      *
      * # Parameters
      * - None
@@ -936,99 +3458,393 @@ impl RocksDBServer {
      * - `result`: Option<String> - The result of the operation
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_get_backup_info(&self) -> Result<Option<String>, String> {
-        debug!("handle_get_backup_info");
-        match self.db_manager.get_backup_info() {
-            Ok(info) => {
-                let result = serde_json::to_string(&info).unwrap();
-                Ok(Some(result))
-            }
-            Err(e) => Err(e),
-        }
+    async fn handle_cache_stats(&self) -> Result<Option<String>, RequestError> {
+        debug!("handle_cache_stats");
+
+        let stats = serde_json::json!({
+            "enabled": self.cache_layer.enabled,
+            "entries": self.cache_layer.len().await,
+            "hits": self.cache_layer.hits(),
+            "misses": self.cache_layer.misses(),
+            "ttl_seconds": self.cache_layer.ttl().as_secs(),
+        });
+        Ok(Some(stats.to_string()))
     }
 
     /**
-     * Begins a new transaction.
+     * Drops every entry from the cache layer.
      *
-     * This function handles the `begin_transaction` action which begins a new transaction in the RocksDB database.
+     * This function handles the `cache_clear` action which empties the cache, so later reads go to RocksDB. Writes
+     * still waiting in the write-behind queue are not discarded. With the cache disabled nothing is cleared.
      *
-     * # Link: begin_transaction
+     * # Link: cache_clear
      *
      * # Parameters
      * - None
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
+     * - `result`: Option<String> - A JSON object `{"enabled": .., "cleared": ..}` with the number of dropped entries
+     * - `error`: Option<String> - Any error that occurred
+     */
+    async fn handle_cache_clear(&self) -> Result<Option<String>, RequestError> {
+        debug!("handle_cache_clear");
+
+        let cleared = self.cache_layer.clear_all().await;
+        Ok(Some(
+            serde_json::json!({ "enabled": self.cache_layer.enabled, "cleared": cleared }).to_string(),
+        ))
+    }
+
+    /**
+     * Writes, reads back and deletes a sentinel key.
+     *
+     * This function handles the `selftest` action which checks the full write and read path of RocksDB, bypassing
+     * the cache. The sentinel key lives under the reserved `__selftest__:` prefix and is deleted again. The result is
+     * a JSON object `{"ok": .., "write_ms": .., "read_ms": ..}`; `ok` is `false` if the value read back differs.
+     *
+     * # Link: selftest
+     *
+     * # Parameters
+     * - `cf_name`: Option<String> - The column family name
+     *
+     * # Returns
+     * - `success`: bool - Whether the operation was successful
      * - `result`: Option<String> - The result of the operation
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_begin_transaction(&self) -> Result<Option<String>, String> {
-        debug!("handle_begin_transaction");
+    async fn handle_selftest(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_selftest with cf_name: {:?}", req.cf_name);
 
-        match self.db_manager.begin_transaction() {
-            Ok(_) => {
-                // Schedule a commit after 10 seconds
-                let db_manager = self.db_manager.clone();
-                spawn(async move {
-                    sleep(Duration::from_secs(10)).await;
-                    if let Err(e) = db_manager.commit_transaction() {
-                        error!("Failed to commit transaction after timeout: {}", e);
-                    }
-                });
+        // Unique per request, so concurrent self-tests never read each other's sentinel.
+        let started_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+        let nonce = format!("{}-{}", started_at, NEXT_SELFTEST.fetch_add(1, Ordering::Relaxed));
+        let key = format!("{}{}", SELFTEST_PREFIX, nonce);
 
-                Ok(Some("Transaction started".to_string()))
-            }
-            Err(e) => Err(e),
+        let started = Instant::now();
+        self.db_manager
+            .put(key.clone(), nonce.clone(), req.cf_name.clone(), None, WriteFlags::default())
+            .map_err(|e| format!("Self-test write failed: {}", e))?;
+        let write_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        let started = Instant::now();
+        let value = self
+            .db_manager
+            .get(key.clone(), req.cf_name.clone(), None, None, ReadFlags::default());
+        let read_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        let deleted = self.db_manager.delete(key, req.cf_name, None);
+        let value = value.map_err(|e| format!("Self-test read failed: {}", e))?;
+        deleted.map_err(|e| format!("Self-test delete failed: {}", e))?;
+
+        let ok = value.as_deref() == Some(nonce.as_str());
+        let round = |ms: f64| (ms * 1000.0).round() / 1000.0;
+        let (write_ms, read_ms) = (round(write_ms), round(read_ms));
+        if !ok {
+            error!("Self-test read back {:?} instead of {:?}", value, nonce);
         }
+        Ok(Some(
+            serde_json::json!({ "ok": ok, "write_ms": write_ms, "read_ms": read_ms }).to_string(),
+        ))
     }
 
     /**
-     * Commits an existing transaction.
+     * Reports whether RocksDB is currently stalling writes.
      *
-     * This function handles the `commit_transaction` action which commits an existing transaction in the RocksDB database.
-     * The function requires the ID of the transaction to commit.
+     * This function handles the `write_health` action which returns the write stall state as a JSON object,
+     * so clients can back off before writes turn into latency.
      *
-     * # Link: commit_transaction
+     * # Link: write_health
+     *
+     * # Parameters
+     * - None
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
      * - `result`: Option<String> - The result of the operation
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_commit_transaction(&self) -> Result<Option<String>, String> {
-        debug!("handle_commit_transaction");
-
-        match self.db_manager.commit_transaction() {
-            Ok(info) => {
-                let result = serde_json::to_string(&info).unwrap();
-                Ok(Some(result))
-            }
-            Err(e) => Err(e),
-        }
+    async fn handle_write_health(&self) -> Result<Option<String>, RequestError> {
+        debug!("handle_write_health");
+
+        let health = self.db_manager.write_health()?;
+        METRICS.set_write_health(&self.name, health.delayed_write_rate, health.write_stopped);
+
+        let result = serde_json::json!({
+            "stalled": health.is_stalled(),
+            "write_stopped": health.write_stopped,
+            "delayed_write_rate": health.delayed_write_rate,
+        });
+        Ok(Some(result.to_string()))
     }
 
     /**
-     * Rolls back an existing transaction.
+     * Reports key count and storage size estimates.
      *
-     * This function handles the `rollback_transaction` action which rolls back an existing transaction in the RocksDB database.
-     * The function requires the ID of the transaction to roll back.
+     * This function handles the `storage_stats` action which returns a JSON object with `estimate_num_keys`,
+     * `live_data_size`, `total_sst_size` and `num_files_at_level`, the SST file count per level starting at level 0.
+     * A `total_sst_size` well above `live_data_size` means obsolete data is waiting for compaction.
      *
-     * # Link: rollback_transaction
+     * # Link: storage_stats
+     *
+     * # Parameters
+     * - `cf_name`: Option<String> - The column family name
      *
      * # Returns
      * - `success`: bool - Whether the operation was successful
      * - `result`: Option<String> - The result of the operation
      * - `error`: Option<String> - Any error that occurred
      */
-    async fn handle_rollback_transaction(&self) -> Result<Option<String>, String> {
-        debug!("handle_rollback_transaction");
+    async fn handle_storage_stats(&self, req: Request) -> Result<Option<String>, RequestError> {
+        debug!("handle_storage_stats with cf_name: {:?}", req.cf_name);
 
-        match self.db_manager.rollback_transaction() {
-            Ok(info) => {
-                let result = serde_json::to_string(&info).unwrap();
-                Ok(Some(result))
-            }
-            Err(e) => Err(e),
+        let stats = self.db_manager.storage_stats(req.cf_name)?;
+        Ok(Some(serde_json::to_string(&stats).unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestServer;
+    use async_std::task;
+    use rocksdb_client_rust::{RequestBuilder, RequestHandler};
+
+    #[test]
+    fn validate_cf_name_rejects_empty_names() {
+        let err = validate_cf_name("").unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidRequest);
+    }
+
+    #[test]
+    fn validate_cf_name_rejects_long_names() {
+        let err = validate_cf_name(&"a".repeat(1000)).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidRequest);
+        assert!(err.message.contains(&MAX_CF_NAME_LEN.to_string()));
+    }
+
+    #[test]
+    fn validate_cf_name_accepts_valid_names() {
+        assert!(validate_cf_name("users").is_ok());
+        assert!(validate_cf_name(&"a".repeat(MAX_CF_NAME_LEN)).is_ok());
+    }
+
+    #[test]
+    fn test_servers_are_isolated() {
+        let first = TestServer::start();
+        let second = TestServer::start();
+        assert_ne!(first.addr(), second.addr());
+
+        first.client().put("key".to_string(), "first".to_string(), None, None).unwrap();
+        assert_eq!(second.client().get("key".to_string(), None, None, None).unwrap(), None);
+        assert_eq!(first.client().ping().unwrap().as_deref(), Some("pong"));
+    }
+
+    #[test]
+    fn incr_and_decr_count_from_zero() {
+        let server = TestServer::start();
+        let mut client = server.client();
+        assert_eq!(client.incr("counter".to_string(), None, None).unwrap().as_deref(), Some("1"));
+        assert_eq!(client.incr("counter".to_string(), Some(10), None).unwrap().as_deref(), Some("11"));
+        assert_eq!(client.decr("counter".to_string(), Some(5), None).unwrap().as_deref(), Some("6"));
+        assert_eq!(client.get("counter".to_string(), None, None, None).unwrap().as_deref(), Some("6"));
+    }
+
+    #[test]
+    fn getset_returns_the_previous_value() {
+        let server = TestServer::start();
+        let mut client = server.client();
+        assert_eq!(client.getset("key".to_string(), "a".to_string(), None, None).unwrap(), None);
+        assert_eq!(client.getset("key".to_string(), "b".to_string(), None, None).unwrap().as_deref(), Some("a"));
+        assert_eq!(client.get("key".to_string(), None, None, None).unwrap().as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn wait_for_key_rejects_timeouts_above_the_maximum() {
+        let server = TestServer::start();
+        let mut handler = RequestHandler::new(server.addr().ip().to_string(), server.addr().port());
+        let request = RequestBuilder::new("wait_for_key")
+            .key(Some("missing".to_string()))
+            .option("timeout_ms".to_string(), u64::MAX.to_string())
+            .build();
+
+        let response = handler.send_request(request).unwrap();
+        assert_eq!(response.error_code.as_deref(), Some("INVALID_REQUEST"));
+    }
+
+    #[test]
+    fn copy_and_rename_move_values() {
+        let server = TestServer::start();
+        let mut client = server.client();
+        client.put("a".to_string(), "value".to_string(), None, None).unwrap();
+        client.copy("a".to_string(), "b".to_string(), None, None).unwrap();
+        client.rename("a".to_string(), "c".to_string(), None).unwrap();
+
+        assert_eq!(client.get("a".to_string(), None, None, None).unwrap(), None);
+        assert_eq!(client.get("b".to_string(), None, None, None).unwrap().as_deref(), Some("value"));
+        assert_eq!(client.get("c".to_string(), None, None, None).unwrap().as_deref(), Some("value"));
+    }
+
+    #[test]
+    fn delete_if_only_deletes_the_expected_value() {
+        let server = TestServer::start();
+        let mut client = server.client();
+        client.put("key".to_string(), "a".to_string(), None, None).unwrap();
+        client.delete_if("key".to_string(), "b".to_string(), None, None).unwrap();
+        assert_eq!(client.get("key".to_string(), None, None, None).unwrap().as_deref(), Some("a"));
+
+        client.delete_if("key".to_string(), "a".to_string(), None, None).unwrap();
+        assert_eq!(client.get("key".to_string(), None, None, None).unwrap(), None);
+    }
+
+    #[test]
+    fn selftest_passes_and_cleans_up() {
+        let server = TestServer::start();
+        let mut client = server.client();
+        let report: Value = serde_json::from_str(&client.selftest(None).unwrap().unwrap()).unwrap();
+        assert_eq!(report["ok"], Value::Bool(true));
+
+        let keys = client.get_range_map(Some(SELFTEST_PREFIX.to_string()), None, None, None, None).unwrap();
+        assert_eq!(keys.as_deref(), Some("{}"));
+    }
+
+    #[test]
+    fn iterator_next_then_prev_returns_to_the_start() {
+        let server = TestServer::start();
+        let mut client = server.client();
+        for key in ["a", "b", "c", "d", "e"] {
+            client.put(key.to_string(), key.to_uppercase(), None, None).unwrap();
+        }
+        let id = client.create_iterator().unwrap().unwrap();
+
+        assert_eq!(client.iterator_seek(id.clone(), "b".to_string()).unwrap().as_deref(), Some("b:B"));
+        assert_eq!(client.iterator_next(id.clone()).unwrap().as_deref(), Some("c:C"));
+        assert_eq!(client.iterator_next(id.clone()).unwrap().as_deref(), Some("d:D"));
+        assert_eq!(client.iterator_prev(id.clone()).unwrap().as_deref(), Some("c:C"));
+        assert_eq!(client.iterator_prev(id).unwrap().as_deref(), Some("b:B"));
+    }
+
+    #[test]
+    fn dropping_the_default_column_family_is_refused() {
+        let server = TestServer::start();
+        let mut handler = RequestHandler::new(server.addr().ip().to_string(), server.addr().port());
+        let request = RequestBuilder::new("drop_column_family")
+            .cf_name(Some(DEFAULT_COLUMN_FAMILY_NAME.to_string()))
+            .build();
+
+        let response = handler.send_request(request).unwrap();
+        assert!(!response.success);
+        assert_eq!(response.error_code.as_deref(), Some("INVALID_REQUEST"));
+        assert_eq!(response.result.as_deref(), Some("Cannot drop the default column family"));
+    }
+
+    fn cached_server() -> TestServer {
+        TestServer::with_config(ServerConfig {
+            cache_enabled: true,
+            ..ServerConfig::default()
+        })
+    }
+
+    fn cache_hits(client: &mut rocksdb_client_rust::RocksDBClient) -> u64 {
+        let stats: Value = serde_json::from_str(&client.cache_stats().unwrap().unwrap()).unwrap();
+        stats["hits"].as_u64().unwrap()
+    }
+
+    #[test]
+    fn merge_in_another_column_family_keeps_the_default_cache_entry() {
+        let server = cached_server();
+        let mut client = server.client();
+        let other = Some("other".to_string());
+        client.create_column_family("other".to_string()).unwrap();
+        client.put("doc".to_string(), r#"{"cf":"default"}"#.to_string(), None, None).unwrap();
+        client.put("doc".to_string(), r#"{"cf":"other"}"#.to_string(), other.clone(), None).unwrap();
+        client.get("doc".to_string(), None, None, None).unwrap();
+
+        let patch = r#"[{"op":"add","path":"/merged","value":true}]"#;
+        client.merge("doc".to_string(), patch.to_string(), other.clone(), None, None).unwrap();
+
+        let hits = cache_hits(&mut client);
+        assert_eq!(client.get("doc".to_string(), None, None, None).unwrap().as_deref(), Some(r#"{"cf":"default"}"#));
+        assert_eq!(cache_hits(&mut client), hits + 1);
+        assert_eq!(
+            client.get("doc".to_string(), other, None, None).unwrap().as_deref(),
+            Some(r#"{"cf":"other","merged":true}"#)
+        );
+    }
+
+    #[test]
+    fn write_batch_write_replaces_cached_values() {
+        let server = cached_server();
+        let mut client = server.client();
+        client.put("key".to_string(), "cached".to_string(), None, None).unwrap();
+        client.put("gone".to_string(), "cached".to_string(), None, None).unwrap();
+        assert_eq!(client.get("key".to_string(), None, None, None).unwrap().as_deref(), Some("cached"));
+        assert_eq!(client.get("gone".to_string(), None, None, None).unwrap().as_deref(), Some("cached"));
+
+        client.write_batch_put("key".to_string(), "batched".to_string(), None).unwrap();
+        client.write_batch_delete("gone".to_string(), None).unwrap();
+        client.write_batch_write().unwrap();
+
+        assert_eq!(client.get("key".to_string(), None, None, None).unwrap().as_deref(), Some("batched"));
+        assert_eq!(client.get("gone".to_string(), None, None, None).unwrap(), None);
+    }
+
+    #[test]
+    fn scan_stream_yields_the_prefix_in_batches() {
+        use futures::StreamExt;
+
+        let server = TestServer::start();
+        let mut client = server.client();
+        for key in ["a", "p:1", "p:2", "p:3", "p:4", "p:5", "q"] {
+            client.put(key.to_string(), key.to_uppercase(), None, None).unwrap();
         }
+
+        let stream = client.scan_stream("p:".to_string()).batch_size(2);
+        let entries: Vec<_> = task::block_on(stream.collect::<Vec<_>>());
+        let keys: Vec<String> = entries.into_iter().map(|entry| entry.unwrap().0).collect();
+        assert_eq!(keys, ["p:1", "p:2", "p:3", "p:4", "p:5"]);
+    }
+
+    #[test]
+    fn file_and_bulk_delete_actions_need_the_admin_address() {
+        let server = TestServer::with_config(ServerConfig {
+            admin_port: true,
+            ..ServerConfig::default()
+        });
+        let mut handler = RequestHandler::new(server.addr().ip().to_string(), server.addr().port());
+        for action in ["scan_delete", "import", "export"] {
+            let request = RequestBuilder::new(action).option("path".to_string(), "/tmp/unused".to_string()).build();
+            let response = handler.send_request(request).unwrap();
+            assert_eq!(response.error_code.as_deref(), Some("UNAUTHORIZED"), "{}", action);
+        }
+    }
+
+    #[test]
+    fn import_applies_queued_cache_writes_first() {
+        let server = cached_server();
+        let mut client = server.client();
+        let file = crate::test_support::TempDir::new();
+        let path = file.join("import.ndjson");
+        std::fs::write(&path, "{\"key\":\"pending\",\"value\":\"imported\"}\n").unwrap();
+
+        client.put("pending".to_string(), "queued".to_string(), None, None).unwrap();
+        let report: Value = serde_json::from_str(&client.import(path, None, Some(false), None).unwrap().unwrap()).unwrap();
+        assert_eq!(report["skipped"], 1, "{}", report);
+        assert_eq!(client.get("pending".to_string(), None, None, None).unwrap().as_deref(), Some("queued"));
+    }
+
+    #[test]
+    fn transactions_end_only_on_the_connection_that_began_them() {
+        let server = TestServer::start();
+        let mut owner = server.client();
+        let mut other = server.client();
+        owner.begin_transaction().unwrap();
+
+        let refused = "No active transaction was begun on this connection";
+        assert_eq!(other.commit_transaction().unwrap_err(), refused);
+        assert_eq!(other.rollback_transaction().unwrap_err(), refused);
+        owner.put("key".to_string(), "value".to_string(), None, Some(true)).unwrap();
+        owner.commit_transaction().unwrap();
+        assert_eq!(other.get("key".to_string(), None, None, None).unwrap().as_deref(), Some("value"));
     }
 }
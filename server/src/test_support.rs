@@ -0,0 +1,112 @@
+//! In-process server for tests: a database in its own temporary directory, listening on an ephemeral port.
+
+use crate::databases::Databases;
+use crate::handle_incoming_connections;
+use crate::helpers::ConnectionLimiter;
+use crate::server::ServerConfig;
+use async_std::net::TcpListener;
+use async_std::sync::Arc;
+use async_std::task::{self, JoinHandle};
+use rocksdb_client_rust::RocksDBClient;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_DIR: AtomicU64 = AtomicU64::new(0);
+
+/// A fresh directory under the system temp directory, deleted with everything in it when dropped.
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub(crate) fn new() -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "rocksdb-server-test-{}-{}",
+            std::process::id(),
+            NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("failed to create the test directory");
+        TempDir { path }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of `name` inside the directory, as the `String` the database constructors take.
+    pub(crate) fn join(&self, name: &str) -> String {
+        self.path.join(name).to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A running server; dropping it stops accepting connections, then closes the database before its directory goes.
+pub(crate) struct TestServer {
+    addr: SocketAddr,
+    // Deleted after the database is closed, as fields drop after `Drop::drop`.
+    _dir: TempDir,
+    databases: Arc<Databases>,
+    accept_task: Option<JoinHandle<()>>,
+}
+
+impl TestServer {
+    pub(crate) fn start() -> Self {
+        Self::with_config(ServerConfig::default())
+    }
+
+    pub(crate) fn with_config(config: ServerConfig) -> Self {
+        let dir = TempDir::new();
+        let databases = Arc::new(
+            Databases::open(dir.path().to_string_lossy().into_owned(), config)
+                .expect("failed to open the test database"),
+        );
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("failed to bind an ephemeral port");
+        let addr = listener.local_addr().expect("failed to read the bound address");
+        let accept_task = task::spawn(handle_incoming_connections(
+            TcpListener::from(listener),
+            false,
+            databases.clone(),
+            Arc::new(ConnectionLimiter::new(0)),
+            false,
+            None,
+            false,
+        ));
+
+        TestServer {
+            addr,
+            _dir: dir,
+            databases,
+            accept_task: Some(accept_task),
+        }
+    }
+
+    pub(crate) fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// A client connected to this server.
+    pub(crate) fn client(&self) -> RocksDBClient {
+        RocksDBClient::builder()
+            .host(self.addr.ip().to_string())
+            .port(self.addr.port())
+            .build()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(accept_task) = self.accept_task.take() {
+            task::block_on(accept_task.cancel());
+        }
+        if let Err(e) = self.databases.close() {
+            eprintln!("Failed to close the test database: {}", e);
+        }
+    }
+}
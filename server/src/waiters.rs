@@ -0,0 +1,42 @@
+use async_std::channel::{bounded, Receiver, Sender};
+use async_std::sync::Mutex;
+use std::collections::HashMap;
+
+type WaiterKey = (String, Option<String>);
+
+/// One-shot waiters registered by `wait_for_key`, woken by the next write to their key.
+#[derive(Default)]
+pub(crate) struct KeyWaiters {
+    waiters: Mutex<HashMap<WaiterKey, Vec<Sender<()>>>>,
+}
+
+impl KeyWaiters {
+    pub(crate) async fn register(&self, key: &str, cf_name: Option<String>) -> Receiver<()> {
+        let (sender, receiver) = bounded(1);
+        let mut waiters = self.waiters.lock().await;
+
+        // Drop waiters whose request already timed out.
+        waiters.retain(|_, senders| {
+            senders.retain(|sender| !sender.is_closed());
+            !senders.is_empty()
+        });
+
+        waiters
+            .entry((key.to_string(), cf_name))
+            .or_default()
+            .push(sender);
+        receiver
+    }
+
+    pub(crate) async fn notify(&self, key: &str, cf_name: Option<String>) {
+        let senders = self
+            .waiters
+            .lock()
+            .await
+            .remove(&(key.to_string(), cf_name));
+
+        for sender in senders.into_iter().flatten() {
+            let _ = sender.try_send(());
+        }
+    }
+}